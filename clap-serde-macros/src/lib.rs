@@ -0,0 +1,76 @@
+/*!
+Macros of [clap-serde](https://crates.io/crates/clap-serde), which load a config at compile time.
+
+The features `toml` (enabled by default), `serde_yaml`, `serde_json`, `ron`, `json5` and `kdl` enable the formats of the config,
+and `env` and `color` enable the keys needing them as in clap-serde.
+*/
+
+#[cfg(not(any(
+    feature = "toml",
+    feature = "serde_yaml",
+    feature = "serde_json",
+    feature = "ron",
+    feature = "json5",
+    feature = "kdl"
+)))]
+compile_error!(
+    "Format feature is missing. Either toml, serde_yaml, serde_json, ron, json5 or kdl should be set."
+);
+
+use proc_macro::TokenStream;
+use quote::quote;
+use std::path::Path;
+use syn::{parse_macro_input, LitStr};
+
+/**
+Load a config file at compile time, and expand to the builder code making the [`Command<'static>`](https://docs.rs/clap/3/clap/type.Command.html).
+
+The path is relative to the directory of `Cargo.toml` (`CARGO_MANIFEST_DIR`),
+and the format is by its extension as [`clap_serde::from_path`].
+The command is checked as [`clap_serde::load_checked`], and the error of the config fails to compile
+at the path given to the macro.
+The clashing arg ids and flags are found in all builds, and the other debug assertions of clap
+only when the macro is built with `debug_assertions` (as proc macros are in the dev profile).
+
+The expanded code refers to `clap` (of version 3), which needs to be a dependency of the crate using the macro.
+The keys handled by [`clap_serde::KeyHandler`]s can't be used, as the macro has no handler.
+```
+let app: clap::Command = clap_serde_macros::include_command!("tests/cli.toml");
+let matches = app.get_matches_from(["app", "-vv", "build", "--release"]);
+assert_eq!(matches.get_count("verbose"), 2);
+```
+```compile_fail
+let app: clap::Command = clap_serde_macros::include_command!("tests/invalid.toml");
+```
+*/
+#[proc_macro]
+pub fn include_command(input: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(input as LitStr);
+    match expand(&path) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(path: &LitStr) -> syn::Result<proc_macro2::TokenStream> {
+    let error = |message: String| syn::Error::new(path.span(), message);
+    let dir = std::env::var("CARGO_MANIFEST_DIR").map_err(|e| error(e.to_string()))?;
+    let file = Path::new(&dir).join(path.value());
+    let builder = clap_serde::from_path(&file)
+        .and_then(|source| source.builder())
+        .map_err(|e| error(format!("{:#}", e)))?;
+    let code: proc_macro2::TokenStream = builder
+        .to_string()
+        .parse()
+        .map_err(|e: proc_macro2::LexError| error(e.to_string()))?;
+    let file = file
+        .to_str()
+        .ok_or_else(|| error(format!("path is not utf-8: {}", file.display())))?;
+    Ok(quote! {{
+        // Rebuild when the config changes.
+        const _: &[u8] = include_bytes!(#file);
+        #[allow(deprecated, clippy::reversed_empty_ranges)]
+        let command: clap::Command<'static> = #code;
+        command
+    }})
+}
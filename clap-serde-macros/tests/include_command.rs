@@ -0,0 +1,44 @@
+use clap::Command;
+
+/// The root is loaded renamed from a temporary name, so only its id differs.
+fn without_root_id(app: &Command) -> String {
+    let debug = format!("{:?}", app);
+    debug[debug.find(", name:").expect("name field")..].to_owned()
+}
+
+#[test]
+fn same_as_loaded() {
+    let app: Command = clap_serde_macros::include_command!("tests/cli.toml");
+    let source = clap_serde::from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cli.toml"))
+        .expect("read failed");
+    let loaded = source.command().expect("load failed");
+    assert_eq!(without_root_id(&app), without_root_id(&loaded));
+}
+
+#[test]
+fn parses_args() {
+    let app: Command = clap_serde_macros::include_command!("tests/cli.toml");
+    let matches = app
+        .try_get_matches_from(["app", "-vv", "--level", "4", "build", "-j", "2"])
+        .expect("parse failed");
+    assert_eq!(matches.get_count("verbose"), 2);
+    assert_eq!(matches.get_one::<u8>("level"), Some(&4));
+    let (name, sub) = matches.subcommand().expect("subcommand");
+    assert_eq!(name, "build");
+    assert_eq!(sub.get_one::<u32>("jobs"), Some(&2));
+
+    let app: Command = clap_serde_macros::include_command!("tests/cli.toml");
+    assert!(app.try_get_matches_from(["app", "--level", "6"]).is_err());
+}
+
+#[cfg(feature = "kdl")]
+#[test]
+fn kdl_config() {
+    let app: Command = clap_serde_macros::include_command!("tests/cli.kdl");
+    let matches = app
+        .try_get_matches_from(["app", "-vv", "build", "-j", "2"])
+        .expect("parse failed");
+    assert_eq!(matches.get_count("verbose"), 2);
+    let sub = matches.subcommand_matches("build").expect("subcommand");
+    assert_eq!(sub.get_one::<u32>("jobs"), Some(&2));
+}
@@ -0,0 +1,166 @@
+//! Command line tool for configs of clap-serde.
+
+use clap_serde::{
+    migrate::{migrate, Format},
+    CommandWrap, LoadOptions, Severity, Source,
+};
+use std::process::exit;
+
+const CLI_YAML: &str = r#"
+name: clap-serde
+about: Tools for configs of clap-serde
+subcommand_required: true
+arg_required_else_help: true
+subcommands:
+    - migrate:
+        about: Rewrite deprecated keys in a config to the current keys
+        args:
+            - file:
+                help: Config file to migrate
+                required: true
+            - format:
+                long: format
+                takes_value: true
+                possible_values: [toml, yaml, json, ron, json5, kdl]
+                help: Format of the config, guessed from the extension or the content if not set
+            - write:
+                short: w
+                long: write
+                help: Overwrite the file instead of printing the result
+            - check:
+                long: check
+                conflicts_with: write
+                help: Exit with 1 if the config has deprecated keys
+    - check:
+        about: Load a config, printing the errors, warnings and conflicting flags
+        args:
+            - file:
+                help: Config file to check
+                required: true
+            - format:
+                long: format
+                takes_value: true
+                possible_values: [toml, yaml, json, ron, json5, kdl]
+                help: Format of the config, guessed from the extension or the content if not set
+    - codegen:
+        about: Print the Rust code building the command of a config
+        args:
+            - file:
+                help: Config file to convert
+                required: true
+            - format:
+                long: format
+                takes_value: true
+                possible_values: [toml, yaml, json, ron, json5, kdl]
+                help: Format of the config, guessed from the extension or the content if not set
+            - derive:
+                long: derive
+                help: Print the types deriving clap::Parser instead of the builder methods
+"#;
+
+fn main() {
+    let app: CommandWrap = serde_yaml::from_str(CLI_YAML).expect("invalid cli config");
+    let matches = clap::Command::from(app)
+        .version(env!("CARGO_PKG_VERSION"))
+        .get_matches();
+    match matches.subcommand() {
+        Some(("migrate", m)) => migrate_file(m),
+        Some(("check", m)) => check_file(m),
+        Some(("codegen", m)) => codegen_file(m),
+        _ => unreachable!("subcommand is required"),
+    }
+}
+
+fn read_file(m: &clap::ArgMatches) -> (String, Format) {
+    let file = m.value_of("file").expect("required");
+    let src = std::fs::read_to_string(file).unwrap_or_else(|e| fail(e));
+    let format = match m.value_of("format") {
+        Some(name) => format_of(name),
+        None => Format::from_path(file).unwrap_or_else(|| Format::sniff(&src)),
+    };
+    (src, format)
+}
+
+/// Format of a name given to `--format`, failing if its feature is not enabled.
+fn format_of(name: &str) -> Format {
+    match name {
+        "toml" => Format::Toml,
+        "yaml" => Format::Yaml,
+        "json" => Format::Json,
+        #[cfg(feature = "ron")]
+        "ron" => Format::Ron,
+        #[cfg(feature = "json5")]
+        "json5" => Format::Json5,
+        #[cfg(feature = "kdl")]
+        "kdl" => Format::Kdl,
+        _ => fail(format_args!("the `{}` feature is not enabled", name)),
+    }
+}
+
+fn migrate_file(m: &clap::ArgMatches) {
+    let file = m.value_of("file").expect("required");
+    let (src, format) = read_file(m);
+    let migrated = migrate(&src, format).unwrap_or_else(|e| fail(e));
+    for note in migrated.notes() {
+        eprintln!("{}", note);
+    }
+    if m.is_present("check") {
+        exit(migrated.is_changed().into());
+    } else if m.is_present("write") {
+        if migrated.is_changed() {
+            std::fs::write(file, migrated.text()).unwrap_or_else(|e| fail(e));
+        }
+    } else {
+        print!("{}", migrated.text());
+    }
+}
+
+fn check_file(m: &clap::ArgMatches) {
+    let (src, format) = read_file(m);
+    let source = Source::new(src, format);
+    let (app, diagnostics) = source
+        .command_with_options(&LoadOptions::new())
+        .unwrap_or_else(|e| {
+            eprintln!("error: {:#}", e);
+            exit(1)
+        });
+    let mut failed = false;
+    for diagnostic in diagnostics {
+        let level = match diagnostic.severity {
+            Severity::Warning => "warning",
+            Severity::Error => {
+                failed = true;
+                "error"
+            }
+        };
+        eprintln!("{}: {}", level, diagnostic);
+    }
+    for conflict in clap_serde::find_conflicts(&app) {
+        failed = true;
+        eprintln!("error: {}", conflict);
+    }
+    exit(failed.into())
+}
+
+fn codegen_file(m: &clap::ArgMatches) {
+    let (src, format) = read_file(m);
+    if m.is_present("derive") {
+        let app = clap_serde::from_str_with_format(&src, format).unwrap_or_else(|e| {
+            eprintln!("error: {:#}", e);
+            exit(1)
+        });
+        print!("{}", clap_serde::codegen::to_derive_rust(&app));
+    } else {
+        // Recorded while loading, which keeps the keys without getters in clap.
+        let builder = Source::new(src, format).builder().unwrap_or_else(|e| {
+            eprintln!("error: {:#}", e);
+            exit(1)
+        });
+        println!("{}", builder);
+    }
+}
+
+fn fail(e: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", e);
+    exit(2)
+}
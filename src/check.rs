@@ -0,0 +1,174 @@
+use crate::find_conflicts;
+use clap::{Arg, Command};
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Once;
+
+/// Keys named by the messages of clap's debug assertions, checked in order.
+const KEYWORDS: &[(&str, &str)] = &[
+    ("index", "index"),
+    ("last(true)", "last"),
+    ("global", "global"),
+    ("required_if_eq_all", "required_if_eq_all"),
+    ("required_if_eq", "required_if_eq"),
+    ("required_unless", "required_unless_present"),
+    ("requires", "requires"),
+    ("conflicts_with", "conflicts_with"),
+    ("conflict with itself", "conflicts_with"),
+    ("hint", "value_hint"),
+    ("failed validation", "default_value"),
+    ("multiple_values", "multiple_values"),
+    ("validator", "validator"),
+    ("short", "short"),
+    ("long", "long"),
+    ("required", "required"),
+];
+
+/// Check `app` as clap does before parsing, returning the message of the failure.
+///
+/// Duplicate arg ids, and flags and subcommand names taken more than once as by [`find_conflicts`], are found in all builds.
+/// The other assertions of clap, like the `requires` of a missing arg, run only in the builds
+/// with `debug_assertions` (and `panic = "unwind"`), as clap has them only there.
+/// The message is prefixed by the path of the key found in it, like `subcommands.test.args.debug.index`.
+pub(crate) fn check(app: &Command) -> Result<(), String> {
+    check_ids(app, String::new())?;
+    if let Some(conflict) = find_conflicts(app).into_iter().next() {
+        return Err(format!("{}: {}", conflict.second, conflict));
+    }
+    let cloned = app.clone();
+    let payload = match catch_quietly(|| cloned.debug_assert()) {
+        Ok(()) => return Ok(()),
+        Err(payload) => payload,
+    };
+    let message = payload
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| payload.downcast_ref::<&str>().copied())
+        .unwrap_or("debug assertion failed")
+        .to_owned();
+    Err(match locate(app, &message) {
+        Some(path) => format!("{}: {}", path, message),
+        None => message,
+    })
+}
+
+thread_local! {
+    /// Whether the panic hook is skipped for the panics of this thread, which are caught.
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f`, catching its panic without the panic hook printing it.
+///
+/// The hook is wrapped once, so that it is still called for the panics of the other threads.
+fn catch_quietly<R>(f: impl FnOnce() -> R) -> std::thread::Result<R> {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if !QUIET.with(Cell::get) {
+                hook(info)
+            }
+        }));
+    });
+    let quiet = QUIET.with(|q| q.replace(true));
+    let result = catch_unwind(AssertUnwindSafe(f));
+    QUIET.with(|q| q.set(quiet));
+    result
+}
+
+/// Check that the ids of the args kept by clap in `command` and its subcommands are not used twice.
+fn check_ids(command: &Command, prefix: String) -> Result<(), String> {
+    let mut ids = HashSet::new();
+    for id in crate::conflicts::args(command).map(Arg::get_id) {
+        if !ids.insert(id) {
+            return Err(format!(
+                "{}args.{}.id: Command {}: arg id `{}` is used by more than one arg",
+                prefix,
+                id,
+                command.get_name(),
+                id
+            ));
+        }
+    }
+    for sub in command.get_subcommands() {
+        let prefix = format!("{}subcommands.{}.", prefix, sub.get_name());
+        check_ids(sub, prefix)?;
+    }
+    Ok(())
+}
+
+/// Path of the key the message is about, guessed from the names in it.
+fn locate(app: &Command, message: &str) -> Option<String> {
+    let words = message
+        .split(|c: char| c.is_whitespace() || "'`\":,(){}[]".contains(c))
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>();
+    // Messages start with the name of the command, if any.
+    let (command, prefix) = words
+        .iter()
+        .take(2)
+        .find_map(|w| find_command(app, w, String::new()))
+        .unwrap_or((app, String::new()));
+    // The ids are quoted, and so are some of the keys.
+    let quoted = quoted(message);
+    let is_arg = |w: &str| command.get_arguments().any(|a| a.get_id() == w);
+    let arg = quoted.iter().chain(&words).find(|w| is_arg(w));
+    let path = match arg {
+        Some(id) => {
+            // Look for the keys out of the quoted ids, so that an id like `indexer` is not the key `index`.
+            let mut rest = message.to_owned();
+            for id in quoted.iter().filter(|w| is_arg(w)) {
+                for (open, close) in [('\'', '\''), ('`', '`'), ('\'', '`'), ('`', '\'')] {
+                    rest = rest.replace(&format!("{}{}{}", open, id, close), "");
+                }
+            }
+            let key = KEYWORDS
+                .iter()
+                .find(|(k, _)| has_word(&rest, k))
+                .map(|(_, key)| format!(".{}", key))
+                .unwrap_or_default();
+            format!("{}args.{}{}", prefix, id, key)
+        }
+        None => prefix.trim_end_matches('.').to_owned(),
+    };
+    (!path.is_empty()).then_some(path)
+}
+
+/// The strings in `'` or `` ` `` quotes of `message`, which clap mixes up like `'{}``.
+fn quoted(message: &str) -> Vec<&str> {
+    let mut quoted = Vec::new();
+    let mut rest = message;
+    while let Some(start) = rest.find(['\'', '`']) {
+        let inner = &rest[start + 1..];
+        let Some(end) = inner.find(['\'', '`']) else {
+            break;
+        };
+        quoted.push(&inner[..end]);
+        rest = &inner[end + 1..];
+    }
+    quoted
+}
+
+/// Whether `word` is in `text`, not as a part of a longer word.
+fn has_word(text: &str, word: &str) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(word).any(|(i, _)| {
+        !text[..i].chars().next_back().is_some_and(is_word)
+            && !text[i + word.len()..].chars().next().is_some_and(is_word)
+    })
+}
+
+fn find_command<'c, 'a>(
+    command: &'c Command<'a>,
+    name: &str,
+    prefix: String,
+) -> Option<(&'c Command<'a>, String)> {
+    if command.get_name() == name {
+        return Some((command, prefix));
+    }
+    command.get_subcommands().find_map(|sub| {
+        let prefix = format!("{}subcommands.{}.", prefix, sub.get_name());
+        find_command(sub, name, prefix)
+    })
+}
@@ -0,0 +1,390 @@
+use crate::de::{ArgFrame, Context};
+use clap4::{
+    builder::{ArgPredicate, OsStr, PossibleValuesParser, Resettable, ValueRange},
+    Arg, Command,
+};
+use serde::de::{DeserializeSeed, Error, Visitor};
+use std::collections::HashSet;
+
+mod value_parser {
+    crate::de::value_parsers!(clap4);
+}
+
+use value_parser::ValueParser;
+
+enum_de!(AA, ArgAction,
+    #[derive(serde::Deserialize, Clone, Copy, Debug)]
+    #[cfg_attr(feature = "kebab-case-key" ,serde(rename_all = "kebab-case"))]
+    #[cfg_attr(feature = "snake-case-key" ,serde(rename_all = "snake_case"))]
+    {
+        Set,
+        Append,
+        SetTrue,
+        SetFalse,
+        Count,
+        Help,
+        HelpShort,
+        HelpLong,
+        Version,
+    }
+);
+
+use clap4::ArgAction as AA;
+
+enum_de!(VH,ValueHint,
+    #[derive(serde::Deserialize, Clone, Copy)]
+    #[cfg_attr(feature = "kebab-case-key" ,serde(rename_all = "kebab-case"))]
+    #[cfg_attr(feature = "snake-case-key" ,serde(rename_all = "snake_case"))]
+    {
+    Unknown,
+    Other,
+    AnyPath,
+    FilePath,
+    DirPath,
+    ExecutablePath,
+    CommandName,
+    CommandString,
+    CommandWithArguments,
+    Username,
+    Hostname,
+    Url,
+    EmailAddress,
+});
+
+use clap4::ValueHint as VH;
+
+/// Number of values, or a range of them like `1..`, `..=3` or `2..5`.
+struct NumArgs(ValueRange);
+
+impl<'de> serde::Deserialize<'de> for NumArgs {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(NumArgsVisitor)
+    }
+}
+
+struct NumArgsVisitor;
+
+impl<'de> Visitor<'de> for NumArgsVisitor {
+    type Value = NumArgs;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a number or a range like `1..` or `1..=3`")
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        usize::try_from(v)
+            .map(|v| NumArgs(v.into()))
+            .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        usize::try_from(v)
+            .map(|v| NumArgs(v.into()))
+            .map_err(|_| E::invalid_value(serde::de::Unexpected::Signed(v), &self))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        parse_range(v)
+            .map(NumArgs)
+            .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Str(v), &self))
+    }
+}
+
+/// Parse a range, which clap asserts to be not empty.
+fn parse_range(s: &str) -> Option<ValueRange> {
+    let s = s.trim();
+    if let Ok(n) = s.parse::<usize>() {
+        return Some(n.into());
+    }
+    let (start, end, inclusive) = match s.split_once("..=") {
+        Some((start, end)) => (start, end, true),
+        None => {
+            let (start, end) = s.split_once("..")?;
+            (start, end, false)
+        }
+    };
+    let start = match start.trim() {
+        "" => 0,
+        start => start.parse().ok()?,
+    };
+    let end = match (end.trim(), inclusive) {
+        ("", false) => usize::MAX,
+        ("", true) => return None,
+        (end, true) => end.parse().ok()?,
+        (end, false) => end.parse::<usize>().ok()?.checked_sub(1)?,
+    };
+    (start <= end).then(|| (start..=end).into())
+}
+
+/// Condition of `default_value_if`, which is the presence of the arg if no value is given.
+fn predicate(value: Option<String>) -> ArgPredicate {
+    match value {
+        Some(v) => v.into(),
+        None => ArgPredicate::IsPresent,
+    }
+}
+
+struct ArgKV<'de, 'c>(&'c mut Context<'de>);
+
+impl<'de, 'c> Visitor<'de> for ArgKV<'de, 'c> {
+    type Value = Arg;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("kv argument")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let name: &str = map
+            .next_key()?
+            .ok_or_else(|| A::Error::missing_field("argument"))?;
+        map.next_value_seed(ArgVisitor(name, self.0))
+    }
+}
+
+impl<'de, 'c> DeserializeSeed<'de> for ArgKV<'de, 'c> {
+    type Value = Arg;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+struct ArgVisitor<'a, 'c>(&'a str, &'c mut Context<'a>);
+
+impl<'a, 'c> Visitor<'a> for ArgVisitor<'a, 'c> {
+    type Value = Arg;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("Arg Map")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'a>,
+    {
+        let id = self.0;
+        let mut arg = Arg::new(id.to_owned());
+        let ctx = self.1;
+        let mut keys = HashSet::new();
+        let mut frame = ArgFrame::default();
+        ctx.arg_node(id)?;
+        ctx.push_path("args", id);
+
+        while let Some(key) = map.next_key::<&str>()? {
+            ctx.check_key(key, &mut keys)?;
+            arg = parse_value!(key, arg, map, Arg, {
+                    // action : specialized
+                    // alias : one_or_many
+                    (aliases, Vec<String>),
+                    (allow_hyphen_values, bool),
+                    (allow_negative_numbers, bool),
+                    // conflicts_with : one_or_many
+                    (conflicts_with_all, Vec<String>),
+                    // default_missing_value : one_or_many
+                    (default_missing_values, Vec<String>),
+                    (default_value, String),
+                    // default_value_if : specialized
+                    // default_value_ifs : specialized
+                    (default_values, Vec<String>),
+                    (display_order, usize),
+                    // env : specialized
+                    (exclusive, bool),
+                    (global, bool),
+                    // group : one_or_many
+                    (groups, Vec<String>),
+                    (help, String),
+                    (help_heading, String),
+                    (hide, bool),
+                    (hide_default_value, bool),
+                    // hide_env : specialized
+                    // hide_env_values : specialized
+                    (hide_long_help, bool),
+                    (hide_possible_values, bool),
+                    (hide_short_help, bool),
+                    (id, String),
+                    (ignore_case, bool),
+                    (index, usize),
+                    (last, bool),
+                    (long, String),
+                    (long_help, String),
+                    (next_line_help, bool),
+                    // num_args : specialized
+                    // overrides_with : one_or_many
+                    (overrides_with_all, Vec<String>),
+                    // possible_values : specialized
+                    (raw, bool),
+                    (require_equals, bool),
+                    (required, bool),
+                    // required_if_eq: tuple2
+                    (required_if_eq_all, Vec<(String, String)>),
+                    (required_if_eq_any, Vec<(String, String)>),
+                    (required_unless_present, String),
+                    (required_unless_present_any, Vec<String>),
+                    (required_unless_present_all, Vec<String>),
+                    // requires : one_or_many
+                    (requires_all, Vec<String>),
+                    // requires_if: tuple2
+                    (requires_ifs, Vec<(String, String)>),
+                    (short, char),
+                    // short_alias : one_or_many
+                    (short_aliases, Vec<char>),
+                    (trailing_var_arg, bool),
+                    // value_delimiter : specialized
+                    // value_hint : specialized
+                    // value_name : one_or_many
+                    (value_names, Vec<String>),
+                    // value_parser : specialized
+                    (value_terminator, String),
+                    // visible_alias : one_or_many
+                    (visible_aliases, Vec<String>),
+                    // visible_short_alias : one_or_many
+                    (visible_short_aliases, Vec<char>),
+                },
+                tuple2: {
+                    (required_if_eq, (String, String)),
+                    (requires_if, (String, String)),
+                },
+                one_or_many: {
+                    (alias, String) => aliases,
+                    (conflicts_with, String) => conflicts_with_all,
+                    (default_missing_value, String) => default_missing_values,
+                    (group, String) => groups,
+                    (overrides_with, String) => overrides_with_all,
+                    (requires, String) => requires_all,
+                    (short_alias, char) => short_aliases,
+                    (value_name, String) => value_names,
+                    (visible_alias, String) => visible_aliases,
+                    (visible_short_alias, char) => visible_short_aliases,
+                },
+                deprecated(ctx):{
+                    "name" => "id",
+                },
+                not_supported: {
+                    "takes_value" => "removed in clap 4, use `action` or `num_args` instead",
+                    "multiple_values" | "min_values" | "max_values" | "number_of_values" =>
+                        "removed in clap 4, use `num_args` instead",
+                    "multiple_occurrences" => "removed in clap 4, use `action` of `append` or `count` instead",
+                    "max_occurrences" => "removed in clap 4",
+                    "forbid_empty_values" => "removed in clap 4, use `value_parser` of `non_empty_string` instead",
+                    "allow_invalid_utf8" => "removed in clap 4, use `value_parser` of `os_string` instead",
+                    "use_value_delimiter" | "require_value_delimiter" =>
+                        "removed in clap 4, use `value_delimiter` instead",
+                },
+                specialize:[
+                    "action" | "arg_action" => arg.action(AA::from(map.next_value::<ArgAction>()?))
+                    "default_value_if" => {
+                        let (id, value, default): (String, Option<String>, Option<String>) =
+                            parse_value_inner!(map, (String, Option<String>, Option<String>), ctx);
+                        arg.default_value_if(id, predicate(value), Resettable::from(default.map(OsStr::from)))
+                    }
+                    "default_value_ifs" => {
+                        let ifs: Vec<(String, Option<String>, Option<String>)> =
+                            parse_value_inner!(map, Vec<(String, Option<String>, Option<String>)>, ctx);
+                        arg.default_value_ifs(ifs.into_iter().map(|(id, value, default)| {
+                            (id, predicate(value), Resettable::from(default.map(OsStr::from)))
+                        }))
+                    }
+                    "env" => {
+                        #[cfg(feature = "env")] { parse_value_inner!(arg, map, Arg, String, env, ctx) }
+                        #[cfg(not(feature = "env"))] { return Err(Error::custom("env feature disabled"))}}
+                    "hide_env" => {
+                        #[cfg(feature = "env")] { parse_value_inner!(arg, map, Arg, bool, hide_env, ctx) }
+                        #[cfg(not(feature = "env"))] { return Err(Error::custom("env feature disabled"))}}
+                    "hide_env_values" => {
+                        #[cfg(feature = "env")] { parse_value_inner!(arg, map, Arg, bool, hide_env_values, ctx) }
+                        #[cfg(not(feature = "env"))] { return Err(Error::custom("env feature disabled"))}}
+                    "num_args" => arg.num_args(map.next_value::<NumArgs>()?.0)
+                    "possible_value" | "possible_values" => {
+                        let values: Vec<String> = parse_value_inner!(map, Vec<String>, ctx);
+                        arg.value_parser(PossibleValuesParser::new(values))
+                    }
+                    "value_delimiter" => arg.value_delimiter(map.next_value::<char>()?)
+                    "value_hint" => arg.value_hint(VH::from(map.next_value::<ValueHint>()?))
+                    "value_parser" => arg.value_parser(map.next_value::<ValueParser>()?)
+                ],
+                unknown(ctx => frame.metadata)
+            );
+        }
+        ctx.pop_path();
+        ctx.add_arg(id, frame)?;
+        Ok(arg)
+    }
+}
+
+impl<'de, 'c> DeserializeSeed<'de> for ArgVisitor<'de, 'c> {
+    type Value = Arg;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+pub(crate) struct Args<'a, 'c, const USE_ARRAY: bool>(
+    pub(crate) Command,
+    pub(crate) &'c mut Context<'a>,
+);
+impl<'de, 'c, const USE_ARRAY: bool> DeserializeSeed<'de> for Args<'de, 'c, USE_ARRAY> {
+    type Value = Command;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        if USE_ARRAY {
+            deserializer.deserialize_seq(self)
+        } else {
+            deserializer.deserialize_map(self)
+        }
+    }
+}
+
+impl<'de, 'c, const USE_ARRAY: bool> Visitor<'de> for Args<'de, 'c, USE_ARRAY> {
+    type Value = Command;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("args")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut com = self.0;
+        while let Some(a) = seq.next_element_seed(ArgKV(&mut *self.1))? {
+            com = com.arg(a);
+        }
+        Ok(com)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let mut app = self.0;
+        while let Some(name) = map.next_key::<&str>()? {
+            app = app.arg(map.next_value_seed(ArgVisitor(name, &mut *self.1))?);
+        }
+        Ok(app)
+    }
+}
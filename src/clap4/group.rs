@@ -0,0 +1,91 @@
+use crate::de::Context;
+use clap4::{ArgGroup, Command};
+use serde::de::{DeserializeSeed, Error, Visitor};
+use std::collections::HashSet;
+
+struct GroupVisitor<'a, 'c>(&'a str, &'c mut Context<'a>);
+
+impl<'de, 'c> Visitor<'de> for GroupVisitor<'de, 'c> {
+    type Value = ArgGroup;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("arg group map")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let mut group = ArgGroup::new(self.0.to_owned());
+        let ctx = self.1;
+        let mut keys = HashSet::new();
+        ctx.node("group", self.0)?;
+        ctx.push_path("groups", self.0);
+        while let Some(key) = map.next_key::<&str>()? {
+            ctx.check_key(key, &mut keys)?;
+            group = parse_value!(key, group, map, ArgGroup, {
+                // arg : one_or_many
+                (args, Vec<String>),
+                // conflicts_with : one_or_many
+                (conflicts_with_all, Vec<String>),
+                (id, String),
+                (multiple, bool),
+                (required, bool),
+                // requires : one_or_many
+                (requires_all, Vec<String>),
+            },
+            one_or_many: {
+                (arg, String) => args,
+                (conflicts_with, String) => conflicts_with_all,
+                (requires, String) => requires_all,
+            }, deprecated(ctx):{
+                "name" => "id",
+            },
+            unknown(ctx));
+        }
+        ctx.pop_path();
+        Ok(group)
+    }
+}
+
+impl<'de, 'c> DeserializeSeed<'de> for GroupVisitor<'de, 'c> {
+    type Value = ArgGroup;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+pub(crate) struct Groups<'a, 'c>(pub(crate) Command, pub(crate) &'c mut Context<'a>);
+impl<'de, 'c> DeserializeSeed<'de> for Groups<'de, 'c> {
+    type Value = Command;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'c> Visitor<'de> for Groups<'de, 'c> {
+    type Value = Command;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("arg groups")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let mut app = self.0;
+        while let Some(name) = map.next_key::<&str>()? {
+            app = app.group(map.next_value_seed(GroupVisitor(name, &mut *self.1))?);
+        }
+        Ok(app)
+    }
+}
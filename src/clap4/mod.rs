@@ -0,0 +1,445 @@
+/*!
+Load [`Command`]s of clap 4, with the `clap4` feature.
+
+The keys are the same as the ones for clap 3 where clap 4 still has them.
+The keys removed in clap 4, like `takes_value`, `multiple_occurrences` or `settings`, fail to load
+with the key to use instead.
+`possible_values` is loaded as the `value_parser` of the values, and `num_args` takes a number or a range like `"1.."`.
+
+The strings are owned by the command as clap 4 has no lifetime,
+and the keys specific to this crate (`auto_long`, `env_prefix`, usage strings, ...) are not supported,
+except the `x-` keys kept as the metadata of [`CommandWrap`].
+```
+const CLAP_TOML: &'static str = r#"
+name = "app_clap_serde"
+version = "1.0"
+[args]
+verbose = { short = "v", action = "count" }
+input = { num_args = "1..", required = true }
+"#;
+let app = clap_serde::clap4::load(&mut toml::Deserializer::new(CLAP_TOML))
+    .expect("parse failed");
+let matches = app.get_matches_from(["app", "-vv", "a", "b"]);
+assert_eq!(matches.get_count("verbose"), 2);
+assert_eq!(matches.get_many::<String>("input").unwrap().len(), 2);
+```
+*/
+
+use crate::de::{Context, KeyTarget};
+use crate::{metadata::CommandMetadata, Diagnostic, LoadOptions, MetaValue, Metadata};
+use clap4::{
+    builder::{Resettable, Str},
+    Arg, ArgGroup, Command,
+};
+use serde::{
+    de::{DeserializeSeed, Error, Visitor},
+    Deserialize, Deserializer,
+};
+use std::{collections::HashSet, ops::Deref};
+
+mod arg;
+mod group;
+
+/// Deserialize clap 4 [`Command`] from [`Deserializer`].
+pub fn load<'de, D>(de: D) -> Result<Command, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    CommandWrap::deserialize(de).map(|a| a.into())
+}
+
+/**
+Deserialize [`CommandWrap`] as [`load`] with `options`, collecting [`Diagnostic`]s as [`LoadOptions::load`].
+
+The [`KeyHandler`](crate::KeyHandler)s are called by their `clap4_` methods.
+```
+use clap_serde::{LoadOptions, UnknownKeys};
+
+const CLAP_TOML: &'static str = r#"
+name = "app_clap_serde"
+[args]
+verbose = { short = "v", action = "count", owner = "cli-team", x-since = "1.2" }
+"#;
+let options = LoadOptions::new().unknown_keys(UnknownKeys::Warn);
+let (wrap, diagnostics) =
+    clap_serde::clap4::load_with_options(&mut toml::Deserializer::new(CLAP_TOML), &options)
+        .expect("parse failed");
+assert!(wrap.get_arguments().any(|a| a.get_id() == "verbose"));
+assert_eq!(diagnostics[0].path, "args.verbose.owner");
+let since = wrap.metadata("", "verbose").and_then(|m| m.get("since"));
+assert_eq!(since.and_then(|v| v.as_str()), Some("1.2"));
+```
+*/
+pub fn load_with_options<'de, D>(
+    de: D,
+    options: &LoadOptions,
+) -> Result<(CommandWrap, Vec<Diagnostic>), D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut ctx = Context::with_options(options.clone());
+    let app = load_root(de, &mut ctx)?;
+    Ok((app, ctx.take_diagnostics()))
+}
+
+/**
+Wrapper of clap 4 [`Command`] to deserialize.
+```
+const CLAP_TOML: &'static str = r#"
+name = "app_clap_serde"
+about = "test-clap-serde"
+"#;
+let app: clap4::Command = toml::from_str::<clap_serde::clap4::CommandWrap>(CLAP_TOML)
+    .expect("parse failed")
+    .into();
+assert_eq!(app.get_name(), "app_clap_serde");
+```
+*/
+#[derive(Debug, Clone)]
+pub struct CommandWrap {
+    app: Command,
+    metadata: CommandMetadata,
+}
+
+impl From<CommandWrap> for Command {
+    fn from(a: CommandWrap) -> Self {
+        a.app
+    }
+}
+
+impl From<Command> for CommandWrap {
+    fn from(app: Command) -> Self {
+        CommandWrap {
+            app,
+            metadata: Default::default(),
+        }
+    }
+}
+
+impl CommandWrap {
+    /// `x-` keys of the arg `arg` of the command at `command`, as [`crate::CommandWrap::metadata`].
+    pub fn metadata(&self, command: &str, arg: &str) -> Option<&Metadata> {
+        self.metadata.arg(command, arg)
+    }
+
+    /// `x-` keys of the command at `command`, as [`crate::CommandWrap::command_metadata`].
+    pub fn command_metadata(&self, command: &str) -> Option<&Metadata> {
+        self.metadata.find(command).map(|c| &c.metadata)
+    }
+}
+
+impl Deref for CommandWrap {
+    type Target = Command;
+
+    fn deref(&self) -> &Self::Target {
+        &self.app
+    }
+}
+
+impl KeyTarget<'_> for Command {
+    fn handle(
+        &mut self,
+        handler: &dyn crate::KeyHandler,
+        key: &str,
+        value: &MetaValue,
+        _: Option<&crate::StringStore>,
+    ) -> Result<bool, String> {
+        handler.clap4_command_key(key, value, self)
+    }
+}
+
+impl KeyTarget<'_> for Arg {
+    fn handle(
+        &mut self,
+        handler: &dyn crate::KeyHandler,
+        key: &str,
+        value: &MetaValue,
+        _: Option<&crate::StringStore>,
+    ) -> Result<bool, String> {
+        handler.clap4_arg_key(key, value, self)
+    }
+}
+
+impl KeyTarget<'_> for ArgGroup {
+    fn handle(
+        &mut self,
+        handler: &dyn crate::KeyHandler,
+        key: &str,
+        value: &MetaValue,
+        _: Option<&crate::StringStore>,
+    ) -> Result<bool, String> {
+        handler.clap4_group_key(key, value, self)
+    }
+}
+
+const TMP_APP_NAME: &str = "__tmp__deserialize__name__";
+
+impl<'de> Deserialize<'de> for CommandWrap {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        load_root(deserializer, &mut Context::new())
+    }
+}
+
+/// Load the root command of `de` in `ctx`.
+fn load_root<'de, D>(de: D, ctx: &mut Context<'de>) -> Result<CommandWrap, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    de.deserialize_map(CommandVisitor(Command::new(TMP_APP_NAME), ctx))
+        //check the name so as not to expose the tmp name.
+        .and_then(|r| {
+            if r.app.get_name() != TMP_APP_NAME {
+                Ok(r)
+            } else {
+                Err(<D::Error>::missing_field("name"))
+            }
+        })
+        .map(|r| CommandWrap {
+            metadata: ctx.take_metadata(),
+            ..r
+        })
+}
+
+#[cfg(feature = "color")]
+enum_de!(ColorChoice, ColorChoice1,
+    #[derive(Deserialize, Clone, Copy)]
+    #[cfg_attr(feature = "kebab-case-key" ,serde(rename_all = "kebab-case"))]
+    #[cfg_attr(feature = "snake-case-key" ,serde(rename_all = "snake_case"))]
+    {
+    Auto,
+    Always,
+    Never,
+});
+
+#[cfg(feature = "color")]
+use clap4::ColorChoice;
+
+struct CommandVisitor<'a, 'c>(Command, &'c mut Context<'a>);
+
+impl<'a, 'c> Visitor<'a> for CommandVisitor<'a, 'c> {
+    type Value = CommandWrap;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("Command Map")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'a>,
+    {
+        let mut app = self.0;
+        let ctx = self.1;
+        let mut keys = HashSet::new();
+        while let Some(key) = map.next_key::<&str>()? {
+            ctx.check_key(key, &mut keys)?;
+            app = parse_value!(key, app, map, Command, {
+                (about, String),
+                (after_help, String),
+                (after_long_help, String),
+                // alias : one_or_many
+                (aliases, Vec<String>),
+                (allow_external_subcommands, bool),
+                (allow_hyphen_values, bool),
+                (allow_missing_positional, bool),
+                (allow_negative_numbers, bool),
+                //args : specialized
+                (arg_required_else_help, bool),
+                (args_conflicts_with_subcommands, bool),
+                (args_override_self, bool),
+                (author, String),
+                (before_help, String),
+                (before_long_help, String),
+                (bin_name, String),
+                // color : specialized
+                (disable_colored_help, bool),
+                (disable_help_flag, bool),
+                (disable_help_subcommand, bool),
+                (disable_version_flag, bool),
+                (display_name, String),
+                (display_order, usize),
+                (dont_delimit_trailing_values, bool),
+                (flatten_help, bool),
+                // groups : specialized
+                (help_expected, bool),
+                (help_template, String),
+                (hide, bool),
+                (hide_possible_values, bool),
+                (ignore_errors, bool),
+                (infer_long_args, bool),
+                (infer_subcommands, bool),
+                (long_about, String),
+                (long_flag, String),
+                // long_flag_alias : one_or_many
+                (long_flag_aliases, Vec<String>),
+                (long_version, String),
+                (max_term_width, usize),
+                (multicall, bool),
+                (name, String),
+                (next_display_order, Option<usize>),
+                // next_help_heading : specialized
+                (next_line_help, bool),
+                (no_binary_name, bool),
+                (override_help, String),
+                (override_usage, String),
+                (propagate_version, bool),
+                (short_flag, char),
+                // short_flag_alias : one_or_many
+                (short_flag_aliases, Vec<char>),
+                // subcommands : specialized
+                (subcommand_help_heading, String),
+                (subcommand_negates_reqs, bool),
+                (subcommand_precedence_over_arg, bool),
+                (subcommand_required, bool),
+                (subcommand_value_name, String),
+                (term_width, usize),
+                (version, String),
+                // visible_alias : one_or_many
+                (visible_aliases, Vec<String>),
+                // visible_long_flag_alias : one_or_many
+                (visible_long_flag_aliases, Vec<String>),
+                // visible_short_flag_alias : one_or_many
+                (visible_short_flag_aliases, Vec<char>),
+            },
+            one_or_many: {
+                (alias, String) => aliases,
+                (long_flag_alias, String) => long_flag_aliases,
+                (short_flag_alias, char) => short_flag_aliases,
+                (visible_alias, String) => visible_aliases,
+                (visible_long_flag_alias, String) => visible_long_flag_aliases,
+                (visible_short_flag_alias, char) => visible_short_flag_aliases,
+            },
+            deprecated(ctx): {
+                "help_heading" => "next_help_heading",
+            },
+            not_supported: {
+                "arg" => "Use args instead",
+                "group" => "Use groups instead",
+                "setting" | "settings" | "global_setting" | "global_settings" =>
+                    "settings are removed in clap 4, use the key of each setting instead",
+                "allow_invalid_utf8_for_external_subcommands" =>
+                    "removed in clap 4, use `external_subcommand_value_parser` of clap instead",
+                "dont_collapse_args_in_usage" => "removed in clap 4, as it is the default",
+                "trailing_var_arg" => "moved to args in clap 4",
+            },
+            specialize:
+            [
+                "args" => map.next_value_seed(arg::Args::<true>(app, ctx))?
+                "args_map" => map.next_value_seed(arg::Args::<false>(app, ctx))?
+                "color" => {
+                    #[cfg(feature = "color")] {
+                        app.color(map.next_value::<ColorChoice1>()?.into())
+                    }
+                    #[cfg(not(feature = "color"))] { return Err(Error::custom("color feature disabled"))}}
+                "next_help_heading" => {
+                    let heading: Option<String> = parse_value_inner!(map, Option<String>, ctx);
+                    app.next_help_heading(Resettable::from(heading.map(Str::from)))
+                }
+                "subcommands" => map.next_value_seed(SubCommands::<true>(app, ctx))?
+                "subcommands_map" => map.next_value_seed(SubCommands::<false>(app, ctx))?
+                "groups" => map.next_value_seed(group::Groups(app, ctx))?
+            ],
+            unknown(ctx => ctx.frame().metadata));
+        }
+
+        Ok(app.into())
+    }
+}
+
+struct NameSeed<'a, 'c>(&'a str, &'c mut Context<'a>);
+
+impl<'de, 'c> DeserializeSeed<'de> for NameSeed<'de, 'c> {
+    type Value = CommandWrap;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let ctx = self.1;
+        ctx.enter(self.0)?;
+        let sub =
+            deserializer.deserialize_map(CommandVisitor(Command::new(self.0.to_owned()), ctx))?;
+        ctx.leave(self.0, sub.app.get_name())?;
+        Ok(sub)
+    }
+}
+
+struct SubCommands<'a, 'c, const KV_ARRAY: bool>(Command, &'c mut Context<'a>);
+impl<'de, 'c, const KV_ARRAY: bool> DeserializeSeed<'de> for SubCommands<'de, 'c, KV_ARRAY> {
+    type Value = Command;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        if KV_ARRAY {
+            deserializer.deserialize_seq(self)
+        } else {
+            deserializer.deserialize_map(self)
+        }
+    }
+}
+
+impl<'de, 'c, const KV_ARRAY: bool> Visitor<'de> for SubCommands<'de, 'c, KV_ARRAY> {
+    type Value = Command;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("Subcommand")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let mut app = self.0;
+        while let Some(name) = map.next_key::<&str>()? {
+            let sub = map.next_value_seed(NameSeed(name, &mut *self.1))?;
+            app = app.subcommand(sub);
+        }
+        Ok(app)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut app = self.0;
+        while let Some(sub) = seq.next_element_seed(InnerSubCommand(&mut *self.1))? {
+            app = app.subcommand(sub)
+        }
+        Ok(app)
+    }
+}
+
+struct InnerSubCommand<'a, 'c>(&'c mut Context<'a>);
+impl<'de, 'c> Visitor<'de> for InnerSubCommand<'de, 'c> {
+    type Value = Command;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("Subcommand Inner")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let k = map
+            .next_key()?
+            .ok_or_else(|| A::Error::invalid_length(0, &"missing command in subcommand"))?;
+        let com = map.next_value_seed(NameSeed(k, self.0))?;
+        Ok(com.into())
+    }
+}
+
+impl<'de, 'c> DeserializeSeed<'de> for InnerSubCommand<'de, 'c> {
+    type Value = Command;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
@@ -0,0 +1,258 @@
+//! `#[derive(Parser)]` types of a command.
+
+use super::{getters, Call};
+use clap::{Arg, ArgAction, Command};
+use std::fmt::Write;
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+/// Types of `app`, with the struct deriving `Parser` first.
+pub(crate) fn derive_rust(app: &Command) -> String {
+    let mut out = String::new();
+    let mut attrs = vec![format!("name = {:?}", app.get_name())];
+    attrs.extend(command_attrs(app));
+    write_struct(
+        &mut out,
+        app,
+        &pascal_case(app.get_name()),
+        "Parser",
+        &attrs,
+    );
+    out
+}
+
+/// Keys of the command itself as `method = value`.
+fn command_attrs(app: &Command) -> Vec<String> {
+    getters::command_node(app)
+        .calls
+        .into_iter()
+        .filter_map(|call| match call {
+            // The args have their own `help_heading`.
+            Call::Method("next_help_heading", _) => None,
+            Call::Method(method, args) => Some(format!("{} = {}", method, args.join(", "))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Write the struct `ty` of `app` deriving `derive`, followed by the types of its subcommands.
+fn write_struct(out: &mut String, app: &Command, ty: &str, derive: &str, attrs: &[String]) {
+    let fields: Vec<_> = getters::args(app).map(Field::new).collect();
+    let subcommands: Vec<_> = app.get_subcommands().collect();
+    let _ = writeln!(out, "#[derive(Debug, clap::{})]", derive);
+    write_attrs(out, "", attrs);
+    if fields.is_empty() && subcommands.is_empty() {
+        let _ = writeln!(out, "pub struct {} {{}}", ty);
+        return;
+    }
+    let _ = writeln!(out, "pub struct {} {{", ty);
+    for field in &fields {
+        write_attrs(out, "    ", &field.attrs);
+        let _ = writeln!(out, "    pub {}: {},", field.ident, field.ty);
+    }
+    if subcommands.is_empty() {
+        out.push_str("}\n");
+        return;
+    }
+    let ident = match fields.iter().any(|f| f.ident == "command") {
+        true => "subcommand",
+        false => "command",
+    };
+    let enum_ty = format!("{}Subcommand", ty);
+    out.push_str("    #[clap(subcommand)]\n");
+    match app.is_subcommand_required_set() {
+        true => {
+            let _ = writeln!(out, "    pub {}: {},", ident, enum_ty);
+        }
+        false => {
+            let _ = writeln!(out, "    pub {}: Option<{}>,", ident, enum_ty);
+        }
+    }
+    out.push_str("}\n");
+
+    let mut structs = Vec::new();
+    let _ = writeln!(
+        out,
+        "\n#[derive(Debug, clap::Subcommand)]\npub enum {} {{",
+        enum_ty
+    );
+    for sub in subcommands {
+        let variant = pascal_case(sub.get_name());
+        let mut attrs = Vec::new();
+        if !is_default_subcommand_name(sub.get_name()) {
+            attrs.push(format!("name = {:?}", sub.get_name()));
+        }
+        attrs.extend(command_attrs(sub));
+        write_attrs(out, "    ", &attrs);
+        if getters::args(sub).next().is_none() && sub.get_subcommands().next().is_none() {
+            let _ = writeln!(out, "    {},", variant);
+        } else {
+            let sub_ty = format!("{}{}", ty, variant);
+            let _ = writeln!(out, "    {}({}),", variant, sub_ty);
+            structs.push((sub, sub_ty));
+        }
+    }
+    out.push_str("}\n");
+    for (sub, sub_ty) in structs {
+        out.push('\n');
+        write_struct(out, sub, &sub_ty, "Args", &[]);
+    }
+}
+
+/// `#[clap(attrs)]`, split into lines if too long.
+fn write_attrs(out: &mut String, pad: &str, attrs: &[String]) {
+    if attrs.is_empty() {
+        return;
+    }
+    let line = format!("{}#[clap({})]", pad, attrs.join(", "));
+    if line.len() <= 100 {
+        let _ = writeln!(out, "{}", line);
+    } else {
+        let _ = writeln!(out, "{}#[clap(", pad);
+        for attr in attrs {
+            let _ = writeln!(out, "{}    {},", pad, attr);
+        }
+        let _ = writeln!(out, "{})]", pad);
+    }
+}
+
+/// Field of an arg.
+struct Field {
+    attrs: Vec<String>,
+    ident: String,
+    ty: String,
+}
+
+impl Field {
+    fn new(arg: &Arg) -> Self {
+        let action = arg.get_action();
+        // `StoreValue` is also the action if not set.
+        let takes_values = arg.is_takes_value_set()
+            || arg.is_positional()
+            || matches!(action, ArgAction::Set | ArgAction::Append);
+        let value = getters::value_type(arg.get_value_parser())
+            .unwrap_or_else(|| getters::default_value_type(arg));
+        let optional = !arg.is_required_set() && arg.get_default_values().is_empty();
+        // The action to set for the flags of clap 3 without one, counted if they can occur more than once.
+        let mut flag_action = None;
+        // Whether the type makes the arg required, which is the type of the value itself.
+        let mut required_by_type = false;
+        let ty = match action {
+            ArgAction::SetTrue | ArgAction::SetFalse | ArgAction::Help | ArgAction::Version => {
+                "bool".to_owned()
+            }
+            ArgAction::Count => "u8".to_owned(),
+            ArgAction::Append => format!("Vec<{}>", value),
+            ArgAction::IncOccurrence => {
+                flag_action = Some(ArgAction::Count);
+                "u8".to_owned()
+            }
+            _ if takes_values => {
+                if arg.is_multiple_values_set() || arg.is_multiple_occurrences_set() {
+                    format!("Vec<{}>", value)
+                } else if optional {
+                    format!("Option<{}>", value)
+                } else {
+                    required_by_type = true;
+                    value.to_owned()
+                }
+            }
+            _ if arg.is_multiple_occurrences_set() => {
+                flag_action = Some(ArgAction::Count);
+                "u8".to_owned()
+            }
+            _ => {
+                flag_action = Some(ArgAction::SetTrue);
+                "bool".to_owned()
+            }
+        };
+
+        let id = arg.get_id();
+        let ident = field_ident(id);
+        let mut attrs = Vec::new();
+        if ident.trim_start_matches("r#") != id {
+            attrs.push(format!("name = {:?}", id));
+        }
+        let mut value_parser = false;
+        for call in getters::arg_node(arg).calls {
+            if let Call::Method(method, args) = call {
+                match method {
+                    // Set by the action or the type.
+                    "takes_value" => continue,
+                    "action" | "multiple_occurrences" if flag_action.is_some() => continue,
+                    "required" if required_by_type => continue,
+                    "value_parser" => value_parser = true,
+                    _ => {}
+                }
+                attrs.push(format!("{} = {}", method, args.join(", ")));
+            }
+        }
+        if let Some(action) = flag_action {
+            attrs.push(format!("action = clap::ArgAction::{:?}", action));
+        } else if takes_values && !value_parser {
+            attrs.push("value_parser".to_owned());
+        }
+        Self { attrs, ident, ty }
+    }
+}
+
+/// Snake case identifier of the arg `id`, such as `foo_bar` of `foo-bar` or `fooBar`.
+fn field_ident(id: &str) -> String {
+    let mut s = String::with_capacity(id.len());
+    let mut prev_lower = false;
+    for c in id.chars() {
+        if c.is_alphanumeric() {
+            if prev_lower && c.is_uppercase() {
+                s.push('_');
+            }
+            prev_lower = c.is_lowercase() || c.is_numeric();
+            s.extend(c.to_lowercase());
+        } else {
+            prev_lower = false;
+            s.push('_');
+        }
+    }
+    if s.is_empty() || s.starts_with(|c: char| c.is_numeric()) {
+        s.insert(0, '_');
+    }
+    match s.as_str() {
+        "crate" | "self" | "super" => s + "_",
+        kw if KEYWORDS.contains(&kw) => format!("r#{}", s),
+        _ => s,
+    }
+}
+
+/// Pascal case type name of the command `name`, such as `FooBar` of `foo-bar` or `foo_bar`.
+fn pascal_case(name: &str) -> String {
+    let mut s: String = name
+        .split(|c: char| !c.is_alphanumeric())
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .into_iter()
+                .flat_map(char::to_uppercase)
+                .chain(chars)
+        })
+        .collect();
+    if s.is_empty() || s.starts_with(|c: char| c.is_numeric()) {
+        s.insert(0, '_');
+    }
+    s
+}
+
+/// Whether the subcommand `name` is the one derived from its variant, which is kebab case.
+fn is_default_subcommand_name(name: &str) -> bool {
+    name.split('-').all(|word| {
+        word.starts_with(|c: char| c.is_ascii_lowercase())
+            && word
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    })
+}
@@ -0,0 +1,368 @@
+//! [`Node`]s of a command read back with the getters of clap.
+
+use super::{Call, Node, ToRust};
+use clap::{builder::ValueParser, AppSettings, Arg, ArgAction, Command, ValueHint};
+
+/// Calls collected from the getters, skipping the unset keys.
+#[derive(Default)]
+struct Calls(Vec<Call>);
+
+impl Calls {
+    fn expr(&mut self, method: &'static str, expr: String) {
+        self.0.push(Call::Method(method, vec![expr]));
+    }
+
+    fn value<T: ToRust>(&mut self, method: &'static str, value: Option<T>) {
+        if let Some(v) = value {
+            self.expr(method, v.to_rust());
+        }
+    }
+
+    fn flag(&mut self, method: &'static str, set: bool) {
+        if set {
+            self.expr(method, true.to_rust());
+        }
+    }
+
+    /// `single` with the value if only one, else `plural` with the slice of them.
+    fn list<T: ToRust>(&mut self, single: &'static str, plural: &'static str, values: Vec<T>) {
+        match values.len() {
+            0 => {}
+            1 => self.value(single, values.into_iter().next()),
+            _ => self.expr(plural, format!("&{}", values.to_rust())),
+        }
+    }
+}
+
+/// Items of `all` not in `visible`, which are the hidden aliases.
+fn hidden<T: PartialEq>(all: impl IntoIterator<Item = T>, visible: &[T]) -> Vec<T> {
+    all.into_iter().filter(|a| !visible.contains(a)).collect()
+}
+
+/// Node of `app` with its args and subcommands.
+pub(crate) fn command_node(app: &Command) -> Node {
+    let mut calls = Calls::default();
+    calls.value("about", app.get_about());
+    calls.value("after_help", app.get_after_help());
+    calls.value("after_long_help", app.get_after_long_help());
+    let visible: Vec<_> = app.get_visible_aliases().collect();
+    calls.list("alias", "aliases", hidden(app.get_all_aliases(), &visible));
+    calls.value("author", app.get_author());
+    calls.value("before_help", app.get_before_help());
+    calls.value("before_long_help", app.get_before_long_help());
+    calls.value("bin_name", app.get_bin_name());
+    #[cfg(feature = "color")]
+    if app.get_color() != clap::ColorChoice::Auto {
+        calls.expr("color", format!("clap::ColorChoice::{:?}", app.get_color()));
+    }
+    calls.value("display_name", app.get_display_name());
+    calls.value("long_about", app.get_long_about());
+    calls.value("long_flag", app.get_long_flag());
+    let visible: Vec<_> = app.get_visible_long_flag_aliases().collect();
+    calls.list(
+        "long_flag_alias",
+        "long_flag_aliases",
+        hidden(app.get_all_long_flag_aliases(), &visible),
+    );
+    calls.value("long_version", app.get_long_version());
+    calls.value("short_flag", app.get_short_flag());
+    let visible: Vec<_> = app.get_visible_short_flag_aliases().collect();
+    calls.list(
+        "short_flag_alias",
+        "short_flag_aliases",
+        hidden(app.get_all_short_flag_aliases(), &visible),
+    );
+    calls.value("subcommand_help_heading", app.get_subcommand_help_heading());
+    calls.value("subcommand_value_name", app.get_subcommand_value_name());
+    calls.value("version", app.get_version());
+    calls.list(
+        "visible_alias",
+        "visible_aliases",
+        app.get_visible_aliases().collect(),
+    );
+    calls.list(
+        "visible_long_flag_alias",
+        "visible_long_flag_aliases",
+        app.get_visible_long_flag_aliases().collect(),
+    );
+    calls.list(
+        "visible_short_flag_alias",
+        "visible_short_flag_aliases",
+        app.get_visible_short_flag_aliases().collect(),
+    );
+
+    for (method, set) in [
+        (
+            "allow_external_subcommands",
+            app.is_allow_external_subcommands_set(),
+        ),
+        (
+            "allow_hyphen_values",
+            app.is_set(AppSettings::AllowHyphenValues),
+        ),
+        (
+            "allow_invalid_utf8_for_external_subcommands",
+            app.is_allow_invalid_utf8_for_external_subcommands_set(),
+        ),
+        (
+            "allow_missing_positional",
+            app.is_allow_missing_positional_set(),
+        ),
+        (
+            "allow_negative_numbers",
+            app.is_allow_negative_numbers_set(),
+        ),
+        (
+            "arg_required_else_help",
+            app.is_arg_required_else_help_set(),
+        ),
+        (
+            "args_conflicts_with_subcommands",
+            app.is_args_conflicts_with_subcommands_set(),
+        ),
+        (
+            "args_override_self",
+            app.is_set(AppSettings::AllArgsOverrideSelf),
+        ),
+        ("disable_colored_help", app.is_disable_colored_help_set()),
+        ("disable_help_flag", app.is_disable_help_flag_set()),
+        (
+            "disable_help_subcommand",
+            app.is_disable_help_subcommand_set(),
+        ),
+        ("disable_version_flag", app.is_disable_version_flag_set()),
+        (
+            "dont_collapse_args_in_usage",
+            app.is_dont_collapse_args_in_usage_set(),
+        ),
+        (
+            "dont_delimit_trailing_values",
+            app.is_dont_delimit_trailing_values_set(),
+        ),
+        ("help_expected", app.is_set(AppSettings::HelpExpected)),
+        ("hide", app.is_hide_set()),
+        (
+            "hide_possible_values",
+            app.is_set(AppSettings::HidePossibleValues),
+        ),
+        ("ignore_errors", app.is_set(AppSettings::IgnoreErrors)),
+        ("infer_long_args", app.is_set(AppSettings::InferLongArgs)),
+        (
+            "infer_subcommands",
+            app.is_set(AppSettings::InferSubcommands),
+        ),
+        ("multicall", app.is_multicall_set()),
+        ("next_line_help", app.is_next_line_help_set()),
+        ("no_binary_name", app.is_no_binary_name_set()),
+        ("propagate_version", app.is_propagate_version_set()),
+        (
+            "subcommand_negates_reqs",
+            app.is_subcommand_negates_reqs_set(),
+        ),
+        (
+            "subcommand_precedence_over_arg",
+            app.is_subcommand_precedence_over_arg_set(),
+        ),
+        ("subcommand_required", app.is_subcommand_required_set()),
+        ("trailing_var_arg", app.is_trailing_var_arg_set()),
+    ] {
+        calls.flag(method, set);
+    }
+    // The settings without their own builder methods.
+    for setting in [
+        AppSettings::DeriveDisplayOrder,
+        AppSettings::SubcommandRequiredElseHelp,
+        AppSettings::UseLongFormatForHelpSubcommand,
+    ] {
+        if app.is_set(setting) {
+            calls.expr("setting", format!("clap::AppSettings::{:?}", setting));
+        }
+    }
+
+    let mut calls = calls.0;
+    calls.extend(args(app).map(|a| Call::Add("arg", arg_node(a))));
+    // After the args, as it is given to the args added later.
+    if let Some(heading) = app.get_next_help_heading() {
+        calls.push(Call::Method("next_help_heading", vec![heading.to_rust()]));
+    }
+    calls.extend(
+        app.get_subcommands()
+            .map(|s| Call::Add("subcommand", command_node(s))),
+    );
+    Node {
+        calls,
+        ..Node::new("Command", app.get_name())
+    }
+}
+
+/// Args of `app`, except the `help` and `version` args `Command::new` adds.
+pub(crate) fn args<'a, 'help>(app: &'a Command<'help>) -> impl Iterator<Item = &'a Arg<'help>> {
+    let generated: Vec<_> = Command::new("").get_arguments().map(arg_node).collect();
+    app.get_arguments()
+        .filter(move |a| !generated.contains(&arg_node(a)))
+}
+
+/// Node of `arg`.
+pub(crate) fn arg_node(arg: &Arg) -> Node {
+    let mut calls = Calls::default();
+    if !matches!(arg.get_action(), ArgAction::StoreValue) {
+        calls.expr("action", format!("clap::ArgAction::{:?}", arg.get_action()));
+    }
+    let visible_aliases = arg.get_visible_aliases().unwrap_or_default();
+    calls.list(
+        "alias",
+        "aliases",
+        hidden(arg.get_all_aliases().unwrap_or_default(), &visible_aliases),
+    );
+    calls.flag("allow_hyphen_values", arg.is_allow_hyphen_values_set());
+    calls.flag("allow_invalid_utf8", arg.is_allow_invalid_utf8_set());
+    let defaults = arg.get_default_values().iter();
+    calls.list(
+        "default_value",
+        "default_values",
+        defaults.map(|v| v.to_string_lossy().into_owned()).collect(),
+    );
+    #[cfg(feature = "env")]
+    calls.value(
+        "env",
+        arg.get_env().map(|e| e.to_string_lossy().into_owned()),
+    );
+    calls.flag("exclusive", arg.is_exclusive_set());
+    calls.flag("forbid_empty_values", arg.is_forbid_empty_values_set());
+    calls.flag("global", arg.is_global_set());
+    calls.value("help", arg.get_help());
+    calls.value("help_heading", arg.get_help_heading());
+    calls.flag("hide", arg.is_hide_set());
+    calls.flag("hide_default_value", arg.is_hide_default_value_set());
+    #[cfg(feature = "env")]
+    {
+        calls.flag("hide_env", arg.is_hide_env_set());
+        calls.flag("hide_env_values", arg.is_hide_env_values_set());
+    }
+    calls.flag("hide_long_help", arg.is_hide_long_help_set());
+    calls.flag("hide_possible_values", arg.is_hide_possible_values_set());
+    calls.flag("hide_short_help", arg.is_hide_short_help_set());
+    calls.flag("ignore_case", arg.is_ignore_case_set());
+    calls.value("index", arg.get_index());
+    calls.flag("last", arg.is_last_set());
+    calls.value("long", arg.get_long());
+    calls.value("long_help", arg.get_long_help());
+    calls.flag("multiple_occurrences", arg.is_multiple_occurrences_set());
+    calls.flag("multiple_values", arg.is_multiple_values_set());
+    calls.flag("next_line_help", arg.is_next_line_help_set());
+    calls.value("number_of_values", arg.get_num_vals());
+    if let Some(values) = arg.get_possible_values() {
+        let plain = values.iter().all(|v| {
+            v.get_help().is_none() && !v.is_hide_set() && v.get_name_and_aliases().count() == 1
+        });
+        let values: Vec<_> = values
+            .iter()
+            .map(|v| {
+                if plain {
+                    return v.get_name().to_rust();
+                }
+                let mut s = format!("clap::PossibleValue::new({})", v.get_name().to_rust());
+                if let Some(help) = v.get_help() {
+                    s += &format!(".help({})", help.to_rust());
+                }
+                if v.is_hide_set() {
+                    s += ".hide(true)";
+                }
+                let aliases: Vec<_> = v.get_name_and_aliases().skip(1).collect();
+                if !aliases.is_empty() {
+                    s += &format!(".aliases({})", aliases.to_rust());
+                }
+                s
+            })
+            .collect();
+        calls.expr("possible_values", format!("[{}]", values.join(", ")));
+    }
+    calls.flag("require_equals", arg.is_require_equals_set());
+    calls.flag(
+        "require_value_delimiter",
+        arg.is_require_value_delimiter_set(),
+    );
+    calls.flag("required", arg.is_required_set());
+    calls.value("short", arg.get_short());
+    let visible_shorts = arg.get_visible_short_aliases().unwrap_or_default();
+    calls.list(
+        "short_alias",
+        "short_aliases",
+        hidden(
+            arg.get_all_short_aliases().unwrap_or_default(),
+            &visible_shorts,
+        ),
+    );
+    calls.flag("takes_value", arg.is_takes_value_set());
+    calls.flag("use_value_delimiter", arg.is_use_value_delimiter_set());
+    calls.value("value_delimiter", arg.get_value_delimiter());
+    let value_type = value_type(arg.get_value_parser());
+    // `AnyPath` is the hint of `PathBuf` values if not set.
+    if !matches!(
+        (arg.get_value_hint(), value_type),
+        (ValueHint::Unknown, _) | (ValueHint::AnyPath, Some("std::path::PathBuf"))
+    ) {
+        calls.expr(
+            "value_hint",
+            format!("clap::ValueHint::{:?}", arg.get_value_hint()),
+        );
+    }
+    calls.list(
+        "value_name",
+        "value_names",
+        arg.get_value_names().unwrap_or_default().to_vec(),
+    );
+    match value_type {
+        Some(ty) if ty != default_value_type(arg) => {
+            calls.expr("value_parser", format!("clap::value_parser!({})", ty))
+        }
+        _ => {}
+    }
+    calls.list("visible_alias", "visible_aliases", visible_aliases);
+    calls.list(
+        "visible_short_alias",
+        "visible_short_aliases",
+        visible_shorts,
+    );
+    Node {
+        calls: calls.0,
+        ..Node::new("Arg", arg.get_id())
+    }
+}
+
+/// Type of the values parsed by `parser`, if it is one of the types the `value_parser` key gives.
+pub(crate) fn value_type(parser: &ValueParser) -> Option<&'static str> {
+    macro_rules! find {
+        ($($ty: ty),*) => {
+            $(
+                if parser.type_id() == ValueParser::from(clap::value_parser!($ty)).type_id() {
+                    return Some(stringify!($ty));
+                }
+            )*
+        };
+    }
+    find!(
+        bool,
+        String,
+        std::ffi::OsString,
+        std::path::PathBuf,
+        i64,
+        i32,
+        i16,
+        i8,
+        u64,
+        u32,
+        u16,
+        u8
+    );
+    None
+}
+
+/// Type of the values of `arg` if no value parser is set.
+pub(crate) fn default_value_type(arg: &Arg) -> &'static str {
+    if arg.is_allow_invalid_utf8_set() {
+        "std::ffi::OsString"
+    } else {
+        "String"
+    }
+}
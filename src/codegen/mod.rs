@@ -0,0 +1,274 @@
+/*!
+Rust code building a command without loading it, generated from the builder methods called while loading,
+or from a [`Command`] with [`to_builder_rust`] and [`to_derive_rust`].
+
+```
+# #[cfg(all(feature = "toml", not(feature = "override-arg")))]
+# {
+use clap_serde::{Format, Source};
+
+let source = Source::new("name = \"app\"\n[args]\nverbose = { short = \"v\" }\n", Format::Toml);
+let builder = source.builder().expect("load failed");
+assert_eq!(
+    builder.to_string(),
+    r#"clap::Command::new("app")
+    .arg(
+        clap::Arg::new("verbose")
+            .short('v')
+    )"#
+);
+# }
+```
+*/
+
+use clap::Command;
+use serde::Deserializer;
+use std::fmt::{Display, Write};
+
+mod derive;
+mod getters;
+
+/**
+Load [`Command`] from [`Deserializer`] as [`load`](crate::load), recording the builder methods called.
+```
+const CLAP_TOML: &'static str = r#"
+name = "app"
+[args]
+input = { required = true }
+"#;
+let (app, builder) = clap_serde::codegen::load_builder(&mut toml::Deserializer::new(CLAP_TOML))
+    .expect("parse failed");
+assert_eq!(app.get_name(), "app");
+assert!(builder.to_string().contains(".required(true)"));
+```
+*/
+pub fn load_builder<'de, D>(de: D) -> Result<(Command<'de>, Builder), D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut ctx = crate::de::Context::recording();
+    let app = crate::de::load_root(de, &mut ctx)?.into();
+    let builder = recorded(&mut ctx, &app);
+    Ok((app, builder))
+}
+
+/// The builder recorded while loading `app` in `ctx`, made by [`Context::recording`](crate::de::Context::recording).
+pub(crate) fn recorded(ctx: &mut crate::de::Context, app: &Command) -> Builder {
+    let root = ctx.take_recording(app.get_name()).expect("recorded");
+    Builder { root }
+}
+
+/**
+Rust expression building `app` with the builder methods, read back from `app` with the getters of clap.
+
+Only the keys having getters in clap 3 are kept, so `requires`, `conflicts_with`, `groups`, `display_order`,
+the ranges of `value_parser` and the other keys without getters are dropped,
+and `global_setting`s are shown as `setting`s.
+Use [`load_builder`] to keep them, which records the builder methods while loading.
+```
+use clap::{Arg, Command};
+
+let app = Command::new("app").arg(Arg::new("verbose").short('v'));
+assert_eq!(
+    clap_serde::codegen::to_builder_rust(&app),
+    r#"clap::Command::new("app")
+    .arg(
+        clap::Arg::new("verbose")
+            .short('v')
+    )"#
+);
+```
+*/
+pub fn to_builder_rust(app: &Command) -> String {
+    Builder {
+        root: getters::command_node(app),
+    }
+    .to_string()
+}
+
+/**
+Rust items of `#[derive(Parser)]` types making `app`, read back from `app` as [`to_builder_rust`].
+
+The command becomes a struct deriving `Parser`, and the subcommands become the variants of an enum deriving `Subcommand`,
+holding a struct deriving `Args` if they have args or subcommands.
+The field type of an arg is inferred from its `action`, `value_parser` and `multiple_values`:
+`bool` of flags, `u8` of counts, `Vec` of multiple values and `Option` of the args neither required nor defaulted.
+The flags without `action` get `SetTrue`, or `Count` if they have `multiple_occurrences`.
+The other keys are given as the attributes calling the builder methods of the same names.
+```
+use clap::{Arg, ArgAction, Command};
+
+let app = Command::new("app")
+    .arg(Arg::new("verbose").short('v').action(ArgAction::Count))
+    .arg(Arg::new("file").required(true));
+assert_eq!(
+    clap_serde::codegen::to_derive_rust(&app),
+    r#"#[derive(Debug, clap::Parser)]
+#[clap(name = "app")]
+pub struct App {
+    #[clap(action = clap::ArgAction::Count, short = 'v')]
+    pub verbose: u8,
+    #[clap(value_parser)]
+    pub file: String,
+}
+"#
+);
+```
+*/
+pub fn to_derive_rust(app: &Command) -> String {
+    derive::derive_rust(app)
+}
+
+/**
+Builder methods called while loading a command, which is shown as the Rust expression building the same command.
+
+The keys handled by [`KeyHandler`](crate::KeyHandler)s are not recorded, as they change the command directly.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Builder {
+    pub(crate) root: Node,
+}
+
+/// Command, arg or group built by the recorded calls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Node {
+    /// Type in `clap`, like `Arg`.
+    pub(crate) ty: &'static str,
+    /// Name given to `new`, or to `mut_arg` and `mut_subcommand`.
+    pub(crate) name: String,
+    pub(crate) calls: Vec<Call>,
+}
+
+/// Builder method called on a [`Node`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Call {
+    /// `.method(args)`, with the arguments as Rust expressions.
+    Method(&'static str, Vec<String>),
+    /// `.method(Type::new(name).calls)`, like `.arg(Arg::new("id").long("id"))`.
+    Add(&'static str, Node),
+    /// `.method(name, |x| x.calls)`, like `.mut_arg("id", |a| a.long("id"))`.
+    Mut(&'static str, Node),
+}
+
+impl Node {
+    pub(crate) fn new(ty: &'static str, name: impl Into<String>) -> Self {
+        Self {
+            ty,
+            name: name.into(),
+            calls: Vec::new(),
+        }
+    }
+
+    fn write_calls(&self, out: &mut String, indent: usize) {
+        let pad = "    ".repeat(indent);
+        for call in &self.calls {
+            match call {
+                Call::Method(method, args) => {
+                    let _ = write!(out, "\n{}.{}({})", pad, method, args.join(", "));
+                }
+                Call::Add(method, node) if node.calls.is_empty() => {
+                    let _ = write!(out, "\n{}.{}({})", pad, method, node.new_expr());
+                }
+                Call::Add(method, node) => {
+                    let _ = write!(out, "\n{}.{}(\n{}    {}", pad, method, pad, node.new_expr());
+                    node.write_calls(out, indent + 2);
+                    let _ = write!(out, "\n{})", pad);
+                }
+                Call::Mut(method, node) => {
+                    let var = node.ty[..1].to_ascii_lowercase();
+                    let _ = write!(
+                        out,
+                        "\n{}.{}({:?}, |{}| {{\n{}    {}",
+                        pad, method, node.name, var, pad, var
+                    );
+                    node.write_calls(out, indent + 2);
+                    let _ = write!(out, "\n{}}})", pad);
+                }
+            }
+        }
+    }
+
+    fn new_expr(&self) -> String {
+        format!("clap::{}::new({:?})", self.ty, self.name)
+    }
+}
+
+impl Display for Builder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut out = self.root.new_expr();
+        self.root.write_calls(&mut out, 1);
+        f.write_str(&out)
+    }
+}
+
+/// Rust expression of a value given to a builder method.
+pub(crate) trait ToRust {
+    fn to_rust(&self) -> String;
+
+    /// Empty array of this type, as the type of `[]` may not be inferred.
+    const EMPTY_ARRAY: &'static str = "[]";
+}
+
+macro_rules! to_rust_debug {
+    ($($ty: ty),*) => {
+        $(
+            impl ToRust for $ty {
+                fn to_rust(&self) -> String {
+                    format!("{:?}", self)
+                }
+            }
+        )*
+    };
+}
+
+to_rust_debug!(bool, usize, char);
+
+impl ToRust for &str {
+    fn to_rust(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    const EMPTY_ARRAY: &'static str = "[\"\"; 0]";
+}
+
+impl ToRust for String {
+    fn to_rust(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl<T: ToRust> ToRust for Option<T> {
+    fn to_rust(&self) -> String {
+        match self {
+            Some(v) => format!("Some({})", v.to_rust()),
+            None => "None".to_owned(),
+        }
+    }
+}
+
+impl<T: ToRust> ToRust for Vec<T> {
+    fn to_rust(&self) -> String {
+        if self.is_empty() {
+            return T::EMPTY_ARRAY.to_owned();
+        }
+        let items: Vec<_> = self.iter().map(T::to_rust).collect();
+        format!("[{}]", items.join(", "))
+    }
+}
+
+impl<T0: ToRust, T1: ToRust> ToRust for (T0, T1) {
+    fn to_rust(&self) -> String {
+        format!("({}, {})", self.0.to_rust(), self.1.to_rust())
+    }
+}
+
+impl<T0: ToRust, T1: ToRust, T2: ToRust> ToRust for (T0, T1, T2) {
+    fn to_rust(&self) -> String {
+        format!(
+            "({}, {}, {})",
+            self.0.to_rust(),
+            self.1.to_rust(),
+            self.2.to_rust()
+        )
+    }
+}
@@ -0,0 +1,388 @@
+/*!
+Loaders for configs written for older versions of clap.
+
+[`clap2_yaml`] reads a yaml written for `load_yaml!` of clap 2 (and clap 3),
+and rewrites the legacy keys to the keys of this crate.
+```
+const CLI_YML: &str = r#"
+name: myapp
+settings:
+    - ArgRequiredElseHelp
+args:
+    - verbose:
+        short: v
+        multiple: true
+        help: Sets the level of verbosity
+    - config:
+        from_usage: -c, --config <FILE> 'Sets a config file'
+subcommands:
+    - test:
+        about: controls testing features
+        args:
+            - debug:
+                short: d
+                hidden: true
+"#;
+let docs = yaml_rust::YamlLoader::load_from_str(CLI_YML).expect("not a yaml");
+let migrated = clap_serde::compat::clap2_yaml(&docs[0]);
+for note in migrated.notes() {
+    println!("{}", note);
+}
+let app = migrated.to_app().expect("parse failed");
+assert!(app.is_arg_required_else_help_set());
+```
+*/
+
+use crate::{
+    de::snake_case,
+    migration::{arg_key, command_key, join, ArgShape, Migration, Rewrite},
+    MigrationNote,
+};
+use clap::Command;
+use yaml_rust::{yaml::Hash, Yaml};
+
+/// Config rewritten to the current keys by [`clap2_yaml`].
+#[derive(Debug, Clone)]
+pub struct Migrated {
+    yaml: Yaml,
+    notes: Vec<MigrationNote>,
+}
+
+impl Migrated {
+    /// The rewritten yaml.
+    pub fn yaml(&self) -> &Yaml {
+        &self.yaml
+    }
+
+    /// Notes of the rewritten keys, in the order they appear.
+    pub fn notes(&self) -> &[MigrationNote] {
+        &self.notes
+    }
+
+    /// Load the [`Command`] from the rewritten yaml.
+    #[allow(deprecated)]
+    pub fn to_app(&self) -> Result<Command<'_>, crate::yaml::Error> {
+        crate::yaml_to_app(&self.yaml)
+    }
+}
+
+/// Rewrite a yaml for `load_yaml!` of clap 2 or clap 3 to the current keys.
+///
+/// Keys this can't rewrite are kept as is, so [`Migrated::to_app`] reports them.
+pub fn clap2_yaml(yaml: &Yaml) -> Migrated {
+    let mut clap2 = Clap2 {
+        migration: Migration::new(),
+    };
+    let yaml = clap2.command("", yaml, &[]);
+    Migrated {
+        yaml,
+        notes: clap2.migration.notes,
+    }
+}
+
+/// Rewriter of the yaml hashes.
+struct Clap2 {
+    migration: Migration,
+}
+
+/// Key and value set instead of a legacy key.
+type Replacement = (String, Yaml);
+
+impl Clap2 {
+    fn command(&mut self, path: &str, yaml: &Yaml, globals: &[Replacement]) -> Yaml {
+        let entries = match entries(yaml) {
+            Some(entries) => entries,
+            None => return yaml.clone(),
+        };
+        let mut out = Hash::new();
+        let mut globals = globals.to_vec();
+        let mut subcommands = None;
+        for (k, v) in entries {
+            let key = match k.as_str() {
+                Some(key) => key,
+                None => {
+                    out.insert(k.clone(), v.clone());
+                    continue;
+                }
+            };
+            let p = join(path, key);
+            match key {
+                "args" => {
+                    let args = self.named_list(&p, v, Self::arg);
+                    out.insert(string("args"), args);
+                    continue;
+                }
+                "subcommands" => {
+                    subcommands = Some((p, v));
+                    continue;
+                }
+                "setting" | "settings" | "global_setting" | "global_settings" => {
+                    let global = key.starts_with("global");
+                    for name in one_or_many(v) {
+                        let name = match name.as_str() {
+                            Some(name) => name,
+                            None => {
+                                out.insert(k.clone(), v.clone());
+                                continue;
+                            }
+                        };
+                        for (key, value) in self.app_setting(&p, name) {
+                            out.insert(string(&key), value.clone());
+                            if global {
+                                globals.push((key, value));
+                            }
+                        }
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            match command_key(key) {
+                Some(rewrite) => {
+                    self.migration.rewrite(&p, key, rewrite);
+                    if let Rewrite::Rename(to) = rewrite {
+                        out.insert(string(to), v.clone());
+                    }
+                }
+                None => {
+                    out.insert(k.clone(), v.clone());
+                }
+            }
+        }
+        // Settings of the parent set by `global_setting` are inherited.
+        for (key, value) in &globals {
+            out.entry(string(key)).or_insert_with(|| value.clone());
+        }
+        if let Some((p, v)) = subcommands {
+            let subs = self.named_list(&p, v, |m, p, v| (None, m.command(p, v, &globals)));
+            out.insert(string("subcommands"), subs);
+        }
+        Yaml::Hash(out)
+    }
+
+    /// Rewrite the list of single-key hashes like `args` and `subcommands`.
+    ///
+    /// `f` returns the new key if it is changed, and the new value.
+    fn named_list(
+        &mut self,
+        path: &str,
+        yaml: &Yaml,
+        mut f: impl FnMut(&mut Self, &str, &Yaml) -> (Option<Yaml>, Yaml),
+    ) -> Yaml {
+        let entries = match entries(yaml) {
+            Some(entries) => entries,
+            None => return yaml.clone(),
+        };
+        let list = entries
+            .into_iter()
+            .map(|(k, v)| {
+                let p = join(path, k.as_str().unwrap_or_default());
+                let (new_k, v) = f(self, &p, v);
+                let mut h = Hash::new();
+                h.insert(new_k.unwrap_or_else(|| k.clone()), v);
+                Yaml::Hash(h)
+            })
+            .collect();
+        Yaml::Array(list)
+    }
+
+    /// Rewrite an arg, which is keyed by its usage string if it has `from_usage`.
+    fn arg(&mut self, path: &str, yaml: &Yaml) -> (Option<Yaml>, Yaml) {
+        let entries = match yaml {
+            Yaml::Null => Vec::new(),
+            _ => match entries(yaml) {
+                Some(entries) => entries,
+                None => return (None, yaml.clone()),
+            },
+        };
+        let shape = arg_shape(&entries);
+        let mut out = Hash::new();
+        let mut usage = None;
+        for (k, v) in entries {
+            let (key, rewrite) = match k.as_str().and_then(|key| Some((key, arg_key(key, shape)?)))
+            {
+                Some(rewrite) => rewrite,
+                None => {
+                    out.insert(k.clone(), v.clone());
+                    continue;
+                }
+            };
+            let p = join(path, key);
+            self.migration.rewrite(&p, key, rewrite);
+            match rewrite {
+                Rewrite::Rename(to) => {
+                    out.insert(string(to), v.clone());
+                }
+                Rewrite::Split(keys) => {
+                    for key in keys {
+                        out.insert(string(key), v.clone());
+                    }
+                }
+                Rewrite::Negate(to) => {
+                    let negated = v.as_bool().map_or_else(|| v.clone(), |b| Yaml::Boolean(!b));
+                    out.insert(string(to), negated);
+                }
+                Rewrite::Remove(_) => {}
+                Rewrite::Settings => {
+                    for name in one_or_many(v) {
+                        match name.as_str() {
+                            Some(name) => {
+                                for key in self.migration.arg_setting(&p, name, shape) {
+                                    out.insert(Yaml::String(key), Yaml::Boolean(true));
+                                }
+                            }
+                            None => {
+                                out.insert(k.clone(), v.clone());
+                            }
+                        }
+                    }
+                }
+                Rewrite::Usage => usage = v.as_str(),
+            }
+        }
+        let key = usage.zip(path.rsplit('.').next());
+        let key = key.map(|(usage, id)| Yaml::String(format!("{}: {}", id, usage)));
+        (key, Yaml::Hash(out))
+    }
+
+    fn app_setting(&mut self, path: &str, name: &str) -> Vec<Replacement> {
+        let key = snake_case(name);
+        let to: &[&'static str] = match key.as_str() {
+            "colored_help"
+            | "contains_last"
+            | "low_index_multiple_positional"
+            | "needs_long_help"
+            | "needs_long_version"
+            | "needs_subcommand_help"
+            | "propagate_global_values_down"
+            | "strict_utf8"
+            | "unified_help_message"
+            | "versionless_subcommands"
+            | "wait_on_error" => {
+                self.migration.note(
+                    path,
+                    format!(
+                        "`{}` is removed, as it is the default or no longer needed",
+                        name
+                    ),
+                );
+                return Vec::new();
+            }
+            "allow_invalid_utf8" => {
+                self.migration.note(
+                    path,
+                    format!(
+                        "`{}` is removed, use `allow_invalid_utf8` of args instead",
+                        name
+                    ),
+                );
+                return Vec::new();
+            }
+            "color_always" | "color_auto" | "color_never" => {
+                self.migration
+                    .note(path, format!("`{}` is replaced with `color`", name));
+                return vec![(
+                    "color".to_owned(),
+                    Yaml::String(variant(&key["color_".len()..])),
+                )];
+            }
+            "derive_display_order" => {
+                self.migration
+                    .note(path, format!("`{}` is replaced with `setting`", name));
+                return vec![("setting".to_owned(), Yaml::String(variant(&key)))];
+            }
+            "allow_leading_hyphen" => &["allow_hyphen_values"],
+            "args_negate_subcommands" => &["args_conflicts_with_subcommands"],
+            "disable_help_flags" => &["disable_help_flag"],
+            "disable_version" => &["disable_version_flag"],
+            "global_version" => &["propagate_version"],
+            "hidden" => &["hide"],
+            "hide_possible_values_in_help" => &["hide_possible_values"],
+            "subcommand_required_else_help" => &["subcommand_required", "arg_required_else_help"],
+            "subcommands_negate_reqs" => &["subcommand_negates_reqs"],
+            _ => {
+                if name != key {
+                    self.migration
+                        .note(path, format!("`{}` is replaced with `{}`", name, key));
+                }
+                return vec![(key, Yaml::Boolean(true))];
+            }
+        };
+        self.migration.replace(path, name, to);
+        to.iter()
+            .map(|k| (k.to_string(), Yaml::Boolean(true)))
+            .collect()
+    }
+}
+
+/// Shape of the arg of `entries`.
+fn arg_shape(entries: &[(&Yaml, &Yaml)]) -> ArgShape {
+    let get = |key: &str| {
+        entries
+            .iter()
+            .find(|(k, _)| k.as_str() == Some(key))
+            .map(|(_, v)| *v)
+    };
+    let keys = entries
+        .iter()
+        .filter_map(|(k, _)| k.as_str().map(str::to_owned))
+        .collect::<Vec<_>>();
+    let settings = ["set", "setting", "settings"]
+        .iter()
+        .filter_map(|k| get(k))
+        .flat_map(one_or_many)
+        .filter_map(|s| s.as_str().map(str::to_owned))
+        .collect::<Vec<_>>();
+    ArgShape::of(
+        &keys,
+        get("takes_value").and_then(Yaml::as_bool),
+        &settings,
+        get("from_usage").and_then(Yaml::as_str),
+    )
+}
+
+/// Entries of a hash, or of a list of hashes.
+fn entries(yaml: &Yaml) -> Option<Vec<(&Yaml, &Yaml)>> {
+    match yaml {
+        Yaml::Hash(h) => Some(h.iter().collect()),
+        Yaml::Array(a) => a
+            .iter()
+            .map(|y| y.as_hash().map(|h| h.iter()))
+            .collect::<Option<Vec<_>>>()
+            .map(|hs| hs.into_iter().flatten().collect()),
+        _ => None,
+    }
+}
+
+fn one_or_many(yaml: &Yaml) -> Vec<&Yaml> {
+    match yaml {
+        Yaml::Array(a) => a.iter().collect(),
+        y => vec![y],
+    }
+}
+
+fn string(s: &str) -> Yaml {
+    Yaml::String(s.to_owned())
+}
+
+/// Name of an enum variant in the case of the case-key feature.
+fn variant(snake: &str) -> String {
+    #[cfg(feature = "snake-case-key")]
+    {
+        snake.to_owned()
+    }
+    #[cfg(feature = "kebab-case-key")]
+    {
+        snake.replace('_', "-")
+    }
+    #[cfg(feature = "pascal-case-key")]
+    {
+        snake
+            .split('_')
+            .flat_map(|w| {
+                let mut cs = w.chars();
+                cs.next().into_iter().flat_map(char::to_uppercase).chain(cs)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,244 @@
+use clap::{Arg, Command};
+
+/// A flag or a subcommand name taken by two keys, found by [`find_conflicts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// The flag like `-v` or `--verbose`, or the subcommand name.
+    pub name: String,
+    /// Path of the key which takes it first, like `args.verbose.short`.
+    pub first: String,
+    /// Path of the other key, like `subcommands.test.args.version.short`.
+    pub second: String,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` is taken by both {} and {}",
+            self.name, self.first, self.second
+        )
+    }
+}
+
+/**
+Find the short flags, long flags and subcommand names taken more than once in the command tree.
+
+Args, their aliases, and `short_flag`, `long_flag` and their aliases of the subcommands are checked in each command,
+with the `global` args of the parent commands propagated as clap does.
+The `help` and `version` args generated by clap are checked only if clap keeps them,
+and the `-h` and `-V` it gives them only if free are not checked.
+Subcommand names and aliases are checked among the sibling subcommands.
+```
+const CLAP_TOML: &'static str = r#"
+name = "app"
+[args]
+verbose = { short = "v", global = true }
+[subcommands.build.args]
+version = { short = "v" }
+"#;
+let app = clap_serde::load(&mut toml::Deserializer::new(CLAP_TOML)).expect("parse failed");
+let conflicts = clap_serde::find_conflicts(&app);
+assert_eq!(conflicts[0].name, "-v");
+assert_eq!(conflicts[0].first, "args.verbose.short");
+assert_eq!(conflicts[0].second, "subcommands.build.args.version.short");
+```
+*/
+pub fn find_conflicts(command: &Command) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    walk(command, "", &[], &mut conflicts);
+    conflicts
+}
+
+/// A flag or a name taken by a key.
+struct Taken<'a> {
+    name: String,
+    /// Id of the arg or name of the subcommand taking it.
+    owner: &'a str,
+    arg: bool,
+    path: String,
+}
+
+fn walk<'a>(command: &'a Command, prefix: &str, globals: &[Taken<'a>], out: &mut Vec<Conflict>) {
+    let mut flags = Vec::new();
+    for taken in globals {
+        // A global arg is not propagated to the command which has an arg of the same id.
+        if !command.get_arguments().any(|a| a.get_id() == taken.owner) {
+            flags.push(Taken {
+                name: taken.name.clone(),
+                owner: taken.owner,
+                arg: true,
+                path: taken.path.clone(),
+            });
+        }
+    }
+    let inherited = flags.len();
+    for arg in args(command) {
+        arg_flags(arg, prefix, &mut flags);
+    }
+    let mut names = Vec::new();
+    for sub in command.get_subcommands() {
+        sub_flags(sub, prefix, &mut flags, &mut names);
+    }
+    check(&flags, out);
+    check(&names, out);
+
+    let next = flags
+        .iter()
+        .enumerate()
+        .filter(|(i, t)| {
+            *i < inherited
+                || command
+                    .get_arguments()
+                    .any(|a| t.arg && a.is_global_set() && a.get_id() == t.owner)
+        })
+        .map(|(_, t)| Taken {
+            name: t.name.clone(),
+            owner: t.owner,
+            arg: true,
+            path: t.path.clone(),
+        })
+        .collect::<Vec<_>>();
+    for sub in command.get_subcommands() {
+        let prefix = format!("{}subcommands.{}.", prefix, sub.get_name());
+        walk(sub, &prefix, &next, out);
+    }
+}
+
+/// The args of `command` which clap keeps, without the generated `help` and `version` it drops.
+pub(crate) fn args<'c, 'a>(command: &'c Command<'a>) -> impl Iterator<Item = &'c Arg<'a>> {
+    let dropped = [
+        dropped(command, "help", command.is_disable_help_flag_set()),
+        dropped(
+            command,
+            "version",
+            command.is_disable_version_flag_set()
+                || (command.get_version().is_none() && command.get_long_version().is_none()),
+        ),
+    ];
+    command
+        .get_arguments()
+        .filter(move |a| !dropped.iter().flatten().any(|d| std::ptr::eq(*a, *d)))
+}
+
+/// The arg `id` generated by clap, if clap drops it from `command`, as it does if `disabled`,
+/// or if another arg has the id or takes `--id`, or a subcommand takes `--id`.
+///
+/// An arg replacing it by `mut_arg`, as with `override-arg`, is kept by clap, so only the arg as generated is dropped.
+fn dropped<'c, 'a>(command: &'c Command<'a>, id: &str, disabled: bool) -> Option<&'c Arg<'a>> {
+    let generated = command.get_arguments().find(|a| {
+        a.get_id() == id
+            && a.get_long() == Some(id)
+            && a.get_short().is_none()
+            && a.is_global_set()
+            && a.get_help() == Some(&format!("Print {} information", id))
+    })?;
+    let replaced = command
+        .get_arguments()
+        .any(|a| !std::ptr::eq(a, generated) && (a.get_id() == id || a.get_long() == Some(id)))
+        || command
+            .get_subcommands()
+            .any(|sub| sub.get_long_flag() == Some(id));
+    (disabled || replaced).then_some(generated)
+}
+
+fn arg_flags<'a>(arg: &'a Arg, prefix: &str, flags: &mut Vec<Taken<'a>>) {
+    let id = arg.get_id();
+    let mut push = |name: String, key: &str| {
+        flags.push(Taken {
+            name,
+            owner: id,
+            arg: true,
+            path: format!("{}args.{}.{}", prefix, id, key),
+        })
+    };
+    if let Some(s) = arg.get_short() {
+        push(format!("-{}", s), "short");
+    }
+    let visible = arg.get_visible_short_aliases().unwrap_or_default();
+    for s in arg.get_all_short_aliases().unwrap_or_default() {
+        let key = match visible.contains(&s) {
+            true => "visible_short_aliases",
+            false => "short_aliases",
+        };
+        push(format!("-{}", s), key);
+    }
+    if let Some(l) = arg.get_long() {
+        push(format!("--{}", l), "long");
+    }
+    let visible = arg.get_visible_aliases().unwrap_or_default();
+    for l in arg.get_all_aliases().unwrap_or_default() {
+        let key = match visible.contains(&l) {
+            true => "visible_aliases",
+            false => "aliases",
+        };
+        push(format!("--{}", l), key);
+    }
+}
+
+fn sub_flags<'a>(
+    sub: &'a Command,
+    prefix: &str,
+    flags: &mut Vec<Taken<'a>>,
+    names: &mut Vec<Taken<'a>>,
+) {
+    let name = sub.get_name();
+    let path = |key: &str| format!("{}subcommands.{}.{}", prefix, name, key);
+    let taken = |taken: String, key: &str| Taken {
+        name: taken,
+        owner: name,
+        arg: false,
+        path: path(key),
+    };
+    if let Some(s) = sub.get_short_flag() {
+        flags.push(taken(format!("-{}", s), "short_flag"));
+    }
+    let visible = sub.get_visible_short_flag_aliases().collect::<Vec<_>>();
+    for s in sub.get_all_short_flag_aliases() {
+        let key = match visible.contains(&s) {
+            true => "visible_short_flag_aliases",
+            false => "short_flag_aliases",
+        };
+        flags.push(taken(format!("-{}", s), key));
+    }
+    if let Some(l) = sub.get_long_flag() {
+        flags.push(taken(format!("--{}", l), "long_flag"));
+    }
+    let visible = sub.get_visible_long_flag_aliases().collect::<Vec<_>>();
+    for l in sub.get_all_long_flag_aliases() {
+        let key = match visible.contains(&l) {
+            true => "visible_long_flag_aliases",
+            false => "long_flag_aliases",
+        };
+        flags.push(taken(format!("--{}", l), key));
+    }
+    names.push(taken(name.to_owned(), "name"));
+    let visible = sub.get_visible_aliases().collect::<Vec<_>>();
+    for a in sub.get_all_aliases() {
+        let key = match visible.contains(&a) {
+            true => "visible_aliases",
+            false => "aliases",
+        };
+        names.push(taken(a.to_owned(), key));
+    }
+}
+
+/// Report the names taken by different owners, once for each pair of keys.
+fn check(taken: &[Taken], out: &mut Vec<Conflict>) {
+    for (i, second) in taken.iter().enumerate() {
+        if let Some(first) = taken[..i]
+            .iter()
+            .find(|f| f.name == second.name && (f.owner, f.arg) != (second.owner, second.arg))
+        {
+            let conflict = Conflict {
+                name: second.name.clone(),
+                first: first.path.clone(),
+                second: second.path.clone(),
+            };
+            // Globals propagated to the subcommands would report the same pair again.
+            if !out.contains(&conflict) {
+                out.push(conflict);
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 #![allow(deprecated)]
-use clap::{AppFlags, AppSettings};
+use clap::AppSettings;
 use serde::{de::DeserializeSeed, Deserialize};
 
 enum_de!(AppSettings,AppSetting1,
@@ -33,7 +33,7 @@ enum_de!(AppSettings,AppSetting1,
     SubcommandRequiredElseHelp,
     #[cfg(feature="allow-deprecated")]
     AllowExternalSubcommands,
-    #[cfg(all(feature = "unstable-multicall", feature="allow-depreacted"))]
+    #[cfg(feature="allow-deprecated")]
     Multicall,
     #[cfg(feature="allow-deprecated")]
     AllowInvalidUtf8ForExternalSubcommands,
@@ -86,17 +86,7 @@ impl<'de> DeserializeSeed<'de> for AppSettingSeed {
     }
 }
 
-pub(crate) struct AppSettingsSeed;
-impl<'de> DeserializeSeed<'de> for AppSettingsSeed {
-    type Value = AppFlags;
-
-    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        Vec::<AppSetting1>::deserialize(deserializer).map(|s| {
-            s.into_iter()
-                .fold(AppFlags::default(), |a, b| a | AppSettings::from(b))
-        })
-    }
+/// Rust expression of the setting.
+pub(crate) fn setting_rust(s: AppSettings) -> String {
+    format!("clap::AppSettings::{:?}", s)
 }
@@ -1,10 +1,17 @@
-use crate::CommandWrap;
+use crate::{
+    codegen::{Call, Node},
+    de::{Context, OneOrMany},
+    CommandWrap,
+};
 use appsettings::*;
 use clap::Command;
+#[cfg(feature = "color")]
+use color::ColorChoiceSeed;
 use serde::{
     de::{DeserializeSeed, Error, Visitor},
     Deserialize,
 };
+use std::collections::HashSet;
 
 mod appsettings;
 #[cfg(feature = "color")]
@@ -16,22 +23,34 @@ impl<'de> Deserialize<'de> for CommandWrap<'de> {
     where
         D: serde::Deserializer<'de>,
     {
-        deserializer
-            .deserialize_map(CommandVisitor(Command::new(TMP_APP_NAME)))
-            //check the name so as not to expose the tmp name.
-            .and_then(|r| {
-                if r.app.get_name() != TMP_APP_NAME {
-                    Ok(r)
-                } else {
-                    Err(<D::Error>::missing_field("name"))
-                }
-            })
+        load_root(deserializer, &mut Context::new())
     }
 }
 
-struct CommandVisitor<'a>(Command<'a>);
+/// Load the root command, which must have `name`, with `ctx`.
+pub(crate) fn load_root<'de, D>(
+    deserializer: D,
+    ctx: &mut Context<'de>,
+) -> Result<CommandWrap<'de>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserializer
+        .deserialize_map(CommandVisitor(Command::new(TMP_APP_NAME), &mut *ctx))
+        //check the name so as not to expose the tmp name.
+        .and_then(|r| {
+            if r.app.get_name() != TMP_APP_NAME {
+                Ok(r)
+            } else {
+                Err(<D::Error>::missing_field("name"))
+            }
+        })
+        .and_then(|r| ctx.finish(r.app).map_err(<D::Error>::custom))
+}
+
+struct CommandVisitor<'a, 'c>(Command<'a>, &'c mut Context<'a>);
 
-impl<'a> Visitor<'a> for CommandVisitor<'a> {
+impl<'a, 'c> Visitor<'a> for CommandVisitor<'a, 'c> {
     type Value = CommandWrap<'a>;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -43,14 +62,17 @@ impl<'a> Visitor<'a> for CommandVisitor<'a> {
         A: serde::de::MapAccess<'a>,
     {
         let mut app = self.0;
+        let ctx = self.1;
+        let mut keys = HashSet::new();
         //TODO: check the first key to get name from the input?
         //currently the name change in `Clap::Command::name` doesn't change the `Clap::Command::id` so might cause problems?
         while let Some(key) = map.next_key::<&str>()? {
+            ctx.check_key(key, &mut keys)?;
             app = parse_value!(key, app, map, Command, {
                 (about, &str),
                 (after_help, &str),
                 (after_long_help, &str),
-                (alias, &str),
+                // alias : one_or_many
                 ref (aliases, Vec<&str>),
                 (allow_external_subcommands, bool),
                 (allow_hyphen_values, bool),
@@ -88,7 +110,7 @@ impl<'a> Visitor<'a> for CommandVisitor<'a> {
                 (infer_subcommands, bool),
                 (long_about, &str),
                 (long_flag, &str),
-                (long_flag_alias, &str),
+                // long_flag_alias : one_or_many
                 ref (long_flag_aliases, Vec<&str>),
                 (long_version, &str),
                 (max_term_width, usize),
@@ -104,7 +126,7 @@ impl<'a> Visitor<'a> for CommandVisitor<'a> {
                 // setting : specialized
                 // settings : specialized (though the original method is deprecated)
                 (short_flag, char),
-                (short_flag_alias, char),
+                // short_flag_alias : one_or_many
                 ref (short_flag_aliases, Vec<char>),
                 // subcommand : not supported single subcommand(now)
                 // subcommands : specialized
@@ -112,18 +134,25 @@ impl<'a> Visitor<'a> for CommandVisitor<'a> {
                 (subcommand_negates_reqs, bool),
                 (subcommand_required, bool),
                 (subcommand_value_name, &str),
-                (propagate_version, bool),
                 (term_width, usize),
                 (trailing_var_arg, bool),
                 (version, &str),
-                (visible_alias, &str),
+                // visible_alias : one_or_many
                 ref (visible_aliases, Vec<&str>),
-                (visible_long_flag_alias, &str),
+                // visible_long_flag_alias : one_or_many
                 ref (visible_long_flag_aliases, Vec<&str>),
-                (visible_short_flag_alias, char),
+                // visible_short_flag_alias : one_or_many
                 ref (visible_short_flag_aliases, Vec<char>),
             },
-            deprecated: [
+            one_or_many: {
+                ref (alias, &str) => aliases,
+                ref (long_flag_alias, &str) => long_flag_aliases,
+                ref (short_flag_alias, char) => short_flag_aliases,
+                ref (visible_alias, &str) => visible_aliases,
+                ref (visible_long_flag_alias, &str) => visible_long_flag_aliases,
+                ref (visible_short_flag_alias, char) => visible_short_flag_aliases,
+            },
+            deprecated(ctx): [
                 "help_message",
                 "version_message",
             ]{
@@ -135,43 +164,91 @@ impl<'a> Visitor<'a> for CommandVisitor<'a> {
             },
             specialize:
             [
-                "args" => map.next_value_seed(super::arg::Args::<true>(app))?
-                "args_map" => map.next_value_seed(super::arg::Args::<false>(app))?
+                "args" => map.next_value_seed(super::arg::Args::<true>(app, ctx))?
+                "args_map" => map.next_value_seed(super::arg::Args::<false>(app, ctx))?
+                "auto_long" => {
+                    ctx.frame().auto.auto_long = Some(map.next_value()?);
+                    app
+                }
+                "auto_value_name" => {
+                    ctx.frame().auto.auto_value_name = Some(map.next_value()?);
+                    app
+                }
+                "auto_env" => {
+                    #[cfg(feature = "env")] {
+                        ctx.frame().auto.auto_env = Some(map.next_value()?);
+                        app
+                    }
+                    #[cfg(not(feature = "env"))] { return Err(Error::custom("env feature disabled"))}}
                 "color" => {
-                    #[cfg(color)] {
-                        app.color(map.next_value_seed(ColorChoiceSeed)?)
+                    #[cfg(feature = "color")] {
+                        let color = map.next_value_seed(ColorChoiceSeed)?;
+                        ctx.record("color", || vec![format!("clap::ColorChoice::{:?}", color)]);
+                        app.color(color)
+                    }
+                    #[cfg(not(feature = "color"))] { return Err(Error::custom("color feature disabled"))}}
+                "env_prefix" => {
+                    #[cfg(feature = "env")] {
+                        ctx.frame().auto.env_prefix = Some(parse_value_inner!(map, &str, ctx));
+                        app
+                    }
+                    #[cfg(not(feature = "env"))] { return Err(Error::custom("env feature disabled"))}}
+                "subcommands" => map.next_value_seed(SubCommands::<true>(app, ctx))?
+                "subcommands_map" => map.next_value_seed(SubCommands::<false>(app, ctx))?
+                "groups" => map.next_value_seed(super::group::Groups(app, ctx))?
+                "setting" => {
+                    let s = map.next_value_seed(AppSettingSeed)?;
+                    ctx.record("setting", || vec![setting_rust(s)]);
+                    app.setting(s)
+                }
+                "settings" => {
+                    let sets = map.next_value::<OneOrMany<AppSetting1>>()?.0.into_iter().map(|s|s.into());
+                    for s in sets {
+                        ctx.record("setting", || vec![setting_rust(s)]);
+                        app = app.setting(s);
                     }
-                    #[cfg(not(color))] { return Err(Error::custom("color feature disabled"))}}
-                "subcommands" => map.next_value_seed(SubCommands::<true>(app))?
-                "subcommands_map" => map.next_value_seed(SubCommands::<false>(app))?
-                "groups" => map.next_value_seed(super::group::Groups(app))?
-                "setting" => app.setting(map.next_value_seed(AppSettingSeed)?)
-                "settings" => app.setting(map.next_value_seed(AppSettingsSeed)?)
-                "global_setting" => app.global_setting(map.next_value_seed(AppSettingSeed)?)
+                    app
+                }
+                "global_setting" => {
+                    let s = map.next_value_seed(AppSettingSeed)?;
+                    ctx.record("global_setting", || vec![setting_rust(s)]);
+                    app.global_setting(s)
+                }
                 "global_settings" => {
-                    let sets = map.next_value::<Vec<AppSetting1>>()?.into_iter().map(|s|s.into());
-                    for s in sets{
+                    let sets = map.next_value::<OneOrMany<AppSetting1>>()?.0.into_iter().map(|s|s.into());
+                    for s in sets {
+                        ctx.record("global_setting", || vec![setting_rust(s)]);
                         app = app.global_setting(s);
                     }
                     app
                 }
-            ]);
+            ],
+            unknown(ctx => ctx.frame().metadata));
         }
 
-        Ok(CommandWrap { app })
+        Ok(app.into())
     }
 }
 
-pub struct NameSeed<'a>(&'a str);
+pub struct NameSeed<'a, 'c>(&'a str, &'c mut Context<'a>);
 
-impl<'de> DeserializeSeed<'de> for NameSeed<'de> {
+impl<'de, 'c> DeserializeSeed<'de> for NameSeed<'de, 'c> {
     type Value = CommandWrap<'de>;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        deserializer.deserialize_map(CommandVisitor(Command::new(self.0)))
+        let ctx = self.1;
+        ctx.enter(self.0)?;
+        ctx.begin_recording(
+            || Node::new("Command", self.0),
+            |n| Call::Add("subcommand", n),
+        );
+        let sub = deserializer.deserialize_map(CommandVisitor(Command::new(self.0), ctx))?;
+        ctx.end_recording();
+        ctx.leave(self.0, sub.app.get_name())?;
+        Ok(sub)
     }
 }
 
@@ -182,12 +259,15 @@ impl<'de> DeserializeSeed<'de> for CommandWrap<'de> {
     where
         D: serde::Deserializer<'de>,
     {
-        deserializer.deserialize_map(CommandVisitor(self.app))
+        let mut ctx = Context::new();
+        deserializer
+            .deserialize_map(CommandVisitor(self.app, &mut ctx))
+            .and_then(|r| ctx.finish(r.app).map_err(<D::Error>::custom))
     }
 }
 
-struct SubCommands<'a, const KV_ARRAY: bool>(Command<'a>);
-impl<'de, const KV_ARRAY: bool> DeserializeSeed<'de> for SubCommands<'de, KV_ARRAY> {
+struct SubCommands<'a, 'c, const KV_ARRAY: bool>(Command<'a>, &'c mut Context<'a>);
+impl<'de, 'c, const KV_ARRAY: bool> DeserializeSeed<'de> for SubCommands<'de, 'c, KV_ARRAY> {
     type Value = Command<'de>;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
@@ -202,7 +282,7 @@ impl<'de, const KV_ARRAY: bool> DeserializeSeed<'de> for SubCommands<'de, KV_ARR
     }
 }
 
-impl<'de, const KV_ARRAY: bool> Visitor<'de> for SubCommands<'de, KV_ARRAY> {
+impl<'de, 'c, const KV_ARRAY: bool> Visitor<'de> for SubCommands<'de, 'c, KV_ARRAY> {
     type Value = Command<'de>;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -215,7 +295,7 @@ impl<'de, const KV_ARRAY: bool> Visitor<'de> for SubCommands<'de, KV_ARRAY> {
     {
         let mut app = self.0;
         while let Some(name) = map.next_key::<&str>()? {
-            let sub = map.next_value_seed(NameSeed(name))?;
+            let sub = map.next_value_seed(NameSeed(name, &mut *self.1))?;
             app = app.subcommand(sub);
         }
         Ok(app)
@@ -226,15 +306,15 @@ impl<'de, const KV_ARRAY: bool> Visitor<'de> for SubCommands<'de, KV_ARRAY> {
         A: serde::de::SeqAccess<'de>,
     {
         let mut app = self.0;
-        while let Some(sub) = seq.next_element_seed(InnerSubCommand)? {
+        while let Some(sub) = seq.next_element_seed(InnerSubCommand(&mut *self.1))? {
             app = app.subcommand(sub)
         }
         Ok(app)
     }
 }
 
-pub struct InnerSubCommand;
-impl<'de> Visitor<'de> for InnerSubCommand {
+pub struct InnerSubCommand<'a, 'c>(&'c mut Context<'a>);
+impl<'de, 'c> Visitor<'de> for InnerSubCommand<'de, 'c> {
     type Value = Command<'de>;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -248,12 +328,12 @@ impl<'de> Visitor<'de> for InnerSubCommand {
         let k = map
             .next_key()?
             .ok_or_else(|| A::Error::invalid_length(0, &"missing command in subcommand"))?;
-        let com = map.next_value_seed(NameSeed(k))?;
+        let com = map.next_value_seed(NameSeed(k, self.0))?;
         Ok(com.into())
     }
 }
 
-impl<'de> DeserializeSeed<'de> for InnerSubCommand {
+impl<'de, 'c> DeserializeSeed<'de> for InnerSubCommand<'de, 'c> {
     type Value = Command<'de>;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
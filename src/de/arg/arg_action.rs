@@ -1,8 +1,9 @@
-use clap::ArgAction as AA;
-use serde::Deserialize;
+use crate::de::Context;
+use clap::{builder::ValueParser, Arg, ArgAction as AA};
+use serde::{de::Error, Deserialize};
 
 enum_de!(AA, ArgAction,
-    #[derive(Deserialize, Clone, Copy)]
+    #[derive(Deserialize, Clone, Copy, Debug)]
     #[cfg_attr(feature = "kebab-case-key" ,serde(rename_all = "kebab-case"))]
     #[cfg_attr(feature = "snake-case-key" ,serde(rename_all = "snake_case"))]
     {
@@ -17,3 +18,64 @@ enum_de!(AA, ArgAction,
         Version,
     }
 );
+
+impl ArgAction {
+    /// Same as [`clap::ArgAction::takes_values`].
+    fn takes_values(self) -> bool {
+        matches!(self, Self::Set | Self::Append | Self::StoreValue)
+    }
+
+    /// The value parser clap sets when building the arg, if no value parser is set,
+    /// with its Rust expression.
+    fn default_value_parser(self) -> Option<(ValueParser, &'static str)> {
+        match self {
+            Self::SetTrue | Self::SetFalse => {
+                Some((ValueParser::bool(), "clap::builder::ValueParser::bool()"))
+            }
+            Self::Count => Some((clap::value_parser!(u8).into(), "clap::value_parser!(u8)")),
+            _ => None,
+        }
+    }
+}
+
+/// Keys in an arg map which are checked against the `action` after all keys are read.
+#[derive(Default)]
+pub(crate) struct ActionCheck {
+    pub(crate) action: Option<ArgAction>,
+    pub(crate) takes_value: Option<bool>,
+    pub(crate) multiple_values: Option<bool>,
+    pub(crate) value_parser: bool,
+}
+
+impl ActionCheck {
+    /// Check the keys conflicting with the action,
+    /// and set `takes_value` and `value_parser` as clap does when building the arg.
+    pub(crate) fn apply<'a, E: Error>(self, arg: Arg<'a>, ctx: &mut Context) -> Result<Arg<'a>, E> {
+        let action = match self.action {
+            Some(action) => action,
+            None => return Ok(arg),
+        };
+        let takes_values = action.takes_values();
+        if let Some(takes_value) = self.takes_value.filter(|t| *t != takes_values) {
+            return Err(E::custom(format_args!(
+                "`takes_value = {}` conflicts with action `{:?}`",
+                takes_value, action
+            )));
+        }
+        if self.multiple_values == Some(true) && !takes_values {
+            return Err(E::custom(format_args!(
+                "`multiple_values = true` conflicts with action `{:?}`",
+                action
+            )));
+        }
+        ctx.record("takes_value", || vec![takes_values.to_string()]);
+        let arg = arg.takes_value(takes_values);
+        Ok(match action.default_value_parser() {
+            Some((value_parser, rust)) if !self.value_parser => {
+                ctx.record("value_parser", || vec![rust.to_owned()]);
+                arg.value_parser(value_parser)
+            }
+            _ => arg,
+        })
+    }
+}
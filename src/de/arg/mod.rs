@@ -1,22 +1,54 @@
-use self::{arg_action::ArgAction, value_hint::ValueHint, value_parser::ValueParser};
-use crate::ArgWrap;
+use self::{
+    arg_action::{ActionCheck, ArgAction},
+    value_hint::ValueHint,
+    value_parser::ValueParser,
+};
+use crate::{
+    codegen::{Call, Node, ToRust},
+    de::{ArgFrame, Context},
+    ArgWrap,
+};
 use clap::{Arg, Command};
 use serde::de::{DeserializeSeed, Error, Visitor};
-use std::marker::PhantomData;
+use std::collections::HashSet;
 
 mod arg_action;
+mod usage;
 mod value_hint;
 mod value_parser;
 
+pub(crate) use value_parser::enum_de_value;
+#[cfg(feature = "clap4")]
+pub(crate) use value_parser::value_parsers;
+
 #[cfg(feature = "override-arg")]
-struct ArgKVO<'a>(Option<Command<'a>>);
+struct ArgKVO<'a, 'c>(Command<'a>, &'c mut Context<'a>);
 
 #[cfg(feature = "override-arg")]
-impl<'de> Visitor<'de> for &mut ArgKVO<'de> {
+impl<'de, 'c> Visitor<'de> for &mut ArgKVO<'de, 'c> {
     type Value = ();
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("kv argument")
+        formatter.write_str("kv argument or usage")
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        let arg = ArgVisitor::new_usage(v, &mut *self.1)?.finish()?;
+        self.0 = std::mem::take(&mut self.0).arg(arg);
+        Ok(())
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        // A string with escapes can't be borrowed, so it is kept in the store.
+        self.1.check_len(v.len()).map_err(E::custom)?;
+        let v = crate::strings::keep(self.1.strings(), v.to_owned()).map_err(E::custom)?;
+        self.visit_borrowed_str(v)
     }
 
     fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
@@ -26,40 +58,63 @@ impl<'de> Visitor<'de> for &mut ArgKVO<'de> {
         let name: &str = map
             .next_key()?
             .ok_or_else(|| A::Error::missing_field("argument"))?;
+        let app = std::mem::take(&mut self.0);
+        let ctx = &mut *self.1;
+        if usage::is_usage(name) {
+            let arg = map.next_value_seed(ArgVisitor::new_key(name, ctx)?)?;
+            self.0 = app.arg(arg);
+            return Ok(());
+        }
         let mut status = Ok(());
-        let app = unsafe { self.0.take().unwrap_unchecked() };
-        let next = app.mut_arg(name, |a| match map.next_value_seed(ArgVisitor(a)) {
-            Ok(a) => a.into(),
-            Err(e) => {
-                status = Err(e);
-                Arg::new(name)
+        self.0 = app.mut_arg(name, |a| {
+            match map.next_value_seed(ArgVisitor::new_mut(a, ctx)) {
+                Ok(a) => a.into(),
+                Err(e) => {
+                    status = Err(e);
+                    Arg::new(name)
+                }
             }
         });
-        self.0.replace(next);
         status
     }
 }
 
 #[cfg(feature = "override-arg")]
-impl<'de> DeserializeSeed<'de> for &mut ArgKVO<'de> {
+impl<'de, 'c> DeserializeSeed<'de> for &mut ArgKVO<'de, 'c> {
     type Value = ();
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        deserializer.deserialize_map(self)
+        deserializer.deserialize_any(self)
     }
 }
 
-#[derive(Clone, Copy)]
-struct ArgKV<'de>(PhantomData<&'de ()>);
+struct ArgKV<'de, 'c>(&'c mut Context<'de>);
 
-impl<'de> Visitor<'de> for ArgKV<'de> {
+impl<'de, 'c> Visitor<'de> for ArgKV<'de, 'c> {
     type Value = ArgWrap<'de>;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("kv argument")
+        formatter.write_str("kv argument or usage")
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        ArgVisitor::new_usage(v, self.0)?.finish()
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        // A string with escapes can't be borrowed, so it is kept in the store.
+        self.0.check_len(v.len()).map_err(E::custom)?;
+        let v = crate::strings::keep(self.0.strings(), v.to_owned()).map_err(E::custom)?;
+        self.visit_borrowed_str(v)
     }
 
     fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
@@ -69,30 +124,59 @@ impl<'de> Visitor<'de> for ArgKV<'de> {
         let name: &str = map
             .next_key()?
             .ok_or_else(|| A::Error::missing_field("argument"))?;
-        map.next_value_seed(ArgVisitor::new_str(name))
+        map.next_value_seed(ArgVisitor::new_key(name, self.0)?)
     }
 }
 
-impl<'de> DeserializeSeed<'de> for ArgKV<'de> {
+impl<'de, 'c> DeserializeSeed<'de> for ArgKV<'de, 'c> {
     type Value = ArgWrap<'de>;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        deserializer.deserialize_map(self)
+        deserializer.deserialize_any(self)
     }
 }
 
-struct ArgVisitor<'a>(Arg<'a>);
+struct ArgVisitor<'a, 'c>(Arg<'a>, &'c mut Context<'a>);
+
+impl<'a, 'c> ArgVisitor<'a, 'c> {
+    /// The arg named by the key in the args, which is an id or a usage string.
+    fn new_key<E: Error>(key: &'a str, ctx: &'c mut Context<'a>) -> Result<Self, E> {
+        if usage::is_usage(key) {
+            Self::new_usage(key, ctx)
+        } else {
+            ctx.begin_recording(|| Node::new("Arg", key), |n| Call::Add("arg", n));
+            Ok(Self(Arg::new(key), ctx))
+        }
+    }
+
+    fn new_usage<E: Error>(usage: &'a str, ctx: &'c mut Context<'a>) -> Result<Self, E> {
+        let arg = usage::parse_usage(usage, ctx).map_err(E::custom)?;
+        Ok(Self(arg, ctx))
+    }
+
+    /// The arg already in the command, which is overridden by `mut_arg`.
+    #[cfg(feature = "override-arg")]
+    fn new_mut(arg: Arg<'a>, ctx: &'c mut Context<'a>) -> Self {
+        ctx.begin_recording(
+            || Node::new("Arg", arg.get_id()),
+            |n| Call::Mut("mut_arg", n),
+        );
+        Self(arg, ctx)
+    }
 
-impl<'a> ArgVisitor<'a> {
-    fn new_str(v: &'a str) -> Self {
-        Self(Arg::new(v))
+    /// The arg without any more keys.
+    fn finish<E: Error>(self) -> Result<ArgWrap<'a>, E> {
+        self.1.arg_node(self.0.get_id())?;
+        self.1.end_recording();
+        self.1.add_arg(self.0.get_id(), ArgFrame::default())?;
+        Ok(ArgWrap { arg: self.0 })
     }
 }
 
-impl<'a> Visitor<'a> for ArgVisitor<'a> {
+impl<'a, 'c> Visitor<'a> for ArgVisitor<'a, 'c> {
     type Value = ArgWrap<'a>;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -104,17 +188,24 @@ impl<'a> Visitor<'a> for ArgVisitor<'a> {
         A: serde::de::MapAccess<'a>,
     {
         let mut arg = self.0;
+        let ctx = self.1;
+        let mut action = ActionCheck::default();
+        let mut frame = ArgFrame::default();
+        let mut keys = HashSet::new();
+        ctx.arg_node(arg.get_id())?;
+        ctx.push_path("args", arg.get_id());
 
         while let Some(key) = map.next_key::<&str>()? {
+            ctx.check_key(key, &mut keys)?;
             arg = parse_value!(key, arg, map, Arg, {
-                    // action : specailized
-                    (alias, &str),
+                    // action : specialized
+                    // alias : one_or_many
                     ref (aliases, Vec<&str>),
                     (allow_hyphen_values, bool),
                     (allow_invalid_utf8, bool),
-                    (conflicts_with, &str),
+                    // conflicts_with : one_or_many
                     ref (conflicts_with_all, Vec<&str>),
-                    (default_missing_value, &str),
+                    // default_missing_value : one_or_many
                     ref (default_missing_values, Vec<&str>),
                     // (default_missing_value_os, &OsStr), // need Deseriaze to OsStr
                     // ref (default_missing_values_os, Vec<&OsStr>),
@@ -127,7 +218,7 @@ impl<'a> Visitor<'a> for ArgVisitor<'a> {
                     (exclusive, bool),
                     (forbid_empty_values, bool),
                     (global, bool),
-                    (group, &str),
+                    // group : one_or_many
                     ref (groups, Vec<&str>),
                     (help, &str),
                     (help_heading, &str),
@@ -148,13 +239,12 @@ impl<'a> Visitor<'a> for ArgVisitor<'a> {
                     (max_values, usize),
                     (min_values, usize),
                     (multiple_occurrences, bool),
-                    (multiple_values, bool),
-                    (id, &str),
+                    // multiple_values : specialized
                     (next_line_help, bool),
                     (number_of_values, usize),
-                    (overrides_with, &str),
+                    // overrides_with : one_or_many
                     ref (overrides_with_all, Vec<&str>),
-                    (possible_value, &str),
+                    // possible_value : one_or_many
                     (possible_values, Vec<&str>),
                     (raw, bool),
                     (require_value_delimiter, bool),
@@ -166,25 +256,25 @@ impl<'a> Visitor<'a> for ArgVisitor<'a> {
                     (required_unless_present, &str),
                     ref (required_unless_present_any, Vec<&str>),
                     ref (required_unless_present_all, Vec<&str>),
-                    (requires, &str),
+                    // requires : one_or_many
                     ref (requires_all, Vec<&str>),
                     // requires_if: tuple2
                     ref (requires_ifs, Vec<(&str, &str)>),
                     (short, char),
-                    (short_alias, char),
+                    // short_alias : one_or_many
                     ref (short_aliases, Vec<char>),
-                    (takes_value, bool),
+                    // takes_value : specialized
                     (use_value_delimiter, bool),
                     // validator_regex : todo
+                    // value_delimiter : specialized
                     // value_hint : specialized
-                    (value_delimiter, char),
-                    (value_name, &str),
+                    // value_name : one_or_many
                     ref (value_names, Vec<&str>),
                     // value_parser : specialized
                     (value_terminator, &str),
-                    (visible_alias, &str),
+                    // visible_alias : one_or_many
                     ref (visible_aliases, Vec<&str>),
-                    (visible_short_alias, char),
+                    // visible_short_alias : one_or_many
                     ref (visible_short_aliases, Vec<char>),
                 },
                 tuple2: {
@@ -194,7 +284,20 @@ impl<'a> Visitor<'a> for ArgVisitor<'a> {
                 tuple3: {
                     (default_value_if, (&str, Option<&str>, Option<&str>)),
                 },
-                deprecated:
+                one_or_many: {
+                    (possible_value, &str) => possible_values,
+                    ref (alias, &str) => aliases,
+                    ref (conflicts_with, &str) => conflicts_with_all,
+                    ref (default_missing_value, &str) => default_missing_values,
+                    ref (group, &str) => groups,
+                    ref (overrides_with, &str) => overrides_with_all,
+                    ref (requires, &str) => requires_all,
+                    ref (short_alias, char) => short_aliases,
+                    ref (value_name, &str) => value_names,
+                    ref (visible_alias, &str) => visible_aliases,
+                    ref (visible_short_alias, char) => visible_short_aliases,
+                },
+                deprecated(ctx):
                 [
                     "case_insensitive",
                     "empty_values",
@@ -222,32 +325,88 @@ impl<'a> Visitor<'a> for ArgVisitor<'a> {
                 // not_supported: {
                 // },
                 specialize:[
-                    "arg_action" => {
-                        arg.action(map.next_value::<ArgAction>()?.into())
+                    "action" | "arg_action" => {
+                        let a = map.next_value::<ArgAction>()?;
+                        action.action = Some(a);
+                        ctx.record("action", || vec![format!("clap::ArgAction::{:?}", a)]);
+                        arg.action(a.into())
+                    }
+                    "auto_long" => {
+                        frame.auto.auto_long = Some(map.next_value()?);
+                        arg
                     }
+                    "auto_value_name" => {
+                        frame.auto.auto_value_name = Some(map.next_value()?);
+                        arg
+                    }
+                    "auto_env" => {
+                        #[cfg(feature = "env")] {
+                            frame.auto.auto_env = Some(map.next_value()?);
+                            arg
+                        }
+                        #[cfg(not(feature = "env"))] { return Err(Error::custom("env feature disabled"))}}
                     "env" => {
-                        #[cfg(feature = "env")] { parse_value_inner!(arg, map, Arg, &str, env) }
+                        #[cfg(feature = "env")] {
+                            let v: &str = parse_value_inner!(map, &str, ctx);
+                            ctx.record("env", || vec![v.to_rust()]);
+                            arg.env(v)
+                        }
                         #[cfg(not(feature = "env"))] { return Err(Error::custom("env feature disabled"))}}
                     "hide_env" => {
-                        #[cfg(feature = "env")] { parse_value_inner!(arg, map, Arg, bool, hide_env) }
+                        #[cfg(feature = "env")] {
+                            let v: bool = parse_value_inner!(map, bool, ctx);
+                            ctx.record("hide_env", || vec![v.to_rust()]);
+                            arg.hide_env(v)
+                        }
                         #[cfg(not(feature = "env"))] { return Err(Error::custom("env feature disabled"))}}
                     "hide_env_values" => {
-                        #[cfg(feature = "env")] { parse_value_inner!(arg, map, Arg, bool, hide_env_values) }
+                        #[cfg(feature = "env")] {
+                            let v: bool = parse_value_inner!(map, bool, ctx);
+                            ctx.record("hide_env_values", || vec![v.to_rust()]);
+                            arg.hide_env_values(v)
+                        }
                         #[cfg(not(feature = "env"))] { return Err(Error::custom("env feature disabled"))}}
+                    "value_delimiter" => {
+                        let v: char = map.next_value()?;
+                        ctx.record("value_delimiter", || vec![v.to_rust()]);
+                        arg.value_delimiter(v)
+                    }
                     "value_hint" => {
-                        arg.value_hint(map.next_value::<ValueHint>()?.into())
+                        let v: clap::ValueHint = map.next_value::<ValueHint>()?.into();
+                        ctx.record("value_hint", || vec![format!("clap::ValueHint::{:?}", v)]);
+                        arg.value_hint(v)
+                    }
+                    "multiple_values" => {
+                        let v = map.next_value()?;
+                        action.multiple_values = Some(v);
+                        ctx.record("multiple_values", || vec![v.to_rust()]);
+                        arg.multiple_values(v)
+                    }
+                    "takes_value" => {
+                        let v = map.next_value()?;
+                        action.takes_value = Some(v);
+                        ctx.record("takes_value", || vec![v.to_rust()]);
+                        arg.takes_value(v)
                     }
                     "value_parser" => {
-                        arg.value_parser(map.next_value::<ValueParser>()?)
+                        action.value_parser = true;
+                        let v = map.next_value::<ValueParser>()?;
+                        ctx.record("value_parser", || vec![v.to_rust()]);
+                        arg.value_parser(v)
                     }
-                ]
+                ],
+                unknown(ctx => frame.metadata)
             );
         }
+        ctx.pop_path();
+        let arg = action.apply(arg, ctx)?;
+        ctx.end_recording();
+        ctx.add_arg(arg.get_id(), frame)?;
         Ok(ArgWrap { arg })
     }
 }
 
-impl<'de> DeserializeSeed<'de> for ArgVisitor<'de> {
+impl<'de, 'c> DeserializeSeed<'de> for ArgVisitor<'de, 'c> {
     type Value = ArgWrap<'de>;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
@@ -265,12 +424,15 @@ impl<'de> DeserializeSeed<'de> for ArgWrap<'de> {
     where
         D: serde::Deserializer<'de>,
     {
-        deserializer.deserialize_map(ArgVisitor(self.arg))
+        deserializer.deserialize_map(ArgVisitor(self.arg, &mut Context::new()))
     }
 }
 
-pub(crate) struct Args<'a, const USE_ARRAY: bool>(pub(crate) Command<'a>);
-impl<'de, const USE_ARRAY: bool> DeserializeSeed<'de> for Args<'de, USE_ARRAY> {
+pub(crate) struct Args<'a, 'c, const USE_ARRAY: bool>(
+    pub(crate) Command<'a>,
+    pub(crate) &'c mut Context<'a>,
+);
+impl<'de, 'c, const USE_ARRAY: bool> DeserializeSeed<'de> for Args<'de, 'c, USE_ARRAY> {
     type Value = Command<'de>;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
@@ -285,7 +447,7 @@ impl<'de, const USE_ARRAY: bool> DeserializeSeed<'de> for Args<'de, USE_ARRAY> {
     }
 }
 
-impl<'de, const USE_ARRAY: bool> Visitor<'de> for Args<'de, USE_ARRAY> {
+impl<'de, 'c, const USE_ARRAY: bool> Visitor<'de> for Args<'de, 'c, USE_ARRAY> {
     type Value = Command<'de>;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -297,10 +459,10 @@ impl<'de, const USE_ARRAY: bool> Visitor<'de> for Args<'de, USE_ARRAY> {
     where
         A: serde::de::SeqAccess<'de>,
     {
-        let mut argkvo = ArgKVO(Some(self.0));
+        let mut argkvo = ArgKVO(self.0, self.1);
 
         while (seq.next_element_seed(&mut argkvo)?).is_some() {}
-        Ok(unsafe { argkvo.0.unwrap_unchecked() })
+        Ok(argkvo.0)
     }
 
     #[cfg(not(feature = "override-arg"))]
@@ -308,9 +470,8 @@ impl<'de, const USE_ARRAY: bool> Visitor<'de> for Args<'de, USE_ARRAY> {
     where
         A: serde::de::SeqAccess<'de>,
     {
-        let x = ArgKV(PhantomData);
         let mut com = self.0;
-        while let Some(a) = seq.next_element_seed(x)? {
+        while let Some(a) = seq.next_element_seed(ArgKV(&mut *self.1))? {
             com = com.arg(a);
         }
         Ok(com)
@@ -323,13 +484,18 @@ impl<'de, const USE_ARRAY: bool> Visitor<'de> for Args<'de, USE_ARRAY> {
         let mut app = self.0;
         while let Some(name) = map.next_key::<&str>()? {
             #[cfg(feature = "override-arg")]
-            {
+            if usage::is_usage(name) {
+                app = app.arg(map.next_value_seed(ArgVisitor::new_key(name, &mut *self.1)?)?);
+            } else {
                 let mut error = None;
-                app = app.mut_arg(name, |a| match map.next_value_seed(ArgVisitor(a)) {
-                    Ok(a) => a.into(),
-                    Err(e) => {
-                        error = Some(e);
-                        Arg::new(name)
+                let ctx = &mut *self.1;
+                app = app.mut_arg(name, |a| {
+                    match map.next_value_seed(ArgVisitor::new_mut(a, ctx)) {
+                        Ok(a) => a.into(),
+                        Err(e) => {
+                            error = Some(e);
+                            Arg::new(name)
+                        }
                     }
                 });
                 if let Some(error) = error {
@@ -338,7 +504,7 @@ impl<'de, const USE_ARRAY: bool> Visitor<'de> for Args<'de, USE_ARRAY> {
             }
             #[cfg(not(feature = "override-arg"))]
             {
-                app = app.arg(map.next_value_seed(ArgVisitor::new_str(name))?);
+                app = app.arg(map.next_value_seed(ArgVisitor::new_key(name, &mut *self.1)?)?);
             }
         }
         Ok(app)
@@ -0,0 +1,135 @@
+use crate::{
+    codegen::{Call, Node, ToRust},
+    de::Context,
+};
+use clap::Arg;
+
+/// Whether the key of an arg is a usage string rather than an id.
+pub(crate) fn is_usage(key: &str) -> bool {
+    key.starts_with(['-', '<', '[']) || key.contains(char::is_whitespace)
+}
+
+/// Parse a clap 2 style usage string like `-c, --config <FILE> 'Sets a config file'`,
+/// recording the arg into `ctx`.
+pub(crate) fn parse_usage<'a>(usage: &'a str, ctx: &mut Context) -> Result<Arg<'a>, String> {
+    let (spec, help) = match usage.find('\'') {
+        Some(start) => {
+            let end = usage
+                .rfind('\'')
+                .filter(|end| *end > start)
+                .ok_or_else(|| format!("unclosed help in usage: `{}`", usage))?;
+            if !usage[end + 1..].trim().is_empty() {
+                return Err(format!("unexpected text after help in usage: `{}`", usage));
+            }
+            (&usage[..start], Some(&usage[start + 1..end]))
+        }
+        None => (usage, None),
+    };
+
+    let mut id = None;
+    let mut short = None;
+    let mut long = None;
+    let mut value_names = Vec::new();
+    let mut required = None;
+    let mut multiple = false;
+
+    for (i, token) in spec
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|t| !t.is_empty())
+        .enumerate()
+    {
+        let token = match token.strip_suffix("...") {
+            Some(token) => {
+                multiple = true;
+                token
+            }
+            None => token,
+        };
+        if token.is_empty() {
+            continue;
+        }
+        let mut value = |v| -> Result<(), String> {
+            let (name, req) = value_name(v)?;
+            value_names.push(name);
+            required.get_or_insert(req);
+            Ok(())
+        };
+        if let Some(l) = token.strip_prefix("--") {
+            let (l, v) = match l.split_once('=') {
+                Some((l, v)) => (l, Some(v)),
+                None => (l, None),
+            };
+            if l.is_empty() || long.replace(l).is_some() {
+                return Err(format!("invalid long in usage: `{}`", token));
+            }
+            if let Some(v) = v {
+                value(v)?;
+            }
+        } else if let Some(s) = token.strip_prefix('-') {
+            if s.chars().count() != 1 || short.replace(s).is_some() {
+                return Err(format!("invalid short in usage: `{}`", token));
+            }
+        } else if token.starts_with(['<', '[']) {
+            value(token)?;
+        } else if let Some(name) = token.strip_suffix(':').filter(|_| i == 0) {
+            id = Some(name);
+        } else {
+            return Err(format!("unexpected `{}` in usage", token));
+        }
+    }
+
+    let positional = short.is_none() && long.is_none();
+    let id = match id.or(long).or(short).or(value_names.first().copied()) {
+        Some(id) if !(positional && value_names.len() > 1) => id,
+        _ => return Err(format!("invalid usage: `{}`", usage)),
+    };
+
+    ctx.begin_recording(|| Node::new("Arg", id), |n| Call::Add("arg", n));
+    let mut arg = Arg::new(id);
+    if let Some(short) = short.and_then(|s| s.chars().next()) {
+        ctx.record("short", || vec![short.to_rust()]);
+        arg = arg.short(short);
+    }
+    if let Some(long) = long {
+        ctx.record("long", || vec![long.to_rust()]);
+        arg = arg.long(long);
+    }
+    if let Some(help) = help {
+        ctx.record("help", || vec![help.to_rust()]);
+        arg = arg.help(help);
+    }
+    if !value_names.is_empty() {
+        let required = required.unwrap_or_default();
+        ctx.record("value_names", || {
+            vec![format!("&{}", value_names.to_rust())]
+        });
+        ctx.record("required", || vec![required.to_rust()]);
+        arg = arg.value_names(&value_names).required(required);
+        if !positional {
+            ctx.record("takes_value", || vec![true.to_rust()]);
+            arg = arg.takes_value(true);
+        }
+    }
+    if multiple {
+        let method = if positional {
+            arg = arg.multiple_values(true);
+            "multiple_values"
+        } else {
+            arg = arg.multiple_occurrences(true);
+            "multiple_occurrences"
+        };
+        ctx.record(method, || vec![true.to_rust()]);
+    }
+    Ok(arg)
+}
+
+/// `<NAME>` to a required value name, and `[NAME]` to an optional one.
+fn value_name(v: &str) -> Result<(&str, bool), String> {
+    if let Some(name) = v.strip_prefix('<').and_then(|v| v.strip_suffix('>')) {
+        Ok((name, true))
+    } else if let Some(name) = v.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        Ok((name, false))
+    } else {
+        Err(format!("invalid value name in usage: `{}`", v))
+    }
+}
@@ -1,8 +1,5 @@
-use clap::builder::ValueParser as VP;
-use serde::Deserialize;
-
 macro_rules! enum_de_value {
-    ($basety : ident, $newty :ident,
+    ($clap : ident, $basety : ident, $newty :ident,
         $(#[$derive_meta:meta])*
         {
             $( $(
@@ -32,13 +29,19 @@ macro_rules! enum_de_value {
                     #[serde(default = "get_true")]
                     max_inclusive: bool
                 } => {
-                    match (min, max, max_inclusive) {
-                        (Some(s), Some(e), false) => clap::value_parser!($pty).range((s $(as $ty_as)*) ..(e $(as $ty_as)*)).into(),
-                        (Some(s), Some(e), true) => clap::value_parser!($pty).range((s $(as $ty_as)*) ..=(e $(as $ty_as)*)).into(),
-                        (Some(s), None, _) => clap::value_parser!($pty).range((s $(as $ty_as)*)..).into(),
-                        (None, Some(e), false) => clap::value_parser!($pty).range(..(e $(as $ty_as)*)).into(),
-                        (None, Some(e), true) => clap::value_parser!($pty).range(..=(e $(as $ty_as)*)).into(),
-                        (None, None, _) => clap::value_parser!($pty).into(),
+                    // The exclusive max is made inclusive, as clap panics if it is the minimum of the type.
+                    let max = max.map(|e| if max_inclusive { Some(e) } else { e.checked_sub(1) });
+                    match (min, max) {
+                        (None, None) => $clap::value_parser!($pty).into(),
+                        (Some(s), None) => $clap::value_parser!($pty).range((s $(as $ty_as)*)..).into(),
+                        (s, Some(Some(e))) => $clap::value_parser!($pty)
+                            .range((s.unwrap_or(<$pty>::MIN) $(as $ty_as)*)..=(e $(as $ty_as)*)).into(),
+                        // Nothing is less than the minimum.
+                        (_, Some(None)) => {
+                            #[allow(clippy::reversed_empty_ranges)]
+                            let empty = (<$pty>::MAX $(as $ty_as)*)..=(<$pty>::MIN $(as $ty_as)*);
+                            $clap::value_parser!($pty).range(empty).into()
+                        }
                     }
                 },)*
             }
@@ -46,116 +49,211 @@ macro_rules! enum_de_value {
     };
 }
 
-const fn get_true() -> bool {
-    true
-}
+pub(crate) use enum_de_value;
 
-enum_de_value!(VP, ValueParser1,
-    #[derive(Deserialize, Clone, Copy)]
-    #[serde(tag = "type")]
-    #[cfg_attr(feature = "kebab-case-key" ,serde(rename_all = "kebab-case"))]
-    #[cfg_attr(feature = "snake-case-key" ,serde(rename_all = "snake_case"))]
-    {
-        Bool => {
-            VP::bool()
-        },
-        String => {
-            VP::string()
-        },
-        OsString => {
-            VP::os_string()
-        },
-        PathBuf => {
-            VP::path_buf()
-        },
-        Boolish => {
-            clap::builder::BoolishValueParser::new().into()
-        },
-        Falsey => {
-            clap::builder::FalseyValueParser::new().into()
-        },
-        NonEmptyString => {
-            clap::builder::NonEmptyStringValueParser::new().into()
-        },
-    }
-    {
-        (i64, I64),
-        (i32, I32, i64),
-        (i16, I16, i64),
-        (i8 , I8, i64),
-        (u64, U64),
-        (u32, U32, i64),
-        (u16, U16, i64),
-        (u8 , U8, i64)
-    }
-);
+/// Define `ValueParser` deserializing the value parsers of the `clap` crate named `$clap`.
+macro_rules! value_parsers {
+    ($clap : ident) => {
+        use serde::Deserialize;
+        use $clap::builder::ValueParser as VP;
 
-enum_de!(VP, ValueParser2,
-    #[derive(Deserialize, Clone, Copy)]
-    #[cfg_attr(feature = "kebab-case-key" ,serde(rename_all = "kebab-case"))]
-    #[cfg_attr(feature = "snake-case-key" ,serde(rename_all = "snake_case"))]
-    {}
-    {
-        Bool => {
-            VP::bool()
-        },
-        String => {
-            VP::string()
-        },
-        OsString => {
-            VP::os_string()
-        },
-        PathBuf => {
-            VP::path_buf()
-        },
-        Boolish => {
-            clap::builder::BoolishValueParser::new().into()
-        },
-        Falsey => {
-            clap::builder::FalseyValueParser::new().into()
-        },
-        NonEmptyString => {
-            clap::builder::NonEmptyStringValueParser::new().into()
-        },
-        I64 => {
-            clap::value_parser!(i64).into()
-        },
-        I32 => {
-            clap::value_parser!(i32).into()
-        },
-        I16 => {
-            clap::value_parser!(i16).into()
-        },
-        I8 => {
-            clap::value_parser!(i8).into()
-        },
-        U64 => {
-            clap::value_parser!(u64).into()
-        },
-        U32 => {
-            clap::value_parser!(u32).into()
-        },
-        U16 => {
-            clap::value_parser!(u16).into()
-        },
-        U8 => {
-            clap::value_parser!(u8).into()
-        },
-    }
-);
+        const fn get_true() -> bool {
+            true
+        }
+
+        $crate::de::enum_de_value!($clap, VP, ValueParser1,
+            #[derive(Deserialize, Clone, Copy)]
+            #[serde(tag = "type")]
+            #[cfg_attr(feature = "kebab-case-key" ,serde(rename_all = "kebab-case"))]
+            #[cfg_attr(feature = "snake-case-key" ,serde(rename_all = "snake_case"))]
+            {
+                Bool => {
+                    VP::bool()
+                },
+                String => {
+                    VP::string()
+                },
+                OsString => {
+                    VP::os_string()
+                },
+                PathBuf => {
+                    VP::path_buf()
+                },
+                Boolish => {
+                    $clap::builder::BoolishValueParser::new().into()
+                },
+                Falsey => {
+                    $clap::builder::FalseyValueParser::new().into()
+                },
+                NonEmptyString => {
+                    $clap::builder::NonEmptyStringValueParser::new().into()
+                },
+            }
+            {
+                (i64, I64),
+                (i32, I32, i64),
+                (i16, I16, i64),
+                (i8 , I8, i64),
+                (u64, U64),
+                (u32, U32, i64),
+                (u16, U16, i64),
+                (u8 , U8, i64)
+            }
+        );
 
-#[derive(Deserialize)]
-#[serde(untagged)]
-pub(crate) enum ValueParser {
-    Value(ValueParser2),
-    Tagged(ValueParser1),
+        enum_de!(VP, ValueParser2,
+            #[derive(Deserialize, Clone, Copy)]
+            #[cfg_attr(feature = "kebab-case-key" ,serde(rename_all = "kebab-case"))]
+            #[cfg_attr(feature = "snake-case-key" ,serde(rename_all = "snake_case"))]
+            {}
+            {
+                Bool => {
+                    VP::bool()
+                },
+                String => {
+                    VP::string()
+                },
+                OsString => {
+                    VP::os_string()
+                },
+                PathBuf => {
+                    VP::path_buf()
+                },
+                Boolish => {
+                    $clap::builder::BoolishValueParser::new().into()
+                },
+                Falsey => {
+                    $clap::builder::FalseyValueParser::new().into()
+                },
+                NonEmptyString => {
+                    $clap::builder::NonEmptyStringValueParser::new().into()
+                },
+                I64 => {
+                    $clap::value_parser!(i64).into()
+                },
+                I32 => {
+                    $clap::value_parser!(i32).into()
+                },
+                I16 => {
+                    $clap::value_parser!(i16).into()
+                },
+                I8 => {
+                    $clap::value_parser!(i8).into()
+                },
+                U64 => {
+                    $clap::value_parser!(u64).into()
+                },
+                U32 => {
+                    $clap::value_parser!(u32).into()
+                },
+                U16 => {
+                    $clap::value_parser!(u16).into()
+                },
+                U8 => {
+                    $clap::value_parser!(u8).into()
+                },
+            }
+        );
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        pub(crate) enum ValueParser {
+            Value(ValueParser2),
+            Tagged(ValueParser1),
+        }
+
+        impl From<ValueParser> for VP {
+            fn from(v: ValueParser) -> Self {
+                match v {
+                    ValueParser::Value(v) => v.into(),
+                    ValueParser::Tagged(t) => t.into(),
+                }
+            }
+        }
+    };
 }
 
-impl From<ValueParser> for VP {
-    fn from(v: ValueParser) -> Self {
-        match v {
-            ValueParser::Value(v) => v.into(),
-            ValueParser::Tagged(t) => t.into(),
+#[cfg(feature = "clap4")]
+pub(crate) use value_parsers;
+
+value_parsers!(clap);
+
+impl crate::codegen::ToRust for ValueParser {
+    fn to_rust(&self) -> String {
+        use {ValueParser1 as V1, ValueParser2 as V2};
+        let ranged = |ty: &str, min: Option<i128>, max: Option<i128>, max_inclusive: bool| {
+            let (type_min, type_max) = match ty {
+                "i64" => (i64::MIN as i128, i64::MAX as i128),
+                "i32" => (i32::MIN as i128, i32::MAX as i128),
+                "i16" => (i16::MIN as i128, i16::MAX as i128),
+                "i8" => (i8::MIN as i128, i8::MAX as i128),
+                "u64" => (0, u64::MAX as i128),
+                "u32" => (0, u32::MAX as i128),
+                "u16" => (0, u16::MAX as i128),
+                _ => (0, u8::MAX as i128),
+            };
+            // Same as the conversion of `enum_de_value`.
+            let max = max.map(|e| {
+                if max_inclusive {
+                    Some(e)
+                } else {
+                    Some(e - 1).filter(|e| *e >= type_min)
+                }
+            });
+            let range = match (min, max) {
+                (None, None) => return format!("clap::value_parser!({})", ty),
+                (Some(s), None) => format!("{}..", s),
+                (s, Some(Some(e))) => format!("{}..={}", s.unwrap_or(type_min), e),
+                (_, Some(None)) => format!("{}..={}", type_max, type_min),
+            };
+            format!("clap::value_parser!({}).range({})", ty, range)
+        };
+        let simple = |ty| format!("clap::value_parser!({})", ty);
+        let tagged = match self {
+            Self::Value(v) => match v {
+                V2::I64 => return simple("i64"),
+                V2::I32 => return simple("i32"),
+                V2::I16 => return simple("i16"),
+                V2::I8 => return simple("i8"),
+                V2::U64 => return simple("u64"),
+                V2::U32 => return simple("u32"),
+                V2::U16 => return simple("u16"),
+                V2::U8 => return simple("u8"),
+                V2::Bool => V1::Bool,
+                V2::String => V1::String,
+                V2::OsString => V1::OsString,
+                V2::PathBuf => V1::PathBuf,
+                V2::Boolish => V1::Boolish,
+                V2::Falsey => V1::Falsey,
+                V2::NonEmptyString => V1::NonEmptyString,
+            },
+            Self::Tagged(t) => *t,
+        };
+        macro_rules! ranged {
+            ($($var: ident => $ty: literal,)*) => {
+                match tagged {
+                    $(V1::$var { min, max, max_inclusive } => {
+                        ranged($ty, min.map(i128::from), max.map(i128::from), max_inclusive)
+                    })*
+                    V1::Bool => "clap::builder::ValueParser::bool()".to_owned(),
+                    V1::String => "clap::builder::ValueParser::string()".to_owned(),
+                    V1::OsString => "clap::builder::ValueParser::os_string()".to_owned(),
+                    V1::PathBuf => "clap::builder::ValueParser::path_buf()".to_owned(),
+                    V1::Boolish => "clap::builder::BoolishValueParser::new()".to_owned(),
+                    V1::Falsey => "clap::builder::FalseyValueParser::new()".to_owned(),
+                    V1::NonEmptyString => "clap::builder::NonEmptyStringValueParser::new()".to_owned(),
+                }
+            };
+        }
+        ranged! {
+            I64 => "i64",
+            I32 => "i32",
+            I16 => "i16",
+            I8 => "i8",
+            U64 => "u64",
+            U32 => "u32",
+            U16 => "u16",
+            U8 => "u8",
         }
     }
 }
@@ -0,0 +1,334 @@
+use super::context::Frame;
+use crate::{
+    codegen::{Call, Node, ToRust},
+    StringStore,
+};
+use clap::{Arg, ArgAction, Command};
+use serde::{
+    de::{Error, IntoDeserializer, Visitor},
+    Deserialize,
+};
+use std::collections::HashMap;
+
+/// Keys of a command to fill in keys of its args from the arg ids.
+#[derive(Default, Clone, Copy)]
+pub(crate) struct CommandAuto<'a> {
+    #[cfg(feature = "env")]
+    pub(crate) env_prefix: Option<&'a str>,
+    #[cfg(feature = "env")]
+    pub(crate) auto_env: Option<bool>,
+    pub(crate) auto_long: Option<AutoLong>,
+    pub(crate) auto_value_name: Option<bool>,
+    #[cfg(not(feature = "env"))]
+    _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Keys of an arg to override [`CommandAuto`].
+#[derive(Default, Clone, Copy)]
+pub(crate) struct ArgAuto {
+    #[cfg(feature = "env")]
+    pub(crate) auto_env: Option<bool>,
+    pub(crate) auto_long: Option<AutoLong>,
+    pub(crate) auto_value_name: Option<bool>,
+}
+
+/// How to make `long` from the arg id.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AutoLong {
+    /// Don't fill in `long`.
+    Off,
+    /// `foo_bar` to `foo-bar`.
+    Kebab,
+    /// `foo-bar` to `foo_bar`.
+    Snake,
+    /// Same as the arg id.
+    Id,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "kebab-case-key", serde(rename_all = "kebab-case"))]
+#[cfg_attr(feature = "snake-case-key", serde(rename_all = "snake_case"))]
+enum AutoLongName {
+    Kebab,
+    Snake,
+    Id,
+}
+
+impl<'de> Deserialize<'de> for AutoLong {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(AutoLongVisitor)
+    }
+}
+
+struct AutoLongVisitor;
+
+impl<'de> Visitor<'de> for AutoLongVisitor {
+    type Value = AutoLong;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a boolean or the case of long")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(if v { AutoLong::Kebab } else { AutoLong::Off })
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(match AutoLongName::deserialize(v.into_deserializer())? {
+            AutoLongName::Kebab => AutoLong::Kebab,
+            AutoLongName::Snake => AutoLong::Snake,
+            AutoLongName::Id => AutoLong::Id,
+        })
+    }
+}
+
+/// [`CommandAuto`] inherited from the parent command.
+struct Inherited {
+    #[cfg(feature = "env")]
+    env_prefix: String,
+    #[cfg(feature = "env")]
+    auto_env: bool,
+    auto_long: AutoLong,
+    auto_value_name: bool,
+}
+
+impl Inherited {
+    /// Whether any key is filled in.
+    fn is_set(&self) -> bool {
+        #[cfg(feature = "env")]
+        if self.auto_env {
+            return true;
+        }
+        self.auto_long != AutoLong::Off || self.auto_value_name
+    }
+}
+
+impl CommandAuto<'_> {
+    /// Whether any key is given to the command.
+    fn is_set(&self) -> bool {
+        #[cfg(feature = "env")]
+        if self.auto_env.is_some() {
+            return true;
+        }
+        self.auto_long.is_some() || self.auto_value_name.is_some()
+    }
+}
+
+impl ArgAuto {
+    /// Whether any key is given to the arg.
+    fn is_set(&self) -> bool {
+        #[cfg(feature = "env")]
+        if self.auto_env.is_some() {
+            return true;
+        }
+        self.auto_long.is_some() || self.auto_value_name.is_some()
+    }
+}
+
+/// Whether any key is given to the command of `frame`, its args or its subcommands.
+fn has_auto(frame: &Frame) -> bool {
+    frame.auto.is_set()
+        || frame.args.iter().any(|(_, arg)| arg.auto.is_set())
+        || frame.subcommands.iter().any(|(_, sub)| has_auto(sub))
+}
+
+/// `foo-bar` or `fooBar` to `FOO_BAR`.
+fn upper_snake_case(id: &str) -> String {
+    let mut s = String::with_capacity(id.len());
+    let mut prev_lower = false;
+    for c in id.chars() {
+        if c.is_alphanumeric() {
+            if prev_lower && c.is_uppercase() {
+                s.push('_');
+            }
+            prev_lower = c.is_lowercase() || c.is_numeric();
+            s.extend(c.to_uppercase());
+        } else {
+            prev_lower = false;
+            s.push('_');
+        }
+    }
+    s
+}
+
+/// Use `id` itself if it is same as `s`, or keep `s` in `strings`.
+fn reuse_or_add<'a>(
+    id: &'a str,
+    s: String,
+    strings: Option<&'a StringStore>,
+) -> Result<&'a str, String> {
+    if id == s {
+        Ok(id)
+    } else {
+        crate::strings::keep(strings, s)
+    }
+}
+
+fn long_of<'a>(
+    id: &'a str,
+    case: AutoLong,
+    strings: Option<&'a StringStore>,
+) -> Result<Option<&'a str>, String> {
+    match case {
+        AutoLong::Off => Ok(None),
+        AutoLong::Kebab => reuse_or_add(id, id.replace('_', "-"), strings).map(Some),
+        AutoLong::Snake => reuse_or_add(id, id.replace('-', "_"), strings).map(Some),
+        AutoLong::Id => Ok(Some(id)),
+    }
+}
+
+/// Fill in the keys of the args in `app` and its subcommands, recording the calls into `calls` if any.
+///
+/// The strings made are kept in `strings`, failing without a store.
+pub(crate) fn apply<'a>(
+    frame: &Frame<'a>,
+    app: Command<'a>,
+    calls: Option<&mut Vec<Call>>,
+    strings: Option<&'a StringStore>,
+) -> Result<Command<'a>, String> {
+    let mut error = None;
+    let app = apply_inner(frame, app, None, calls, strings, &mut error);
+    match error {
+        Some(e) => Err(e),
+        None => Ok(app),
+    }
+}
+
+fn apply_inner<'a>(
+    frame: &Frame<'a>,
+    app: Command<'a>,
+    parent: Option<&Inherited>,
+    mut calls: Option<&mut Vec<Call>>,
+    strings: Option<&'a StringStore>,
+    error: &mut Option<String>,
+) -> Command<'a> {
+    let auto = frame.auto;
+    let inherited = Inherited {
+        #[cfg(feature = "env")]
+        env_prefix: match (auto.env_prefix, parent) {
+            (Some(prefix), _) => prefix.to_owned(),
+            (None, Some(parent)) => {
+                format!("{}{}_", parent.env_prefix, upper_snake_case(app.get_name()))
+            }
+            (None, None) => String::new(),
+        },
+        #[cfg(feature = "env")]
+        auto_env: auto
+            .auto_env
+            .unwrap_or_else(|| parent.is_some_and(|p| p.auto_env)),
+        auto_long: auto
+            .auto_long
+            .unwrap_or_else(|| parent.map_or(AutoLong::Off, |p| p.auto_long)),
+        auto_value_name: auto
+            .auto_value_name
+            .unwrap_or_else(|| parent.is_some_and(|p| p.auto_value_name)),
+    };
+
+    // Only the loaded args, as mutating the generated args like `help` changes how clap treats them.
+    // `mut_arg` moves the arg to the last, so all of them are mutated (and recorded) in the loaded order
+    // if any is, and none if nothing is filled in.
+    let mut app = app;
+    if inherited.is_set() || frame.args.iter().any(|(_, arg)| arg.auto.is_set()) {
+        // Index of the last arg of each id, which is the one loaded if overridden.
+        let last: HashMap<_, _> = frame
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, (id, _))| (*id, i))
+            .collect();
+        for (i, (id, arg)) in frame.args.iter().enumerate() {
+            if last[id] == i {
+                let mut node = Node::new("Arg", *id);
+                app = app.mut_arg(*id, |mut a| {
+                    if let Err(e) =
+                        apply_arg(&mut a, &arg.auto, &inherited, &mut node.calls, strings)
+                    {
+                        error.get_or_insert(e);
+                    }
+                    a
+                });
+                if let Some(calls) = &mut calls {
+                    calls.push(Call::Mut("mut_arg", node));
+                }
+            }
+        }
+    }
+
+    // `mut_subcommand` adds an empty subcommand if not found, so the subcommands are mutated by id,
+    // and only if anything is filled in.
+    for (id, sub) in &frame.subcommands {
+        if !inherited.is_set() && !has_auto(sub) {
+            continue;
+        }
+        let mut node = Node::new("Command", id.as_str());
+        let sub_calls = calls.is_some().then_some(&mut node.calls);
+        app = app.mut_subcommand(id.as_str(), |s| {
+            apply_inner(sub, s, Some(&inherited), sub_calls, strings, error)
+        });
+        if let Some(calls) = &mut calls {
+            if !node.calls.is_empty() {
+                calls.push(Call::Mut("mut_subcommand", node));
+            }
+        }
+    }
+    app
+}
+
+/// Whether `arg` takes a value or is a flag, so that it is not left positional by `auto_long`.
+///
+/// An arg without `short`, `takes_value` and `action` is positional, as clap makes it.
+fn takes_value_or_flag(arg: &Arg) -> bool {
+    arg.get_short().is_some()
+        || arg.is_takes_value_set()
+        || !matches!(arg.get_action(), ArgAction::StoreValue)
+}
+
+/// Fill in the keys of `arg`, recording the calls into `calls`.
+fn apply_arg<'a>(
+    arg: &mut Arg<'a>,
+    auto: &ArgAuto,
+    inherited: &Inherited,
+    calls: &mut Vec<Call>,
+    strings: Option<&'a StringStore>,
+) -> Result<(), String> {
+    let id = arg.get_id();
+
+    let auto_long = auto.auto_long.unwrap_or(inherited.auto_long);
+    if arg.get_long().is_none()
+        && arg.get_index().is_none()
+        && !arg.is_last_set()
+        && takes_value_or_flag(arg)
+    {
+        if let Some(long) = long_of(id, auto_long, strings)? {
+            calls.push(Call::Method("long", vec![long.to_rust()]));
+            *arg = std::mem::take(arg).long(long);
+        }
+    }
+
+    let auto_value_name = auto.auto_value_name.unwrap_or(inherited.auto_value_name);
+    if auto_value_name
+        && arg.get_value_names().is_none()
+        && (arg.is_takes_value_set() || arg.is_positional())
+    {
+        let name = reuse_or_add(id, upper_snake_case(id), strings)?;
+        calls.push(Call::Method("value_name", vec![name.to_rust()]));
+        *arg = std::mem::take(arg).value_name(name);
+    }
+
+    #[cfg(feature = "env")]
+    if arg.get_env().is_none() && auto.auto_env.unwrap_or(inherited.auto_env) {
+        let env = format!("{}{}", inherited.env_prefix, upper_snake_case(id));
+        calls.push(Call::Method("env", vec![env.to_rust()]));
+        *arg = std::mem::take(arg).env(crate::strings::keep(strings, env)?);
+    }
+    Ok(())
+}
@@ -0,0 +1,35 @@
+/// `ArgRequiredElseHelp` or `arg-required-else-help` to `arg_required_else_help`.
+pub(crate) fn snake_case(name: &str) -> String {
+    let mut s = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                s.push('_');
+            }
+            s.extend(c.to_lowercase());
+        } else if c == '-' {
+            s.push('_');
+        } else {
+            s.push(c);
+        }
+    }
+    s
+}
+
+/// `snake` in the same case as `key`, which is snake_case, kebab-case or PascalCase.
+#[cfg(feature = "migrate")]
+pub(crate) fn same_case_as(key: &str, snake: &str) -> String {
+    if key.contains('-') {
+        snake.replace('_', "-")
+    } else if key.starts_with(char::is_uppercase) {
+        snake
+            .split('_')
+            .flat_map(|w| {
+                let mut cs = w.chars();
+                cs.next().into_iter().flat_map(char::to_uppercase).chain(cs)
+            })
+            .collect()
+    } else {
+        snake.to_owned()
+    }
+}
@@ -0,0 +1,612 @@
+use crate::{
+    codegen::{Call, Node},
+    metadata::CommandMetadata,
+    CommandWrap, Diagnostic, DiagnosticKind, Duplicates, KeyHandler, LoadOptions, MetaValue,
+    Metadata, Severity, StringStore, UnknownKeys,
+};
+use clap::{Arg, ArgGroup, Command};
+use serde::de::{DeserializeSeed, Error, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use std::collections::{HashMap, HashSet};
+
+/// Information of a command collected while loading, which [`Command`] can't hold.
+#[derive(Default)]
+pub(crate) struct Frame<'a> {
+    pub(crate) auto: super::auto::CommandAuto<'a>,
+    /// Args by id, in the order they are loaded.
+    pub(crate) args: Vec<(&'a str, ArgFrame)>,
+    /// Subcommands by id, in the order they are loaded.
+    pub(crate) subcommands: Vec<(String, Frame<'a>)>,
+    /// Name of the command, which differs from its id if given by the `name` key.
+    pub(crate) name: String,
+    pub(crate) metadata: Metadata,
+    /// Ids of the args and names of the subcommands, if duplicates are checked.
+    arg_ids: HashSet<&'a str>,
+    subcommand_names: HashSet<String>,
+}
+
+/// Information of an arg collected while loading, which [`Arg`](clap::Arg) can't hold.
+#[derive(Default)]
+pub(crate) struct ArgFrame {
+    pub(crate) auto: super::auto::ArgAuto,
+    pub(crate) metadata: Metadata,
+}
+
+/// What a map is loaded into, which is passed to [`KeyHandler`]s.
+pub(crate) trait KeyTarget<'a> {
+    fn handle(
+        &mut self,
+        handler: &dyn KeyHandler,
+        key: &str,
+        value: &MetaValue,
+        strings: Option<&'a StringStore>,
+    ) -> Result<bool, String>;
+}
+
+impl<'a> KeyTarget<'a> for Command<'a> {
+    fn handle(
+        &mut self,
+        handler: &dyn KeyHandler,
+        key: &str,
+        value: &MetaValue,
+        strings: Option<&'a StringStore>,
+    ) -> Result<bool, String> {
+        handler.command_key(key, value, self, strings)
+    }
+}
+
+impl<'a> KeyTarget<'a> for Arg<'a> {
+    fn handle(
+        &mut self,
+        handler: &dyn KeyHandler,
+        key: &str,
+        value: &MetaValue,
+        strings: Option<&'a StringStore>,
+    ) -> Result<bool, String> {
+        handler.arg_key(key, value, self, strings)
+    }
+}
+
+impl<'a> KeyTarget<'a> for ArgGroup<'a> {
+    fn handle(
+        &mut self,
+        handler: &dyn KeyHandler,
+        key: &str,
+        value: &MetaValue,
+        strings: Option<&'a StringStore>,
+    ) -> Result<bool, String> {
+        handler.group_key(key, value, self, strings)
+    }
+}
+
+/// State shared by the visitors while loading a command tree.
+pub(crate) struct Context<'a> {
+    /// Frames of the commands from the root to the command being loaded.
+    stack: Vec<Frame<'a>>,
+    /// Path from the root to the map being loaded, like `subcommands.test`.
+    path: Vec<String>,
+    /// Deprecated keys found, if they are collected instead of failing.
+    diagnostics: Option<Vec<Diagnostic>>,
+    options: LoadOptions,
+    /// Subcommands, args and groups loaded so far.
+    nodes: usize,
+    /// Builders from the root to the one being loaded, if the builder methods called are recorded.
+    recording: Option<Vec<Recording>>,
+    /// Strings made while loading, which the loaded command borrows, if the loader has a store.
+    strings: Option<&'a StringStore>,
+}
+
+/// [`Node`] being recorded, which is added to its parent by `call` when finished.
+struct Recording {
+    node: Node,
+    call: fn(Node) -> Call,
+}
+
+impl<'a> Context<'a> {
+    pub(crate) fn new() -> Self {
+        Self {
+            stack: vec![Frame::default()],
+            path: Vec::new(),
+            diagnostics: None,
+            options: LoadOptions::default(),
+            nodes: 0,
+            recording: None,
+            strings: None,
+        }
+    }
+
+    /// Keep the strings made while loading in `strings`, instead of failing with a value which needs one.
+    pub(crate) fn with_strings(self, strings: &'a StringStore) -> Self {
+        Self {
+            strings: Some(strings),
+            ..self
+        }
+    }
+
+    pub(crate) fn strings(&self) -> Option<&'a StringStore> {
+        self.strings
+    }
+
+    /// Context recording the builder methods called, which are taken by [`Context::take_recording`].
+    pub(crate) fn recording() -> Self {
+        Self {
+            recording: Some(vec![Recording {
+                node: Node::new("Command", ""),
+                call: |node| Call::Add("", node),
+            }]),
+            ..Self::new()
+        }
+    }
+
+    /// Record the builder method called on the command, arg or group being loaded.
+    pub(crate) fn record(&mut self, method: &'static str, args: impl FnOnce() -> Vec<String>) {
+        if let Some(Some(r)) = self.recording.as_mut().map(|r| r.last_mut()) {
+            r.node.calls.push(Call::Method(method, args()));
+        }
+    }
+
+    /// Start recording the methods called on `node`, which is added to the parent by `call`.
+    pub(crate) fn begin_recording(&mut self, node: impl FnOnce() -> Node, call: fn(Node) -> Call) {
+        if let Some(recording) = &mut self.recording {
+            recording.push(Recording { node: node(), call });
+        }
+    }
+
+    /// Finish recording the node started by [`Context::begin_recording`].
+    pub(crate) fn end_recording(&mut self) {
+        if let Some(recording) = &mut self.recording {
+            if recording.len() > 1 {
+                let r = recording.pop().expect("checked the length");
+                let call = (r.call)(r.node);
+                if let Some(parent) = recording.last_mut() {
+                    parent.node.calls.push(call);
+                }
+            }
+        }
+    }
+
+    /// Take the recorded root command named `name`, after [`Context::finish`].
+    pub(crate) fn take_recording(&mut self, name: &str) -> Option<Node> {
+        let mut root = self.recording.take()?.into_iter().next()?.node;
+        // The root is created with a temporary name, which is renamed by the `name` key.
+        root.name = name.to_owned();
+        root.calls.retain(|c| !matches!(c, Call::Method("name", _)));
+        Some(root)
+    }
+
+    /// Context for [`LoadOptions::load`], which collects [`Diagnostic`]s.
+    pub(crate) fn with_options(options: LoadOptions) -> Self {
+        Self {
+            diagnostics: Some(Vec::new()),
+            options,
+            ..Self::new()
+        }
+    }
+
+    /// Frame of the command being loaded.
+    pub(crate) fn frame(&mut self) -> &mut Frame<'a> {
+        self.stack.last_mut().expect("root frame is never popped")
+    }
+
+    /// Start loading the subcommand keyed by `key`.
+    pub(crate) fn enter<E: Error>(&mut self, key: &str) -> Result<(), E> {
+        if let Some(max) = self.options.limits.max_depth {
+            if self.stack.len() > max {
+                return Err(E::custom(self.limit_error(format_args!(
+                    "subcommand `{}` is nested deeper than the limit {}",
+                    key, max
+                ))));
+            }
+        }
+        self.node("subcommand", key)?;
+        self.stack.push(Frame::default());
+        self.push_path("subcommands", key);
+        Ok(())
+    }
+
+    /// Start loading the arg `id` of the command being loaded.
+    pub(crate) fn arg_node<E: Error>(&mut self, id: &str) -> Result<(), E> {
+        if let Some(max) = self.options.limits.max_args {
+            if self.frame().args.len() >= max {
+                return Err(E::custom(self.limit_error(format_args!(
+                    "arg `{}` is over the limit of {} args in a command",
+                    id, max
+                ))));
+            }
+        }
+        self.node("arg", id)
+    }
+
+    /// Start loading the subcommand, arg or group named `name`.
+    pub(crate) fn node<E: Error>(&mut self, kind: &str, name: &str) -> Result<(), E> {
+        self.nodes += 1;
+        if let Some(max) = self.options.limits.max_nodes {
+            if self.nodes > max {
+                return Err(E::custom(self.limit_error(format_args!(
+                    "{} `{}` is over the limit of {} subcommands, args and groups",
+                    kind, name, max
+                ))));
+            }
+        }
+        self.check_len(name.len()).map_err(E::custom)
+    }
+
+    /// Check the length of a string against the limit.
+    pub(crate) fn check_len(&self, len: usize) -> Result<(), String> {
+        match self.options.limits.max_string_len {
+            Some(max) if len > max => Err(self.limit_error(format_args!(
+                "string of {} bytes is longer than the limit {}",
+                len, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Start loading a list or map `depth` deep in the value of the unknown `key`.
+    fn meta_nested<E: Error>(&self, key: &str, depth: usize) -> Result<(), E> {
+        match self.options.limits.max_depth {
+            Some(max) if depth > max => Err(E::custom(self.limit_error(format_args!(
+                "value of `{}` is nested deeper than the limit {}",
+                key, max
+            )))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count an item loaded in a list or map of the value of the unknown `key`.
+    fn meta_item<E: Error>(&mut self, key: &str) -> Result<(), E> {
+        self.nodes += 1;
+        match self.options.limits.max_nodes {
+            Some(max) if self.nodes > max => Err(E::custom(self.limit_error(format_args!(
+                "item of `{}` is over the limit of {} nodes",
+                key, max
+            )))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Message of a limit, with the path of the map being loaded.
+    fn limit_error(&self, message: std::fmt::Arguments) -> String {
+        match self.path.is_empty() {
+            true => message.to_string(),
+            false => format!("{}: {}", self.path.join("."), message),
+        }
+    }
+
+    /// Finish loading the subcommand keyed by `key`, which is named `name`.
+    pub(crate) fn leave<E: Error>(&mut self, key: &str, name: &str) -> Result<(), E> {
+        if self.stack.len() > 1 {
+            let mut frame = self.stack.pop().expect("checked the length");
+            frame.name = name.to_owned();
+            self.pop_path();
+            if self.check_duplicates() && !self.frame().subcommand_names.insert(name.to_owned()) {
+                self.duplicate(Some("subcommands"), name)?;
+            }
+            self.frame().subcommands.push((key.to_owned(), frame));
+        }
+        Ok(())
+    }
+
+    /// Add the arg loaded to the command being loaded.
+    pub(crate) fn add_arg<E: Error>(&mut self, id: &'a str, frame: ArgFrame) -> Result<(), E> {
+        if self.check_duplicates() && !self.frame().arg_ids.insert(id) {
+            self.duplicate(Some("args"), id)?;
+        }
+        self.frame().args.push((id, frame));
+        Ok(())
+    }
+
+    /// Check `key` is not in `keys`, the keys loaded before in the same map, and add it.
+    pub(crate) fn check_key<'k, E: Error>(
+        &mut self,
+        key: &'k str,
+        keys: &mut HashSet<&'k str>,
+    ) -> Result<(), E> {
+        self.check_len(key.len()).map_err(E::custom)?;
+        if self.check_duplicates() && !keys.insert(key) {
+            self.duplicate(None, key)?;
+        }
+        Ok(())
+    }
+
+    /// Whether duplicates are reported, so that the keys, args and subcommands loaded are kept to check them.
+    fn check_duplicates(&self) -> bool {
+        self.options.duplicates != Duplicates::Allow
+    }
+
+    /// Handle `name` given more than once in `section`, or in the map being loaded if `None`.
+    fn duplicate<E: Error>(&mut self, section: Option<&str>, name: &str) -> Result<(), E> {
+        match self.options.duplicates {
+            Duplicates::Allow => Ok(()),
+            Duplicates::Warn => {
+                self.report_in(
+                    section,
+                    name,
+                    None,
+                    Severity::Warning,
+                    DiagnosticKind::Duplicate,
+                );
+                Ok(())
+            }
+            Duplicates::Deny => Err(E::custom(format_args!(
+                "duplicate {}: `{}`",
+                match section {
+                    Some("args") => "arg",
+                    Some(_) => "subcommand",
+                    None => "key",
+                },
+                name
+            ))),
+        }
+    }
+
+    /// Start loading the map `name` in `section`, like an arg in `args`.
+    pub(crate) fn push_path(&mut self, section: &str, name: &str) {
+        self.path.push(format!("{}.{}", section, name));
+    }
+
+    /// Finish loading the map started by [`Context::push_path`].
+    pub(crate) fn pop_path(&mut self) {
+        self.path.pop();
+    }
+
+    /// Report the deprecated `key` of the map being loaded.
+    ///
+    /// Returns whether to load it as `replacement` (or skip it if there's none) instead of failing.
+    pub(crate) fn deprecated(&mut self, key: &str, replacement: Option<&'static str>) -> bool {
+        if self.diagnostics.is_none() {
+            return cfg!(feature = "allow-deprecated") && replacement.is_some();
+        }
+        let severity = match replacement {
+            Some(_) => Severity::Warning,
+            None => Severity::Error,
+        };
+        self.report(key, replacement, severity, DiagnosticKind::Deprecated);
+        true
+    }
+
+    /// Handle `key` which is not a key of the map being loaded into `target`,
+    /// by the [`KeyHandler`]s and the [`UnknownKeys`] option.
+    ///
+    /// Returns the name and the value of an `x-` key to be kept as [`Metadata`].
+    pub(crate) fn unknown_key<'de, A, T>(
+        &mut self,
+        key: &str,
+        map: &mut A,
+        expected: &'static [&'static str],
+        target: &mut T,
+    ) -> Result<Option<(String, MetaValue)>, A::Error>
+    where
+        A: MapAccess<'de>,
+        T: KeyTarget<'a>,
+    {
+        let mut value = None;
+        if !self.options.handlers.is_empty() {
+            let v = map.next_value_seed(MetaSeed::new(self, key))?;
+            for handler in &self.options.handlers {
+                if target
+                    .handle(&**handler, key, &v, self.strings)
+                    .map_err(A::Error::custom)?
+                {
+                    return Ok(None);
+                }
+            }
+            value = Some(v);
+        }
+        if let Some(name) = key.strip_prefix("x-").or_else(|| key.strip_prefix("x_")) {
+            let value = match value {
+                Some(v) => v,
+                None => map.next_value_seed(MetaSeed::new(self, key))?,
+            };
+            return Ok(Some((name.to_owned(), value)));
+        }
+        match self.options.unknown_keys {
+            UnknownKeys::Deny => return Err(A::Error::unknown_field(key, expected)),
+            UnknownKeys::Warn => {
+                self.report(key, None, Severity::Warning, DiagnosticKind::UnknownKey)
+            }
+            UnknownKeys::Ignore => {}
+        }
+        if value.is_none() {
+            map.next_value::<IgnoredAny>()?;
+        }
+        Ok(None)
+    }
+
+    fn report(
+        &mut self,
+        key: &str,
+        replacement: Option<&'static str>,
+        severity: Severity,
+        kind: DiagnosticKind,
+    ) {
+        self.report_in(None, key, replacement, severity, kind)
+    }
+
+    fn report_in(
+        &mut self,
+        section: Option<&str>,
+        key: &str,
+        replacement: Option<&'static str>,
+        severity: Severity,
+        kind: DiagnosticKind,
+    ) {
+        let path = self
+            .path
+            .iter()
+            .map(String::as_str)
+            .chain(section)
+            .chain([key])
+            .collect::<Vec<_>>()
+            .join(".");
+        if let Some(diagnostics) = &mut self.diagnostics {
+            diagnostics.push(Diagnostic {
+                path,
+                key: key.to_owned(),
+                replacement,
+                severity,
+                kind,
+            });
+        }
+    }
+
+    /// Take the collected diagnostics.
+    pub(crate) fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        self.diagnostics.take().unwrap_or_default()
+    }
+
+    /// Take the metadata of the command tree loaded, for the clap 4 backend which has no keys to fill in.
+    #[cfg(feature = "clap4")]
+    pub(crate) fn take_metadata(&mut self) -> CommandMetadata {
+        metadata_of(self.frame())
+    }
+
+    /// Apply the collected information to the loaded root command.
+    pub(crate) fn finish(&mut self, app: Command<'a>) -> Result<CommandWrap<'a>, String> {
+        let calls = match &mut self.recording {
+            Some(recording) => recording.last_mut().map(|r| &mut r.node.calls),
+            None => None,
+        };
+        let frame = self.stack.last_mut().expect("root frame is never popped");
+        Ok(CommandWrap {
+            app: super::auto::apply(frame, app, calls, self.strings)?,
+            metadata: metadata_of(frame),
+        })
+    }
+}
+
+fn metadata_of(frame: &mut Frame) -> CommandMetadata {
+    let mut args: Vec<(String, Metadata)> = Vec::new();
+    // Index in `args` of each id.
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for (id, arg) in &mut frame.args {
+        let metadata = std::mem::take(&mut arg.metadata);
+        match index.get(id) {
+            // The arg is overridden.
+            Some(&i) => metadata
+                .iter()
+                .for_each(|(k, v)| args[i].1.insert(k.to_owned(), v.clone())),
+            None => {
+                index.insert(*id, args.len());
+                args.push((id.to_string(), metadata));
+            }
+        }
+    }
+    CommandMetadata {
+        metadata: std::mem::take(&mut frame.metadata),
+        args,
+        subcommands: frame
+            .subcommands
+            .iter_mut()
+            .map(|(_, sub)| (sub.name.clone(), metadata_of(sub)))
+            .collect(),
+    }
+}
+
+/// Seed of the [`MetaValue`] of an unknown key, within the limits of the context.
+struct MetaSeed<'k, 'c, 'a> {
+    key: &'k str,
+    ctx: &'c mut Context<'a>,
+    /// Number of the lists and maps the value is in.
+    depth: usize,
+}
+
+impl<'k, 'c, 'a> MetaSeed<'k, 'c, 'a> {
+    fn new(ctx: &'c mut Context<'a>, key: &'k str) -> Self {
+        Self { key, ctx, depth: 0 }
+    }
+
+    /// Seed of the items of the list or map being loaded.
+    fn nested<E: Error>(&mut self) -> Result<MetaSeed<'k, '_, 'a>, E> {
+        self.ctx.meta_nested(self.key, self.depth + 1)?;
+        Ok(MetaSeed {
+            key: self.key,
+            ctx: &mut *self.ctx,
+            depth: self.depth + 1,
+        })
+    }
+
+    fn string<E: Error>(&self, v: &str) -> Result<String, E> {
+        self.ctx.check_len(v.len()).map_err(E::custom)?;
+        Ok(v.to_owned())
+    }
+}
+
+impl<'de, 'k, 'c, 'a> DeserializeSeed<'de> for MetaSeed<'k, 'c, 'a> {
+    type Value = MetaValue;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'k, 'c, 'a> Visitor<'de> for MetaSeed<'k, 'c, 'a> {
+    type Value = MetaValue;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(MetaValue::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(MetaValue::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(MetaValue::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(MetaValue::Int(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(i64::try_from(v).map_or(MetaValue::Float(v as f64), MetaValue::Int))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(MetaValue::Float(v))
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.string(v).map(MetaValue::String)
+    }
+
+    fn visit_seq<A>(mut self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut list = Vec::new();
+        while let Some(v) = seq.next_element_seed(self.nested()?)? {
+            self.ctx.meta_item(self.key)?;
+            list.push(v);
+        }
+        Ok(MetaValue::List(list))
+    }
+
+    fn visit_map<A>(mut self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::new();
+        while let Some(k) = map.next_key::<String>()? {
+            let k = self.string(&k)?;
+            let v = map.next_value_seed(self.nested()?)?;
+            self.ctx.meta_item(self.key)?;
+            entries.push((k, v));
+        }
+        Ok(MetaValue::Map(entries))
+    }
+}
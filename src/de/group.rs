@@ -1,10 +1,15 @@
-use crate::ArgGroupWrap;
+use crate::{
+    codegen::{Call, Node},
+    de::Context,
+    ArgGroupWrap,
+};
 use clap::{ArgGroup, Command};
 use serde::de::{DeserializeSeed, Error, Visitor};
+use std::collections::HashSet;
 
-struct GroupVisitor<'a>(&'a str);
+struct GroupVisitor<'a, 'c>(&'a str, &'c mut Context<'a>);
 
-impl<'de> Visitor<'de> for GroupVisitor<'de> {
+impl<'de, 'c> Visitor<'de> for GroupVisitor<'de, 'c> {
     type Value = ArgGroupWrap<'de>;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -16,27 +21,40 @@ impl<'de> Visitor<'de> for GroupVisitor<'de> {
         A: serde::de::MapAccess<'de>,
     {
         let mut group = ArgGroup::new(self.0);
+        let ctx = self.1;
+        let mut keys = HashSet::new();
+        ctx.node("group", self.0)?;
+        ctx.push_path("groups", self.0);
+        ctx.begin_recording(|| Node::new("ArgGroup", self.0), |n| Call::Add("group", n));
         while let Some(key) = map.next_key::<&str>()? {
+            ctx.check_key(key, &mut keys)?;
             group = parse_value!(key, group, map, ArgGroup, {
-                (arg, &str),
+                // arg : one_or_many
                 ref (args, Vec<&str>),
-                (conflicts_with, &str),
+                // conflicts_with : one_or_many
                 ref (conflicts_with_all, Vec<&str>),
                 (id, &str),
                 (multiple, bool),
                 (required, bool),
-                (requires, &str),
+                // requires : one_or_many
                 ref (requires_all, Vec<&str>),
-            }, deprecated:{
+            },
+            one_or_many: {
+                ref (arg, &str) => args,
+                ref (conflicts_with, &str) => conflicts_with_all,
+                ref (requires, &str) => requires_all,
+            }, deprecated(ctx):{
                 "name" => "id",
-            });
+            },
+            unknown(ctx));
         }
-
+        ctx.pop_path();
+        ctx.end_recording();
         Ok(ArgGroupWrap { group })
     }
 }
 
-impl<'de> DeserializeSeed<'de> for GroupVisitor<'de> {
+impl<'de, 'c> DeserializeSeed<'de> for GroupVisitor<'de, 'c> {
     type Value = ArgGroupWrap<'de>;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
@@ -47,8 +65,8 @@ impl<'de> DeserializeSeed<'de> for GroupVisitor<'de> {
     }
 }
 
-pub(crate) struct Groups<'a>(pub(crate) Command<'a>);
-impl<'de> DeserializeSeed<'de> for Groups<'de> {
+pub(crate) struct Groups<'a, 'c>(pub(crate) Command<'a>, pub(crate) &'c mut Context<'a>);
+impl<'de, 'c> DeserializeSeed<'de> for Groups<'de, 'c> {
     type Value = Command<'de>;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
@@ -59,7 +77,7 @@ impl<'de> DeserializeSeed<'de> for Groups<'de> {
     }
 }
 
-impl<'de> Visitor<'de> for Groups<'de> {
+impl<'de, 'c> Visitor<'de> for Groups<'de, 'c> {
     type Value = Command<'de>;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -72,7 +90,7 @@ impl<'de> Visitor<'de> for Groups<'de> {
     {
         let mut app = self.0;
         while let Some(name) = map.next_key::<&str>()? {
-            app = app.group(map.next_value_seed(GroupVisitor(name))?);
+            app = app.group(map.next_value_seed(GroupVisitor(name, &mut *self.1))?);
         }
         Ok(app)
     }
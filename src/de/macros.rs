@@ -1,23 +1,46 @@
 macro_rules! parse_value_inner {
-    ( $app : ident, $map : ident, $target_type:ty, $value_type:ty, $register : ident) => {
-        <$target_type>::$register($app, $map.next_value::<$value_type>()?)
+    ( $app : ident, $map : ident, $target_type:ty, $value_type:ty, $register : ident, $ctx : ident) => {
+        <$target_type>::$register($app, parse_value_inner!($map, $value_type, $ctx))
     };
-    ( $app : ident, $map : ident, $target_type:ty, ref $value_type:ty, $register : ident) => {
-        <$target_type>::$register($app, &$map.next_value::<$value_type>()?)
+    ( $app : ident, $target_type:ty, ref $value : ident, $register : ident) => {
+        <$target_type>::$register($app, &$value)
+    };
+    ( $app : ident, $target_type:ty, $value : ident, $register : ident) => {
+        <$target_type>::$register($app, $value)
+    };
+    ( $map : ident, $value_type:ty, $ctx : ident) => {{
+        // The length is checked first, so that a string over the limit is not kept in the store.
+        let raw = $map.next_value::<<$value_type as $crate::de::KeyValue>::Raw>()?;
+        if let Err(e) = $ctx.check_len(<$value_type as $crate::de::KeyValue>::longest(&raw)) {
+            return Err(Error::custom(e));
+        }
+        match <$value_type as $crate::de::KeyValue>::from_raw(raw, $ctx.strings()) {
+            Ok(v) => v,
+            Err(e) => return Err(Error::custom(e)),
+        }
+    }};
+}
+
+/// Rust expression of the value `$v` given to a builder method, by reference if `ref`.
+macro_rules! rust_arg {
+    (ref $v : ident) => {
+        format!("&{}", $crate::codegen::ToRust::to_rust(&$v))
+    };
+    ($v : ident) => {
+        $crate::codegen::ToRust::to_rust(&$v)
     };
 }
 
 macro_rules! parse_value {
     ($key : ident, $app : ident, $map : ident, $target_type:ty,
-        { $(
-            $( ( $register : ident, $value_type:ty), ),+
-            $( ref ( $register_r : ident, $value_type_r:ty) ),* $(,)?
-        )* }
+        { $( $($ref:ident)? ( $register : ident, $value_type:ty) ),* $(,)? }
         $(, tuple2:{$(( $register_t : ident, ( $value_type_t0:ty,  $value_type_t1:ty)),)*})?
         $(, tuple3:{$(( $register_3t : ident, ( $value_type_3t0:ty,  $value_type_3t1:ty,  $value_type_3t2:ty)),)*})?
-        $(, deprecated:$([$($dep:pat ,)*])?$({$($dep_s:pat => $dep_d:expr,)*})?)?
-        $(, not_supported:{$($ns:pat => $ns_r:stmt ,)*})?
-        $(, specialize:[$( $sp_pat : pat => $sp_exp : expr )+ ])? ) => {{
+        $(, one_or_many:{$( $($ref_m:ident)? ( $register_m : ident, $value_type_m:ty) => $plural_m : ident,)*})?
+        $(, deprecated($ctx:ident):$([$($dep:pat ,)*])?$({$($dep_s:pat => $dep_d:expr,)*})?)?
+        $(, not_supported:{$($ns:pat => $ns_r:expr ,)*})?
+        $(, specialize:[$( $sp_pat : pat => $sp_exp : expr )+ ])?
+        , unknown($uctx:ident $(=> $meta:expr)?) ) => {{
             #[allow(unused_mut)]
             let mut key;
             convert_case_to!($key, key);
@@ -25,45 +48,74 @@ macro_rules! parse_value {
             #[allow(unused_labels)]
             'parse_value_jmp_loop: loop {
                 break 'parse_value_jmp_loop match key {
-                    $(
-                        $( stringify!($register) => parse_value_inner!($app, $map, $target_type, $value_type, $register), )*
-                        $( stringify!($register_r) => parse_value_inner!($app, $map, $target_type, ref $value_type_r, $register_r), )*
-                    )*
+                    $( stringify!($register) => {
+                        let v = parse_value_inner!($map, $value_type, $uctx);
+                        $uctx.record(stringify!($register), || vec![rust_arg!($($ref)? v)]);
+                        parse_value_inner!($app, $target_type, $($ref)? v, $register)
+                    } )*
                     $($(
                         stringify!($register_t) => {
-                            let (v0, v1) = $map.next_value::<($value_type_t0, $value_type_t1)>()?;
+                            let (v0, v1) = parse_value_inner!($map, ($value_type_t0, $value_type_t1), $uctx);
+                            $uctx.record(stringify!($register_t), || vec![rust_arg!(v0), rust_arg!(v1)]);
                             <$target_type>::$register_t($app, v0, v1)
                         }
                     )*)*
                     $($(
                         stringify!($register_3t) => {
-                            let (v0, v1, v2) = $map.next_value::<($value_type_3t0, $value_type_3t1, $value_type_3t2)>()?;
+                            let (v0, v1, v2) = parse_value_inner!($map, ($value_type_3t0, $value_type_3t1, $value_type_3t2), $uctx);
+                            $uctx.record(stringify!($register_3t), || vec![rust_arg!(v0), rust_arg!(v1), rust_arg!(v2)]);
                             <$target_type>::$register_3t($app, v0, v1, v2)
                         }
                     )*)*
+                    $($(
+                        stringify!($register_m) => {
+                            match <[$value_type_m; 1]>::try_from(parse_value_inner!($map, Vec<$value_type_m>, $uctx)) {
+                                Ok([v]) => {
+                                    $uctx.record(stringify!($register_m), || vec![rust_arg!(v)]);
+                                    <$target_type>::$register_m($app, v)
+                                }
+                                Err(vs) => {
+                                    $uctx.record(stringify!($plural_m), || vec![rust_arg!($($ref_m)? vs)]);
+                                    parse_value_inner!($app, $target_type, $($ref_m)? vs, $plural_m)
+                                }
+                            }
+                        }
+                    )*)*
                     $($($sp_pat => {$sp_exp})*)*
-                    $($(depr @ ($($dep )|* ) => {return Err(Error::custom(format_args!("deprecated key: {}", depr)))})*)*
+                    $($(depr @ ($($dep )|* ) => {
+                        if !$ctx.deprecated($key, None) {
+                            return Err(Error::custom(format_args!("deprecated key: {}", depr)))
+                        }
+                        $map.next_value::<serde::de::IgnoredAny>()?;
+                        $app
+                    })*)*
                     $($($(
-                        #[cfg(feature="allow-deprecated")]
                         $dep_s => {
-                            const N_KEY : &str = stringify!($dep_d);
-                            convert_case_to!(N_KEY, key);
+                            if !$ctx.deprecated($key, Some($dep_d)) {
+                                return Err(Error::custom(format_args!("deprecated key: {}, use {} instead", $key, $dep_d)))
+                            }
+                            key = $dep_d;
                             continue 'parse_value_jmp_loop;
                         },
-                        #[cfg(not(feature="allow-deprecated"))]
-                        $dep_s => {
-                            return Err(Error::custom(format_args!("deprecated key: {}, use {} insted", stringify!($depr_s), $dep_d)))
-                        },
                     )*)*)*
                     $($(
                         $ns => {
-                            return Err(Error::custom(format_args!("not supported key : {}, {} ", stringify!($ns), stringify!($ns_r))))
+                            return Err(Error::custom(format_args!("not supported key `{}`: {}", $key, $ns_r)))
                         }
                     )*)*
-                    unknown => return Err(Error::unknown_field(unknown, &[
-                        $( $( stringify!($register),)*
-                            $( stringify!($register_r),)*  )*
-                        $($(stringify!($sp_pat),)*)* ]))
+                    _ => {
+                        const EXPECTED: &[&str] = &[
+                            $( stringify!($register),)*
+                            $($(stringify!($register_t),)*)*
+                            $($(stringify!($register_3t),)*)*
+                            $($(stringify!($register_m),)*)*
+                            $($(stringify!($sp_pat),)*)* ];
+                        let mut target = $app;
+                        if let Some((_name, _value)) = $uctx.unknown_key($key, &mut $map, EXPECTED, &mut target)? {
+                            $($meta.insert(_name, _value);)?
+                        }
+                        target
+                    }
                 }
             }
         }
@@ -3,4 +3,22 @@ mod macros;
 
 mod app;
 mod arg;
+mod auto;
+#[cfg(any(feature = "yaml", feature = "migrate"))]
+mod case;
+mod context;
 mod group;
+mod scalar;
+
+pub(crate) use app::load_root;
+pub(crate) use arg::enum_de_value;
+#[cfg(feature = "clap4")]
+pub(crate) use arg::value_parsers;
+#[cfg(feature = "migrate")]
+pub(crate) use case::same_case_as;
+#[cfg(any(feature = "yaml", feature = "migrate"))]
+pub(crate) use case::snake_case;
+#[cfg(feature = "clap4")]
+pub(crate) use context::KeyTarget;
+pub(crate) use context::{ArgFrame, Context};
+pub(crate) use scalar::{KeyValue, OneOrMany};
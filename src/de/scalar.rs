@@ -0,0 +1,320 @@
+use crate::StringStore;
+use serde::{
+    de::{Error, IntoDeserializer, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use std::{borrow::Cow, marker::PhantomData};
+
+/// Type of the value for a key in the key tables of `parse_value!`.
+///
+/// The value is first deserialized as [`KeyValue::Raw`] and then converted,
+/// so that keys typed as `&str` can also accept other scalars.
+pub(crate) trait KeyValue<'de>: Sized {
+    type Raw: Deserialize<'de>;
+    /// Convert `raw`, keeping the strings made in `strings`, failing if a string is made without a store.
+    fn from_raw(raw: Self::Raw, strings: Option<&'de StringStore>) -> Result<Self, String>;
+
+    /// Length of the longest string in `raw`, checked against the limit before it is converted.
+    fn longest(_raw: &Self::Raw) -> usize {
+        0
+    }
+}
+
+macro_rules! key_value_as_is {
+    ($($ty: ty),*) => {
+        $(
+            impl<'de> KeyValue<'de> for $ty {
+                type Raw = Self;
+                fn from_raw(raw: Self::Raw, _: Option<&'de StringStore>) -> Result<Self, String> {
+                    Ok(raw)
+                }
+            }
+        )*
+    };
+}
+
+key_value_as_is!(bool, usize);
+
+/// `char` keys are short names, which clap panics with if `-`.
+impl<'de> KeyValue<'de> for char {
+    type Raw = Short;
+    fn from_raw(raw: Self::Raw, _: Option<&'de StringStore>) -> Result<Self, String> {
+        Ok(raw.0)
+    }
+}
+
+impl<'de> KeyValue<'de> for &'de str {
+    type Raw = Scalar<'de>;
+    fn from_raw(raw: Self::Raw, strings: Option<&'de StringStore>) -> Result<Self, String> {
+        raw.into_str(strings)
+    }
+
+    fn longest(raw: &Self::Raw) -> usize {
+        raw.0.len()
+    }
+}
+
+/// Owned string, for the clap 4 backend whose builders don't borrow the input.
+impl<'de> KeyValue<'de> for String {
+    type Raw = Scalar<'de>;
+    fn from_raw(raw: Self::Raw, _: Option<&'de StringStore>) -> Result<Self, String> {
+        Ok(raw.0.into_owned())
+    }
+
+    fn longest(raw: &Self::Raw) -> usize {
+        raw.0.len()
+    }
+}
+
+impl<'de, T: KeyValue<'de>> KeyValue<'de> for Option<T> {
+    type Raw = Option<T::Raw>;
+    fn from_raw(raw: Self::Raw, strings: Option<&'de StringStore>) -> Result<Self, String> {
+        raw.map(|v| T::from_raw(v, strings)).transpose()
+    }
+
+    fn longest(raw: &Self::Raw) -> usize {
+        raw.as_ref().map_or(0, T::longest)
+    }
+}
+
+impl<'de, T: KeyValue<'de>> KeyValue<'de> for Vec<T> {
+    type Raw = OneOrMany<T::Raw>;
+    fn from_raw(raw: Self::Raw, strings: Option<&'de StringStore>) -> Result<Self, String> {
+        raw.0.into_iter().map(|v| T::from_raw(v, strings)).collect()
+    }
+
+    fn longest(raw: &Self::Raw) -> usize {
+        raw.0.iter().map(T::longest).max().unwrap_or(0)
+    }
+}
+
+impl<'de, T0: KeyValue<'de>, T1: KeyValue<'de>> KeyValue<'de> for (T0, T1) {
+    type Raw = (T0::Raw, T1::Raw);
+    fn from_raw((v0, v1): Self::Raw, strings: Option<&'de StringStore>) -> Result<Self, String> {
+        Ok((T0::from_raw(v0, strings)?, T1::from_raw(v1, strings)?))
+    }
+
+    fn longest((v0, v1): &Self::Raw) -> usize {
+        T0::longest(v0).max(T1::longest(v1))
+    }
+}
+
+impl<'de, T0: KeyValue<'de>, T1: KeyValue<'de>, T2: KeyValue<'de>> KeyValue<'de> for (T0, T1, T2) {
+    type Raw = (T0::Raw, T1::Raw, T2::Raw);
+    fn from_raw(
+        (v0, v1, v2): Self::Raw,
+        strings: Option<&'de StringStore>,
+    ) -> Result<Self, String> {
+        Ok((
+            T0::from_raw(v0, strings)?,
+            T1::from_raw(v1, strings)?,
+            T2::from_raw(v2, strings)?,
+        ))
+    }
+
+    fn longest((v0, v1, v2): &Self::Raw) -> usize {
+        T0::longest(v0).max(T1::longest(v1)).max(T2::longest(v2))
+    }
+}
+
+/// String borrowed from the input, or an owned string like a stringified integer, float or boolean.
+///
+/// Formats give the parsed value of a scalar, which is stringified here,
+/// so `1.0` stays `1.0` but `0x10` of yaml becomes `16`.
+/// As clap only borrows strings, the owned ones are kept in the [`StringStore`] of the loader, if any.
+pub(crate) struct Scalar<'de>(pub(crate) Cow<'de, str>);
+
+impl<'de> Scalar<'de> {
+    pub(crate) fn into_str(self, strings: Option<&'de StringStore>) -> Result<&'de str, String> {
+        match self.0 {
+            Cow::Borrowed(s) => Ok(s),
+            Cow::Owned(s) => crate::strings::keep(strings, s),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Scalar<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ScalarVisitor)
+    }
+}
+
+struct ScalarVisitor;
+
+impl<'de> Visitor<'de> for ScalarVisitor {
+    type Value = Scalar<'de>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a string, an integer, a float or a boolean")
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Scalar(Cow::Borrowed(v)))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Scalar(Cow::Owned(v.to_owned())))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Scalar(Cow::Owned(v)))
+    }
+
+    fn visit_char<E>(self, v: char) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Scalar(Cow::Owned(v.to_string())))
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Scalar(Cow::Borrowed(if v { "true" } else { "false" })))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Scalar(Cow::Owned(v.to_string())))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Scalar(Cow::Owned(v.to_string())))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        // `Debug` keeps the fractional part, so `1.0` stays `1.0` instead of `1`.
+        Ok(Scalar(Cow::Owned(format!("{:?}", v))))
+    }
+}
+
+/// A char other than `-`.
+pub(crate) struct Short(char);
+
+impl<'de> Deserialize<'de> for Short {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match char::deserialize(deserializer)? {
+            '-' => Err(D::Error::invalid_value(
+                serde::de::Unexpected::Char('-'),
+                &"a short name other than `-`",
+            )),
+            c => Ok(Short(c)),
+        }
+    }
+}
+
+/// A list, or a single scalar treated as a list of one element.
+pub(crate) struct OneOrMany<T>(pub(crate) Vec<T>);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for OneOrMany<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(OneOrManyVisitor(PhantomData))
+    }
+}
+
+struct OneOrManyVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> OneOrManyVisitor<T> {
+    fn one<E, D>(v: D) -> Result<OneOrMany<T>, E>
+    where
+        E: Error,
+        D: Deserializer<'de, Error = E>,
+    {
+        T::deserialize(v).map(|v| OneOrMany(vec![v]))
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for OneOrManyVisitor<T> {
+    type Value = OneOrMany<T>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a value or a list of values")
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Self::one(serde::de::value::BorrowedStrDeserializer::new(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Self::one(v.into_deserializer())
+    }
+
+    fn visit_char<E>(self, v: char) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Self::one(v.into_deserializer())
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Self::one(v.into_deserializer())
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Self::one(v.into_deserializer())
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Self::one(v.into_deserializer())
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Self::one(v.into_deserializer())
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut vs = Vec::new();
+        while let Some(v) = seq.next_element()? {
+            vs.push(v);
+        }
+        Ok(OneOrMany(vs))
+    }
+}
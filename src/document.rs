@@ -0,0 +1,402 @@
+use crate::{
+    de::{load_root, Context},
+    source::{LoadError, Location},
+    StringStore,
+};
+use clap::Command;
+use serde::de::{
+    self,
+    value::{BorrowedStrDeserializer, MapAccessDeserializer},
+    DeserializeSeed, MapAccess, SeqAccess, Visitor,
+};
+use std::{cell::Cell, fmt::Display};
+
+/// Value of a [`Document`].
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Seq(Vec<Value>),
+    Map(Vec<Entry>),
+}
+
+/// Key and value of a map, in the order of the source.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Entry {
+    pub(crate) key: String,
+    /// Location of the key in the source, if known.
+    pub(crate) location: Option<Location>,
+    pub(crate) value: Value,
+}
+
+/**
+Config parsed into owned values, for the formats whose deserializers don't borrow the strings.
+
+The [`Command`] loaded by [`Document::command`] borrows the document.
+```
+# #[cfg(feature = "kdl")] {
+const CLAP_KDL: &str = r#"
+name "app"
+arg "verbose" short="v" help="more output"
+subcommand "test" {
+    about "run tests"
+}
+"#;
+let doc = clap_serde::Document::from_kdl(CLAP_KDL).expect("parse failed");
+let app = doc.command().expect("load failed");
+assert_eq!(app.get_name(), "app");
+assert!(app.find_subcommand("test").is_some());
+# }
+```
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    root: Value,
+    /// Text of the source, to show the line of the errors.
+    text: String,
+}
+
+impl Document {
+    /// Parse a json5 string.
+    #[cfg(feature = "json5")]
+    pub fn from_json5(src: &str) -> Result<Self, LoadError> {
+        json5::from_str(src)
+            .map(|root| Self {
+                root,
+                text: src.to_owned(),
+            })
+            .map_err(|json5::Error::Message { msg, location }| {
+                let location = location.map(|l| Location {
+                    line: l.line,
+                    column: l.column,
+                });
+                LoadError::new(msg, location, src)
+            })
+    }
+
+    /**
+    Parse a kdl string.
+
+    Each node is a key, and its value is
+    - `true` if the node has nothing, like `required`,
+    - the argument if it has one, like `short "v"`,
+    - a list of the arguments if it has more, like `aliases "a" "b"`,
+    - a list of the children named `-` if all of them are, like `aliases { - "a"; - "b"; }`,
+    - a map of the properties and the children otherwise, like `value_parser type="i64" min=0`.
+
+    The nodes `arg "id"`, `subcommand "name"` and `group "id"` are loaded as the items of
+    `args`, `subcommands` and `groups`, with the properties and children of the node.
+    Bare words like `action count` are strings, and both `true` and `#true` are booleans.
+    */
+    #[cfg(feature = "kdl")]
+    pub fn from_kdl(src: &str) -> Result<Self, LoadError> {
+        crate::kdl::parse(src).map(|root| Self {
+            root,
+            text: src.to_owned(),
+        })
+    }
+
+    /// Load [`Command`] borrowing the document, with the location of the last key read in the error.
+    pub fn command(&self) -> Result<Command<'_>, LoadError> {
+        self.load(Borrow, &mut Context::new())
+    }
+
+    /// Load [`Command`] in `ctx`, with the strings of the document given by `strs`.
+    pub(crate) fn load<'v, 'de, S: Strs<'v, 'de>>(
+        &'v self,
+        strs: S,
+        ctx: &mut Context<'de>,
+    ) -> Result<Command<'de>, LoadError> {
+        let last = Cell::new(None);
+        let de = ValueDeserializer {
+            value: &self.root,
+            strs,
+            last: &last,
+        };
+        load_root(de, ctx)
+            .map(Command::from)
+            .map_err(|e| LoadError::new(e.0, last.get(), &self.text))
+    }
+}
+
+/// How the strings of a [`Document`] borrowed for `'v` are given to the loader as `&'de str`.
+pub(crate) trait Strs<'v, 'de>: Copy {
+    fn get(self, s: &'v str) -> &'de str;
+}
+
+/// The strings borrowed from the document itself.
+#[derive(Clone, Copy)]
+pub(crate) struct Borrow;
+
+impl<'v: 'de, 'de> Strs<'v, 'de> for Borrow {
+    fn get(self, s: &'v str) -> &'de str {
+        s
+    }
+}
+
+/// The strings kept in the store, for a document which doesn't live as long as the command.
+impl<'v, 'de> Strs<'v, 'de> for &'de StringStore {
+    fn get(self, s: &'v str) -> &'de str {
+        self.add(s.to_owned())
+    }
+}
+
+#[cfg(feature = "json5")]
+impl<'de> de::Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+#[cfg(feature = "json5")]
+struct ValueVisitor;
+
+#[cfg(feature = "json5")]
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        Ok(i64::try_from(v).map_or(Value::Float(v as f64), Value::Int))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::Str(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::Str(v))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        de::Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Value::Seq(values))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::new();
+        while let Some((key, value)) = map.next_entry()? {
+            entries.push(Entry {
+                key,
+                location: None,
+                value,
+            });
+        }
+        Ok(Value::Map(entries))
+    }
+}
+
+/// Error of loading a [`Document`], which is located by the last key read.
+#[derive(Debug)]
+struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+struct ValueDeserializer<'v, 'c, S> {
+    value: &'v Value,
+    strs: S,
+    /// Location of the last key read, which is where the error is if any.
+    last: &'c Cell<Option<Location>>,
+}
+
+impl<'v, 'c, S: Copy> ValueDeserializer<'v, 'c, S> {
+    fn map(&self, entries: &'v [Entry]) -> MapDeserializer<'v, 'c, S> {
+        MapDeserializer {
+            iter: entries.iter(),
+            value: None,
+            strs: self.strs,
+            last: self.last,
+        }
+    }
+}
+
+impl<'v, 'de, 'c, S: Strs<'v, 'de>> de::Deserializer<'de> for ValueDeserializer<'v, 'c, S> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(v) => visitor.visit_bool(*v),
+            Value::Int(v) => visitor.visit_i64(*v),
+            Value::Float(v) => visitor.visit_f64(*v),
+            Value::Str(v) => visitor.visit_borrowed_str(self.strs.get(v)),
+            Value::Seq(values) => visitor.visit_seq(SeqDeserializer {
+                iter: values.iter(),
+                strs: self.strs,
+                last: self.last,
+            }),
+            Value::Map(entries) => visitor.visit_map(self.map(entries)),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Str(v) => visitor.visit_enum(BorrowedStrDeserializer::new(self.strs.get(v))),
+            Value::Map(entries) => {
+                visitor.visit_enum(MapAccessDeserializer::new(self.map(entries)))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct SeqDeserializer<'v, 'c, S> {
+    iter: std::slice::Iter<'v, Value>,
+    strs: S,
+    last: &'c Cell<Option<Location>>,
+}
+
+impl<'v, 'de, 'c, S: Strs<'v, 'de>> SeqAccess<'de> for SeqDeserializer<'v, 'c, S> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.iter
+            .next()
+            .map(|value| {
+                seed.deserialize(ValueDeserializer {
+                    value,
+                    strs: self.strs,
+                    last: self.last,
+                })
+            })
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapDeserializer<'v, 'c, S> {
+    iter: std::slice::Iter<'v, Entry>,
+    value: Option<&'v Value>,
+    strs: S,
+    last: &'c Cell<Option<Location>>,
+}
+
+impl<'v, 'de, 'c, S: Strs<'v, 'de>> MapAccess<'de> for MapDeserializer<'v, 'c, S> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(entry) => {
+                if entry.location.is_some() {
+                    self.last.set(entry.location);
+                }
+                self.value = Some(&entry.value);
+                seed.deserialize(BorrowedStrDeserializer::new(self.strs.get(&entry.key)))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value is missing"))?;
+        seed.deserialize(ValueDeserializer {
+            value,
+            strs: self.strs,
+            last: self.last,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
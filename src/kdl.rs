@@ -0,0 +1,551 @@
+//! Parser of kdl into the values of a [`Document`](crate::Document).
+//!
+//! Both kdl v1 and v2 are accepted, except the multi-line strings of v2.
+
+use crate::document::{Entry, Value};
+use crate::source::{LoadError, Location};
+
+type Error = (String, Location);
+
+struct Node {
+    name: String,
+    location: Location,
+    args: Vec<Value>,
+    props: Vec<Entry>,
+    children: Option<Vec<Node>>,
+}
+
+/// Nodes loaded as the items of a key of commands, and whether the key is a list of single key maps.
+const ITEM_NODES: [(&str, &str, bool); 3] = [
+    ("arg", "args", true),
+    ("subcommand", "subcommands", true),
+    ("group", "groups", false),
+];
+
+pub(crate) fn parse(src: &str) -> Result<Value, LoadError> {
+    let mut parser = Parser {
+        src,
+        pos: 0,
+        line: 1,
+        line_start: 0,
+    };
+    parser
+        .nodes(false)
+        .and_then(|nodes| into_map(nodes, true))
+        .map(Value::Map)
+        .map_err(|(message, location)| LoadError::new(message, Some(location), src))
+}
+
+/// Entries of `nodes`, collecting the [`ITEM_NODES`] if they are the keys of a command.
+fn into_map(nodes: Vec<Node>, command: bool) -> Result<Vec<Entry>, Error> {
+    let mut entries: Vec<Entry> = Vec::new();
+    // Index in `entries` of the key collecting each of the `ITEM_NODES`.
+    let mut collected = [None; ITEM_NODES.len()];
+    for node in nodes {
+        let item = ITEM_NODES
+            .iter()
+            .position(|(name, _, _)| command && *name == node.name);
+        let Some(i) = item else {
+            entries.push(Entry {
+                key: node.name.clone(),
+                location: Some(node.location),
+                value: node.into_value()?,
+            });
+            continue;
+        };
+        let (_, key, list) = ITEM_NODES[i];
+        let location = Some(node.location);
+        let entry = node.into_item(key == "subcommands")?;
+        let j = *collected[i].get_or_insert_with(|| {
+            entries.push(Entry {
+                key: key.to_owned(),
+                location,
+                value: match list {
+                    true => Value::Seq(Vec::new()),
+                    false => Value::Map(Vec::new()),
+                },
+            });
+            entries.len() - 1
+        });
+        match &mut entries[j].value {
+            Value::Seq(items) => items.push(Value::Map(vec![entry])),
+            Value::Map(items) => items.push(entry),
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+impl Node {
+    fn error(&self, message: &str) -> Error {
+        (format!("node `{}` {}", self.name, message), self.location)
+    }
+
+    /// The node like `arg "id" short="v"`, as the entry of the id.
+    fn into_item(mut self, command: bool) -> Result<Entry, Error> {
+        let key = match self.args.pop() {
+            Some(Value::Str(id)) if self.args.is_empty() => id,
+            _ => return Err(self.error("needs one string argument as the id")),
+        };
+        let mut entries = self.props;
+        entries.extend(into_map(self.children.unwrap_or_default(), command)?);
+        Ok(Entry {
+            key,
+            location: Some(self.location),
+            value: Value::Map(entries),
+        })
+    }
+
+    fn into_value(mut self) -> Result<Value, Error> {
+        if !self.args.is_empty() {
+            return match (self.props.is_empty(), &self.children) {
+                (true, None) if self.args.len() == 1 => Ok(self.args.remove(0)),
+                (true, None) => Ok(Value::Seq(self.args)),
+                _ => Err(self.error("has both arguments and properties or children")),
+            };
+        }
+        match self.children {
+            None if self.props.is_empty() => Ok(Value::Bool(true)),
+            Some(children)
+                if self.props.is_empty()
+                    && !children.is_empty()
+                    && children.iter().all(|c| c.name == "-") =>
+            {
+                children
+                    .into_iter()
+                    .map(Node::into_value)
+                    .collect::<Result<_, _>>()
+                    .map(Value::Seq)
+            }
+            children => {
+                let mut entries = self.props;
+                entries.extend(into_map(children.unwrap_or_default(), false)?);
+                Ok(Value::Map(entries))
+            }
+        }
+    }
+}
+
+fn is_newline(c: char) -> bool {
+    matches!(
+        c,
+        '\n' | '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}'
+    )
+}
+
+fn is_space(c: char) -> bool {
+    (c.is_whitespace() && !is_newline(c)) || c == '\u{FEFF}'
+}
+
+fn is_ident_char(c: char) -> bool {
+    !(is_space(c) || is_newline(c) || "\\/(){}<>;[]=,\"".contains(c) || c.is_control())
+}
+
+/// Argument or property of a node.
+enum Item {
+    Arg(Value),
+    Prop(Entry),
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    line: usize,
+    /// Position of the start of the current line.
+    line_start: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn location(&self) -> Location {
+        Location {
+            line: self.line,
+            column: self.src[self.line_start..self.pos].chars().count() + 1,
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, Error> {
+        Err((message.into(), self.location()))
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        // `\r\n` is a newline at `\n`.
+        if is_newline(c) && !(c == '\r' && self.peek() == Some('\n')) {
+            self.line += 1;
+            self.line_start = self.pos;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        let found = self.rest().starts_with(s);
+        if found {
+            s.chars().for_each(|_| {
+                self.bump();
+            });
+        }
+        found
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), Error> {
+        match self.eat(s) {
+            true => Ok(()),
+            false => self.error(format!("expected `{}`", s)),
+        }
+    }
+
+    /// Skip a comment starting with `/*`, which can be nested.
+    fn block_comment(&mut self) -> Result<(), Error> {
+        let start = self.location();
+        self.expect("/*")?;
+        let mut depth = 1;
+        while depth > 0 {
+            if self.eat("/*") {
+                depth += 1;
+            } else if self.eat("*/") {
+                depth -= 1;
+            } else if self.bump().is_none() {
+                return Err(("unclosed comment".to_owned(), start));
+            }
+        }
+        Ok(())
+    }
+
+    /// Skip a comment starting with `//`, with the newline.
+    fn line_comment(&mut self) {
+        while let Some(c) = self.bump() {
+            if is_newline(c) && !(c == '\r' && self.peek() == Some('\n')) {
+                break;
+            }
+        }
+    }
+
+    /// Skip spaces in a node, which are spaces, block comments and escaped newlines.
+    fn node_space(&mut self) -> Result<bool, Error> {
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                Some(c) if is_space(c) => {
+                    self.bump();
+                }
+                Some('/') if self.rest().starts_with("/*") => self.block_comment()?,
+                Some('\\') => {
+                    self.bump();
+                    while let Some(c) = self.peek() {
+                        match c {
+                            c if is_space(c) => {
+                                self.bump();
+                            }
+                            '/' if self.rest().starts_with("/*") => self.block_comment()?,
+                            _ => break,
+                        }
+                    }
+                    match self.peek() {
+                        Some('/') if self.rest().starts_with("//") => self.line_comment(),
+                        Some(c) if is_newline(c) => {
+                            self.eat("\r");
+                            self.bump();
+                        }
+                        None => {}
+                        Some(_) => return self.error("expected a newline after `\\`"),
+                    }
+                }
+                _ => return Ok(self.pos > start),
+            }
+        }
+    }
+
+    /// Skip spaces, newlines, comments and `;` between nodes.
+    fn line_space(&mut self) -> Result<(), Error> {
+        loop {
+            self.node_space()?;
+            match self.peek() {
+                Some(c) if is_newline(c) || c == ';' => {
+                    self.bump();
+                }
+                Some('/') if self.rest().starts_with("//") => self.line_comment(),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn nodes(&mut self, in_children: bool) -> Result<Vec<Node>, Error> {
+        let mut nodes = Vec::new();
+        loop {
+            self.line_space()?;
+            match self.peek() {
+                None if in_children => return self.error("expected `}`"),
+                None => return Ok(nodes),
+                Some('}') if in_children => return Ok(nodes),
+                Some('}') => return self.error("unexpected `}`"),
+                _ if self.eat("/-") => {
+                    self.line_space()?;
+                    self.node()?;
+                }
+                _ => nodes.push(self.node()?),
+            }
+        }
+    }
+
+    fn children(&mut self) -> Result<Vec<Node>, Error> {
+        self.expect("{")?;
+        let nodes = self.nodes(true)?;
+        self.expect("}")?;
+        Ok(nodes)
+    }
+
+    fn node(&mut self) -> Result<Node, Error> {
+        self.type_annotation()?;
+        let location = self.location();
+        let name = match self.peek() {
+            Some('"') => self.string()?,
+            _ if self.raw_string_start().is_some() => self.string()?,
+            _ => match self.bare()? {
+                Value::Str(name) => name,
+                _ => return Err(("expected a node name".to_owned(), location)),
+            },
+        };
+        let mut node = Node {
+            name,
+            location,
+            args: Vec::new(),
+            props: Vec::new(),
+            children: None,
+        };
+        loop {
+            let spaced = self.node_space()?;
+            match self.peek() {
+                None | Some('}') => break,
+                Some(c) if is_newline(c) || c == ';' => {
+                    self.bump();
+                    break;
+                }
+                Some('/') if self.rest().starts_with("//") => {
+                    self.line_comment();
+                    break;
+                }
+                Some('{') if node.children.is_none() => node.children = Some(self.children()?),
+                // Children commented out by a slashdash can be before or after the children.
+                _ if self.eat("/-") => {
+                    self.node_space()?;
+                    match self.peek() {
+                        Some('{') => {
+                            self.children()?;
+                        }
+                        _ if node.children.is_some() => {
+                            return self.error("expected the end of the node after the children")
+                        }
+                        _ => {
+                            self.item()?;
+                        }
+                    }
+                }
+                _ if node.children.is_some() => {
+                    return self.error("expected the end of the node after the children")
+                }
+                _ if !spaced => return self.error("expected a space"),
+                _ => match self.item()? {
+                    Item::Arg(v) => node.args.push(v),
+                    Item::Prop(p) => node.props.push(p),
+                },
+            }
+        }
+        Ok(node)
+    }
+
+    /// Skip a type annotation like `(u8)`, which is not used, with the spaces after it as v2.
+    fn type_annotation(&mut self) -> Result<(), Error> {
+        if self.eat("(") {
+            self.node_space()?;
+            let location = self.location();
+            if !matches!(self.value()?, Value::Str(_)) {
+                return Err(("expected a type name".to_owned(), location));
+            }
+            self.node_space()?;
+            self.expect(")")?;
+            self.node_space()?;
+        }
+        Ok(())
+    }
+
+    fn item(&mut self) -> Result<Item, Error> {
+        self.type_annotation()?;
+        let location = self.location();
+        let value = self.value()?;
+        // v2 allows spaces around `=`, so go back if no `=` is after them.
+        let (pos, line, line_start) = (self.pos, self.line, self.line_start);
+        self.node_space()?;
+        if !self.eat("=") {
+            (self.pos, self.line, self.line_start) = (pos, line, line_start);
+            return Ok(Item::Arg(value));
+        }
+        let key = match value {
+            Value::Str(key) => key,
+            _ => return Err(("expected a property name".to_owned(), location)),
+        };
+        self.node_space()?;
+        self.type_annotation()?;
+        Ok(Item::Prop(Entry {
+            key,
+            location: Some(location),
+            value: self.value()?,
+        }))
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        match self.peek() {
+            Some('"') => self.string().map(Value::Str),
+            _ if self.raw_string_start().is_some() => self.string().map(Value::Str),
+            Some(c) if is_ident_char(c) => self.bare(),
+            _ => self.error("expected a value"),
+        }
+    }
+
+    /// Number of `#` of the raw string starting here, like `r#"` of v1 or `#"` of v2.
+    fn raw_string_start(&self) -> Option<usize> {
+        let rest = self.rest();
+        let rest = rest.strip_prefix('r').unwrap_or(rest);
+        let hashes = rest.len() - rest.trim_start_matches('#').len();
+        let v1 = self.rest().starts_with('r');
+        (rest[hashes..].starts_with('"') && (v1 || hashes > 0)).then_some(hashes)
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let start = self.location();
+        let unclosed = || Err(("unclosed string".to_owned(), start));
+        if let Some(hashes) = self.raw_string_start() {
+            self.eat("r");
+            let close = format!("\"{}", "#".repeat(hashes));
+            self.pos += hashes + 1;
+            let Some(len) = self.rest().find(&close) else {
+                return unclosed();
+            };
+            let s = self.rest()[..len].to_owned();
+            s.chars().chain(close.chars()).for_each(|_| {
+                self.bump();
+            });
+            return Ok(s);
+        }
+        if self.rest().starts_with("\"\"\"") {
+            return self.error("multi-line strings are not supported");
+        }
+        self.expect("\"")?;
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                None => return unclosed(),
+                Some('"') => return Ok(s),
+                Some('\\') => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('b') => s.push('\u{08}'),
+                    Some('f') => s.push('\u{0C}'),
+                    Some('s') => s.push(' '),
+                    Some(c @ ('\\' | '/' | '"')) => s.push(c),
+                    Some('u') => {
+                        let c = self.unicode_escape()?;
+                        s.push(c);
+                    }
+                    Some(c) if is_space(c) || is_newline(c) => {
+                        while self.peek().is_some_and(|c| is_space(c) || is_newline(c)) {
+                            self.bump();
+                        }
+                    }
+                    _ => return self.error("invalid escape in string"),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    /// The char of `{XXXX}` after `\u`.
+    fn unicode_escape(&mut self) -> Result<char, Error> {
+        self.expect("{")?;
+        let rest = self.rest();
+        let len = rest.find('}').unwrap_or(rest.len());
+        let c = u32::from_str_radix(&rest[..len], 16)
+            .ok()
+            .filter(|_| (1..=6).contains(&len))
+            .and_then(char::from_u32);
+        match c {
+            Some(c) => {
+                self.pos += len;
+                self.expect("}")?;
+                Ok(c)
+            }
+            None => self.error("invalid unicode escape"),
+        }
+    }
+
+    /// A bare word, which is a number, a keyword or a string.
+    fn bare(&mut self) -> Result<Value, Error> {
+        let location = self.location();
+        let len = self
+            .rest()
+            .find(|c| !is_ident_char(c))
+            .unwrap_or(self.rest().len());
+        let word = &self.rest()[..len];
+        self.pos += len;
+        let error = |message: &str| Err((format!("{} `{}`", message, word), location));
+        let value = match word {
+            "" => return error("expected a value"),
+            "true" | "#true" => Value::Bool(true),
+            "false" | "#false" => Value::Bool(false),
+            "null" | "#null" => Value::Null,
+            "#inf" => Value::Float(f64::INFINITY),
+            "#-inf" => Value::Float(f64::NEG_INFINITY),
+            "#nan" => Value::Float(f64::NAN),
+            w if w.starts_with('#') => return error("unknown keyword"),
+            w if is_number(w) => match parse_number(w) {
+                Some(v) => v,
+                None => return error("invalid number"),
+            },
+            w => Value::Str(w.to_owned()),
+        };
+        Ok(value)
+    }
+}
+
+fn is_number(word: &str) -> bool {
+    let word = word.strip_prefix(['+', '-']).unwrap_or(word);
+    word.starts_with(|c: char| c.is_ascii_digit())
+        || (word.starts_with('.') && word[1..].starts_with(|c: char| c.is_ascii_digit()))
+}
+
+fn parse_number(word: &str) -> Option<Value> {
+    let word = word.replace('_', "");
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word.strip_prefix('+').unwrap_or(&word)),
+    };
+    let radix = [("0x", 16), ("0o", 8), ("0b", 2)]
+        .into_iter()
+        .find_map(|(prefix, radix)| Some((digits.strip_prefix(prefix)?, radix)));
+    if let Some((digits, radix)) = radix {
+        // The sign is only before the prefix, which `from_str_radix` would take after it.
+        if digits.starts_with(['+', '-']) {
+            return None;
+        }
+        let v = u64::from_str_radix(digits, radix).ok()?;
+        let v = if negative {
+            0i64.checked_sub_unsigned(v)?
+        } else {
+            i64::try_from(v).ok()?
+        };
+        return Some(Value::Int(v));
+    }
+    if let Ok(v) = word.parse::<i64>() {
+        return Some(Value::Int(v));
+    }
+    word.parse::<f64>().ok().map(Value::Float)
+}
@@ -19,10 +19,37 @@ compile_error!("Case setting feature is missing. Either one should be set.");
 ))]
 compile_error!("Case setting feature is conflicting. Only one should be set.");
 
+mod check;
+pub mod codegen;
+#[cfg(feature = "yaml")]
+pub mod compat;
+mod conflicts;
+#[cfg(feature = "migrate")]
+pub mod migrate;
+#[cfg(any(feature = "yaml", feature = "migrate"))]
+mod migration;
 #[macro_use]
 mod de;
+#[cfg(feature = "clap4")]
+pub mod clap4;
+#[cfg(any(feature = "json5", feature = "kdl"))]
+mod document;
 #[cfg(feature = "docsrs")]
 pub mod documents;
+#[cfg(feature = "kdl")]
+mod kdl;
+mod metadata;
+mod options;
+#[cfg(any(
+    feature = "toml",
+    feature = "serde_yaml",
+    feature = "serde_json",
+    feature = "ron",
+    feature = "json5",
+    feature = "kdl"
+))]
+mod source;
+mod strings;
 #[cfg(feature = "yaml")]
 #[deprecated(since = "0.4", note = "use serde-yaml instead")]
 mod yaml;
@@ -30,6 +57,31 @@ mod yaml;
 #[cfg(all(test, feature = "snake-case-key"))]
 mod tests;
 
+pub use conflicts::{find_conflicts, Conflict};
+#[cfg(any(feature = "json5", feature = "kdl"))]
+pub use document::Document;
+pub use metadata::{MetaValue, Metadata};
+pub use options::{Duplicates, KeyHandler, LoadOptions, UnknownKeys};
+#[cfg(feature = "serde_json")]
+pub use source::from_json_str;
+#[cfg(feature = "ron")]
+pub use source::from_ron_str;
+#[cfg(feature = "toml")]
+pub use source::from_toml_str;
+#[cfg(feature = "serde_yaml")]
+pub use source::from_yaml_str;
+#[cfg(any(
+    feature = "toml",
+    feature = "serde_yaml",
+    feature = "serde_json",
+    feature = "ron",
+    feature = "json5",
+    feature = "kdl"
+))]
+pub use source::{
+    from_path, from_str_with_format, Format, LoadError, LoadedCommand, Location, Source,
+};
+pub use strings::StringStore;
 #[cfg(feature = "yaml")]
 pub use yaml::{yaml_to_app, YamlWrap};
 
@@ -56,6 +108,194 @@ where
     CommandWrap::deserialize(de).map(|a| a.into())
 }
 
+/**
+Deserialize [`Command`] from [`Deserializer`], and check it as clap does before parsing.
+
+Invalid combinations of keys, which would panic in [`Command::debug_assert`] or [`Command::get_matches`],
+fail to load with the error naming the key, like `args.ignore.short`.
+Arg ids used more than once, and the flags and subcommand names found by [`find_conflicts`], are found in all builds.
+The other checks, like a `requires` or `conflicts_with` naming a missing arg, are the debug assertions of clap,
+which run only in the builds with `debug_assertions` and `panic = "unwind"`,
+and whose panic is caught without the panic hook printing it.
+```
+const CLAP_TOML: &'static str = r#"
+name = "app_clap_serde"
+[args]
+input = { short = "i" }
+ignore = { short = "i" }
+"#;
+let result = clap_serde::load_checked(&mut toml::Deserializer::new(CLAP_TOML));
+assert!(result.unwrap_err().to_string().starts_with("args.ignore.short"));
+```
+*/
+pub fn load_checked<'de, D>(de: D) -> Result<Command<'de>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+    let app = load(de)?;
+    check::check(&app).map_err(D::Error::custom)?;
+    Ok(app)
+}
+
+/// Key which can be loaded only when a cargo feature is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GatedKey {
+    /// Name of the key (in snake_case).
+    pub key: &'static str,
+    /// Cargo feature of this crate needed to load the key.
+    pub feature: &'static str,
+    /// Whether the feature is enabled in this build.
+    pub enabled: bool,
+}
+
+const GATED_KEYS: &[GatedKey] = &[
+    GatedKey {
+        key: "color",
+        feature: "color",
+        enabled: cfg!(feature = "color"),
+    },
+    GatedKey {
+        key: "env",
+        feature: "env",
+        enabled: cfg!(feature = "env"),
+    },
+    GatedKey {
+        key: "env_prefix",
+        feature: "env",
+        enabled: cfg!(feature = "env"),
+    },
+    GatedKey {
+        key: "auto_env",
+        feature: "env",
+        enabled: cfg!(feature = "env"),
+    },
+    GatedKey {
+        key: "hide_env",
+        feature: "env",
+        enabled: cfg!(feature = "env"),
+    },
+    GatedKey {
+        key: "hide_env_values",
+        feature: "env",
+        enabled: cfg!(feature = "env"),
+    },
+];
+
+/**
+Keys gated by cargo features, and whether they are supported in this build.
+```
+let env = clap_serde::gated_keys()
+    .iter()
+    .find(|k| k.key == "env")
+    .unwrap();
+assert_eq!(env.enabled, cfg!(feature = "env"));
+```
+*/
+pub fn gated_keys() -> &'static [GatedKey] {
+    GATED_KEYS
+}
+
+/**
+Deserialize [`Command`] from [`Deserializer`], collecting [`Diagnostic`]s of the deprecated keys.
+
+Deprecated keys with a replacement are loaded as the replacement, and the others are skipped,
+regardless of the `allow-deprecated` feature.
+```
+const CLAP_TOML: &'static str = r#"
+name = "app_clap_serde"
+help_heading = "OPTIONS"
+"#;
+let (app, diagnostics) =
+    clap_serde::load_with_diagnostics(&mut toml::Deserializer::new(CLAP_TOML))
+        .expect("parse failed");
+assert_eq!(app.get_next_help_heading(), Some("OPTIONS"));
+assert_eq!(diagnostics[0].key, "help_heading");
+assert_eq!(diagnostics[0].replacement, Some("next_help_heading"));
+```
+*/
+pub fn load_with_diagnostics<'de, D>(de: D) -> Result<(Command<'de>, Vec<Diagnostic>), D::Error>
+where
+    D: Deserializer<'de>,
+{
+    LoadOptions::new()
+        .load(de)
+        .map(|(app, diagnostics)| (app.into(), diagnostics))
+}
+
+/// What a [`Diagnostic`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// The key is deprecated.
+    Deprecated,
+    /// The key is not known, and skipped.
+    UnknownKey,
+    /// The key, arg id or subcommand name is given more than once.
+    Duplicate,
+}
+
+/// Severity of a [`Diagnostic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The key is loaded as its replacement, or skipped as it is not needed.
+    Warning,
+    /// The key is skipped, though it was needed.
+    Error,
+}
+
+/// A key reported by [`load_with_diagnostics`] or [`LoadOptions::load`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Path of the key, like `subcommands.test.args.debug.name`.
+    pub path: String,
+    /// The key as written in the config.
+    pub key: String,
+    /// Key (in snake_case) loaded instead, if any.
+    pub replacement: Option<&'static str>,
+    /// How the key is treated, as a warning or an error.
+    pub severity: Severity,
+    /// Why the key is reported, like a deprecated or an unknown key.
+    pub kind: DiagnosticKind,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.kind, self.replacement) {
+            (DiagnosticKind::Deprecated, Some(r)) => write!(
+                f,
+                "{}: deprecated key `{}`, use `{}` instead",
+                self.path, self.key, r
+            ),
+            (DiagnosticKind::Deprecated, None) => {
+                write!(f, "{}: deprecated key `{}` is ignored", self.path, self.key)
+            }
+            (DiagnosticKind::UnknownKey, _) => {
+                write!(f, "{}: unknown key `{}` is ignored", self.path, self.key)
+            }
+            (DiagnosticKind::Duplicate, _) => {
+                write!(f, "{}: `{}` is given more than once", self.path, self.key)
+            }
+        }
+    }
+}
+
+/// A legacy key rewritten by `compat::clap2_yaml` or `migrate::migrate`.
+#[cfg(any(feature = "yaml", feature = "migrate"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationNote {
+    /// Path of the key, like `subcommands.test.args.debug.hidden`.
+    pub path: String,
+    /// What was done to the key.
+    pub message: String,
+}
+
+#[cfg(any(feature = "yaml", feature = "migrate"))]
+impl std::fmt::Display for MigrationNote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
 /**
 Wrapper of [`Command`] to deserialize.
 ```
@@ -75,6 +315,7 @@ assert_eq!(app.get_about(), Some("test-clap-serde"));
 #[derive(Debug, Clone)]
 pub struct CommandWrap<'a> {
     app: Command<'a>,
+    metadata: metadata::CommandMetadata,
 }
 
 #[deprecated]
@@ -88,7 +329,10 @@ impl<'a> From<CommandWrap<'a>> for Command<'a> {
 
 impl<'a> From<Command<'a>> for CommandWrap<'a> {
     fn from(app: Command<'a>) -> Self {
-        CommandWrap { app }
+        CommandWrap {
+            app,
+            metadata: Default::default(),
+        }
     }
 }
 
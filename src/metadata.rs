@@ -0,0 +1,261 @@
+use crate::CommandWrap;
+use serde::{
+    de::{
+        value::{Error, MapDeserializer, SeqDeserializer},
+        DeserializeOwned, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+    },
+    forward_to_deserialize_any, Deserialize, Deserializer,
+};
+
+/// Value of an `x-` key, in any format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetaValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<MetaValue>),
+    Map(Vec<(String, MetaValue)>),
+}
+
+impl MetaValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            MetaValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            MetaValue::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            MetaValue::Int(i) => Some(*i as f64),
+            MetaValue::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            MetaValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Deserialize the value to `T`.
+    pub fn deserialize_into<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        T::deserialize(self.clone())
+    }
+}
+
+impl<'de> Deserializer<'de> for MetaValue {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            MetaValue::Null => visitor.visit_unit(),
+            MetaValue::Bool(b) => visitor.visit_bool(b),
+            MetaValue::Int(i) => visitor.visit_i64(i),
+            MetaValue::Float(f) => visitor.visit_f64(f),
+            MetaValue::String(s) => visitor.visit_string(s),
+            MetaValue::List(l) => visitor.visit_seq(SeqDeserializer::new(l.into_iter())),
+            MetaValue::Map(m) => visitor.visit_map(MapDeserializer::new(m.into_iter())),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            MetaValue::Null => visitor.visit_none(),
+            v => visitor.visit_some(v),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for MetaValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de> Deserialize<'de> for MetaValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(MetaValueVisitor)
+    }
+}
+
+struct MetaValueVisitor;
+
+impl<'de> Visitor<'de> for MetaValueVisitor {
+    type Value = MetaValue;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(MetaValue::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(MetaValue::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        MetaValue::deserialize(deserializer)
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(MetaValue::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(MetaValue::Int(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(i64::try_from(v).map_or(MetaValue::Float(v as f64), MetaValue::Int))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(MetaValue::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(MetaValue::String(v.to_owned()))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut list = Vec::new();
+        while let Some(v) = seq.next_element()? {
+            list.push(v);
+        }
+        Ok(MetaValue::List(list))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(MetaValue::Map(entries))
+    }
+}
+
+/// `x-` (or `x_`) keys of a command or an arg, without the prefix.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    entries: Vec<(String, MetaValue)>,
+}
+
+impl Metadata {
+    /// Value of the key `x-{name}`.
+    pub fn get(&self, name: &str) -> Option<&MetaValue> {
+        // The later one wins, as other keys do.
+        self.entries
+            .iter()
+            .rev()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v)
+    }
+
+    /// Names and values, in the order they are loaded.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &MetaValue)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn insert(&mut self, name: String, value: MetaValue) {
+        self.entries.push((name, value));
+    }
+}
+
+/// [`Metadata`] of a command tree.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CommandMetadata {
+    pub(crate) metadata: Metadata,
+    pub(crate) args: Vec<(String, Metadata)>,
+    pub(crate) subcommands: Vec<(String, CommandMetadata)>,
+}
+
+impl CommandMetadata {
+    /// The command found by `path`, names of the subcommands separated by spaces.
+    pub(crate) fn find(&self, path: &str) -> Option<&CommandMetadata> {
+        path.split_whitespace().try_fold(self, |c, name| {
+            c.subcommands
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, sub)| sub)
+        })
+    }
+
+    /// Metadata of the arg `arg` of the command found by `command`.
+    pub(crate) fn arg(&self, command: &str, arg: &str) -> Option<&Metadata> {
+        self.find(command)?
+            .args
+            .iter()
+            .find(|(id, _)| id == arg)
+            .map(|(_, m)| m)
+    }
+}
+
+impl<'a> CommandWrap<'a> {
+    /**
+    `x-` keys of the arg `arg` of the command at `command`.
+
+    `command` is the names of the subcommands from the root separated by spaces, or empty for the root.
+    ```
+    const CLAP_TOML: &'static str = r#"
+    name = "app"
+    [subcommands.build]
+    args = { jobs = { short = "j", x-owner = "build-team" } }
+    "#;
+    let wrap: clap_serde::CommandWrap = toml::from_str(CLAP_TOML).expect("parse failed");
+    let jobs = wrap.metadata("build", "jobs").expect("no metadata");
+    assert_eq!(jobs.get("owner").and_then(|v| v.as_str()), Some("build-team"));
+    ```
+    */
+    pub fn metadata(&self, command: &str, arg: &str) -> Option<&Metadata> {
+        self.metadata.arg(command, arg)
+    }
+
+    /// `x-` keys of the command at `command`, in the same way as [`CommandWrap::metadata`].
+    pub fn command_metadata(&self, command: &str) -> Option<&Metadata> {
+        self.metadata.find(command).map(|c| &c.metadata)
+    }
+}
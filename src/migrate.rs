@@ -0,0 +1,561 @@
+/*!
+Rewrite configs to the current keys.
+
+[`migrate`] reads a config and rewrites the keys which are loaded only with `allow-deprecated`
+(`name` of args and groups, `help_heading` of commands, `use_delimiter` and `require_delimiter` of args),
+the keys of clap 2 which fail to load (like `hidden`, `multiple`, `from_usage` and `settings` of args),
+as `compat::clap2_yaml` does,
+and `args` and `subcommands` written as maps, which is the schema of 0.3, to lists.
+
+Comments and layout are kept for toml.
+Yaml and json are written again from the parsed value, so comments of yaml are dropped.
+```
+const CLI_JSON: &str = r#"{
+    "name": "myapp",
+    "help_heading": "OPTIONS",
+    "args": {
+        "verbose": { "short": "v", "use_delimiter": true }
+    }
+}"#;
+let migrated = clap_serde::migrate::migrate(CLI_JSON, clap_serde::migrate::Format::Json)
+    .expect("not a json");
+for note in migrated.notes() {
+    println!("{}", note);
+}
+let app = clap_serde::load(&mut serde_json::Deserializer::from_str(migrated.text()))
+    .expect("parse failed");
+assert_eq!(app.get_next_help_heading(), Some("OPTIONS"));
+```
+*/
+
+use crate::{
+    de::{same_case_as, snake_case},
+    migration::{arg_key, command_key, group_key, join, ArgShape, Migration, Rewrite},
+    MigrationNote,
+};
+use std::fmt::Display;
+use toml_edit::TableLike;
+
+pub use crate::source::Format;
+
+/// Error while reading or writing a config in [`migrate`].
+#[derive(Debug)]
+pub enum Error {
+    Toml(toml_edit::TomlError),
+    Yaml(serde_yaml::Error),
+    Json(serde_json::Error),
+    /// The format is not supported, which is only loaded.
+    Unsupported(Format),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Toml(e) => e.fmt(f),
+            Error::Yaml(e) => e.fmt(f),
+            Error::Json(e) => e.fmt(f),
+            Error::Unsupported(format) => write!(f, "{:?} configs can't be migrated", format),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Toml(e) => Some(e),
+            Error::Yaml(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Unsupported(_) => None,
+        }
+    }
+}
+
+impl From<toml_edit::TomlError> for Error {
+    fn from(e: toml_edit::TomlError) -> Self {
+        Error::Toml(e)
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(e: serde_yaml::Error) -> Self {
+        Error::Yaml(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+/// Config rewritten to the current keys by [`migrate`].
+#[derive(Debug, Clone)]
+pub struct Migrated {
+    text: String,
+    notes: Vec<MigrationNote>,
+}
+
+impl Migrated {
+    /// The rewritten config.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Notes of the rewritten keys, in the order they appear.
+    pub fn notes(&self) -> &[MigrationNote] {
+        &self.notes
+    }
+
+    /// Whether any key is rewritten.
+    pub fn is_changed(&self) -> bool {
+        !self.notes.is_empty()
+    }
+}
+
+impl From<Migrated> for String {
+    fn from(m: Migrated) -> Self {
+        m.text
+    }
+}
+
+/// Rewrite the deprecated keys in the config `src` written in `format`.
+///
+/// If no key is rewritten, the text is kept as is.
+pub fn migrate(src: &str, format: Format) -> Result<Migrated, Error> {
+    let mut rewriter = Rewriter {
+        migration: Migration::new(),
+    };
+    let text = match format {
+        Format::Toml => {
+            let mut doc: toml_edit::DocumentMut = src.parse()?;
+            rewriter.command("", doc.as_table_mut() as &mut dyn TableLike);
+            doc.to_string()
+        }
+        Format::Yaml => {
+            let mut value: serde_yaml::Value = serde_yaml::from_str(src)?;
+            if let Some(map) = value.as_mapping_mut() {
+                rewriter.command("", map);
+            }
+            serde_yaml::to_string(&value)?
+        }
+        Format::Json => {
+            let mut value: serde_json::Value = serde_json::from_str(src)?;
+            if let Some(map) = value.as_object_mut() {
+                rewriter.command("", map);
+            }
+            serde_json::to_string_pretty(&value)? + "\n"
+        }
+        #[allow(unreachable_patterns)]
+        _ => return Err(Error::Unsupported(format)),
+    };
+    let notes = rewriter.migration.notes;
+    let text = if notes.is_empty() {
+        src.to_owned()
+    } else {
+        text
+    };
+    Ok(Migrated { text, notes })
+}
+
+/// Map in a config of any format.
+trait Map {
+    /// Keys which are strings, in order.
+    fn keys(&self) -> Vec<String>;
+
+    /// Rename the key `from` to `to`, keeping its position.
+    fn rename(&mut self, from: &str, to: &str);
+
+    /// Maps in the value of `key`, which is the value itself or the elements of a list.
+    fn children(&mut self, key: &str) -> Vec<&mut Self>;
+
+    /// Turn the map at `key` to a list of maps with single entry.
+    ///
+    /// Returns `false` if it is not a map, or the format can load the map as a list.
+    fn map_to_list(&mut self, key: &str) -> bool;
+
+    /// The value of `key` if it is a bool.
+    fn bool(&self, key: &str) -> Option<bool>;
+
+    /// The value of `key` if it is a string, or the strings in it if it is a list.
+    fn strings(&self, key: &str) -> Vec<String>;
+
+    /// Set `key` to the value of the key `from`.
+    fn copy(&mut self, from: &str, key: &str);
+
+    /// Set `key` to `value`.
+    fn set_bool(&mut self, key: &str, value: bool);
+
+    fn remove(&mut self, key: &str);
+}
+
+/// Rewriter of the maps of any format.
+struct Rewriter {
+    migration: Migration,
+}
+
+impl Rewriter {
+    fn rename<M: Map + ?Sized>(&mut self, map: &mut M, path: &str, from: &str, to: &str) {
+        let to = same_case_as(from, to);
+        if map.keys().contains(&to) {
+            self.migration
+                .note(path, format!("`{}` is kept, as `{}` is also set", from, to));
+        } else {
+            map.rename(from, &to);
+            self.migration
+                .note(path, format!("`{}` is renamed to `{}`", from, to));
+        }
+    }
+
+    fn command<M: Map + ?Sized>(&mut self, path: &str, map: &mut M) -> Option<String> {
+        for key in map.keys() {
+            let p = join(path, &key);
+            match snake_case(&key).as_str() {
+                k @ ("args" | "args_map" | "subcommands" | "subcommands_map") => {
+                    if !k.ends_with("_map") && map.map_to_list(&key) {
+                        self.migration
+                            .note(&p, format!("`{}` is changed from a map to a list", key));
+                    }
+                    let f: fn(&mut Self, &str, &mut M) -> Option<String> = if k.starts_with("args")
+                    {
+                        Self::arg
+                    } else {
+                        Self::command
+                    };
+                    for named in map.children(&key) {
+                        self.named(&p, named, f);
+                    }
+                }
+                "groups" => {
+                    for named in map.children(&key) {
+                        self.named(&p, named, Self::group);
+                    }
+                }
+                k => match command_key(k) {
+                    Some(Rewrite::Rename(to)) => self.rename(map, &p, &key, to),
+                    Some(rewrite) => {
+                        self.migration.rewrite(&p, &key, rewrite);
+                        map.remove(&key);
+                    }
+                    None => {}
+                },
+            }
+        }
+        None
+    }
+
+    /// Rewrite the values of a map keyed by names, like an element of `args`.
+    ///
+    /// `f` returns the new name if it is changed.
+    fn named<M: Map + ?Sized>(
+        &mut self,
+        path: &str,
+        map: &mut M,
+        f: fn(&mut Self, &str, &mut M) -> Option<String>,
+    ) {
+        for name in map.keys() {
+            let p = join(path, &name);
+            let mut new_name = None;
+            for value in map.children(&name) {
+                new_name = new_name.or(f(self, &p, value));
+            }
+            if let Some(new_name) = new_name {
+                map.rename(&name, &new_name);
+            }
+        }
+    }
+
+    /// Rewrite an arg, which is renamed to `id: usage` if it has `from_usage`.
+    fn arg<M: Map + ?Sized>(&mut self, path: &str, map: &mut M) -> Option<String> {
+        let keys = map.keys();
+        let snake_keys = keys.iter().map(|k| snake_case(k)).collect::<Vec<_>>();
+        let get = |key: &str| keys.iter().zip(&snake_keys).find(|(_, k)| *k == key);
+        let settings = ["set", "setting", "settings"]
+            .iter()
+            .filter_map(|k| get(k))
+            .flat_map(|(key, _)| map.strings(key))
+            .collect::<Vec<_>>();
+        let usage = get("from_usage").and_then(|(key, _)| map.strings(key).pop());
+        let shape = ArgShape::of(
+            &snake_keys,
+            get("takes_value").and_then(|(key, _)| map.bool(key)),
+            &settings,
+            usage.as_deref(),
+        );
+        let mut new_name = None;
+        for (key, snake) in keys.iter().zip(&snake_keys) {
+            let p = join(path, key);
+            let rewrite = match arg_key(snake, shape) {
+                Some(Rewrite::Rename(to)) => {
+                    self.rename(map, &p, key, to);
+                    continue;
+                }
+                Some(rewrite) => rewrite,
+                None => continue,
+            };
+            self.migration.rewrite(&p, key, rewrite);
+            match rewrite {
+                Rewrite::Rename(_) | Rewrite::Remove(_) => {}
+                Rewrite::Split(to) => {
+                    for to in to {
+                        map.copy(key, &same_case_as(key, to));
+                    }
+                }
+                Rewrite::Negate(to) => match map.bool(key) {
+                    Some(value) => map.set_bool(&same_case_as(key, to), !value),
+                    None => map.copy(key, &same_case_as(key, to)),
+                },
+                Rewrite::Settings => {
+                    for name in map.strings(key) {
+                        for to in self.migration.arg_setting(&p, &name, shape) {
+                            map.set_bool(&same_case_as(key, &to), true);
+                        }
+                    }
+                }
+                Rewrite::Usage => {
+                    let id = path.rsplit('.').next().unwrap_or_default();
+                    new_name = usage.as_ref().map(|usage| format!("{}: {}", id, usage));
+                }
+            }
+            map.remove(key);
+        }
+        new_name
+    }
+
+    fn group<M: Map + ?Sized>(&mut self, path: &str, map: &mut M) -> Option<String> {
+        for key in map.keys() {
+            if let Some(Rewrite::Rename(to)) = group_key(&snake_case(&key)) {
+                self.rename(map, &join(path, &key), &key, to);
+            }
+        }
+        None
+    }
+}
+
+impl Map for dyn TableLike {
+    fn keys(&self) -> Vec<String> {
+        self.iter().map(|(k, _)| k.to_owned()).collect()
+    }
+
+    fn rename(&mut self, from: &str, to: &str) {
+        // Tables have no way to rename a key, so insert all the entries again.
+        let keys = self
+            .iter()
+            .filter_map(|(k, _)| self.key(k).cloned())
+            .collect::<Vec<_>>();
+        for key in keys {
+            let item = match self.remove(key.get()) {
+                Some(item) => item,
+                None => continue,
+            };
+            let name = if key.get() == from { to } else { key.get() };
+            self.insert(name, item);
+            if let Some(mut new_key) = self.key_mut(name) {
+                *new_key.leaf_decor_mut() = key.leaf_decor().clone();
+                *new_key.dotted_decor_mut() = key.dotted_decor().clone();
+            }
+        }
+    }
+
+    fn children(&mut self, key: &str) -> Vec<&mut Self> {
+        use toml_edit::{Item, Value};
+        match self.get_mut(key) {
+            Some(Item::Table(t)) => vec![t],
+            Some(Item::Value(Value::InlineTable(t))) => vec![t],
+            Some(Item::ArrayOfTables(a)) => a.iter_mut().map(|t| t as &mut Self).collect(),
+            Some(Item::Value(Value::Array(a))) => a
+                .iter_mut()
+                .filter_map(Value::as_inline_table_mut)
+                .map(|t| t as &mut Self)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn map_to_list(&mut self, _key: &str) -> bool {
+        // Tables are loaded as lists, so they are kept.
+        false
+    }
+
+    fn bool(&self, key: &str) -> Option<bool> {
+        self.get(key)?.as_bool()
+    }
+
+    fn strings(&self, key: &str) -> Vec<String> {
+        match self.get(key) {
+            Some(item) if item.is_array() => item
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|v| v.as_str().map(str::to_owned))
+                .collect(),
+            Some(item) => item.as_str().map(str::to_owned).into_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn copy(&mut self, from: &str, key: &str) {
+        if let Some(item) = self.get(from).cloned() {
+            self.insert(key, item);
+        }
+    }
+
+    fn set_bool(&mut self, key: &str, value: bool) {
+        self.insert(key, toml_edit::value(value));
+    }
+
+    fn remove(&mut self, key: &str) {
+        TableLike::remove(self, key);
+    }
+}
+
+impl Map for serde_json::Map<String, serde_json::Value> {
+    fn keys(&self) -> Vec<String> {
+        self.keys().cloned().collect()
+    }
+
+    fn rename(&mut self, from: &str, to: &str) {
+        *self = std::mem::take(self)
+            .into_iter()
+            .map(|(k, v)| {
+                if k == from {
+                    (to.to_owned(), v)
+                } else {
+                    (k, v)
+                }
+            })
+            .collect();
+    }
+
+    fn children(&mut self, key: &str) -> Vec<&mut Self> {
+        use serde_json::Value;
+        match self.get_mut(key) {
+            Some(Value::Object(map)) => vec![map],
+            Some(Value::Array(a)) => a.iter_mut().filter_map(Value::as_object_mut).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn map_to_list(&mut self, key: &str) -> bool {
+        use serde_json::Value;
+        let value = match self.get_mut(key) {
+            Some(value @ Value::Object(_)) => value,
+            _ => return false,
+        };
+        let list = match value.take() {
+            Value::Object(map) => map
+                .into_iter()
+                .map(|e| Value::Object(std::iter::once(e).collect()))
+                .collect(),
+            _ => unreachable!("checked to be an object"),
+        };
+        *value = Value::Array(list);
+        true
+    }
+
+    fn bool(&self, key: &str) -> Option<bool> {
+        self.get(key)?.as_bool()
+    }
+
+    fn strings(&self, key: &str) -> Vec<String> {
+        use serde_json::Value;
+        match self.get(key) {
+            Some(Value::String(s)) => vec![s.clone()],
+            Some(Value::Array(a)) => a
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_owned))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn copy(&mut self, from: &str, key: &str) {
+        if let Some(value) = self.get(from).cloned() {
+            self.insert(key.to_owned(), value);
+        }
+    }
+
+    fn set_bool(&mut self, key: &str, value: bool) {
+        self.insert(key.to_owned(), value.into());
+    }
+
+    fn remove(&mut self, key: &str) {
+        serde_json::Map::remove(self, key);
+    }
+}
+
+impl Map for serde_yaml::Mapping {
+    fn keys(&self) -> Vec<String> {
+        self.keys()
+            .filter_map(|k| k.as_str().map(str::to_owned))
+            .collect()
+    }
+
+    fn rename(&mut self, from: &str, to: &str) {
+        *self = std::mem::take(self)
+            .into_iter()
+            .map(|(k, v)| {
+                if k.as_str() == Some(from) {
+                    (to.into(), v)
+                } else {
+                    (k, v)
+                }
+            })
+            .collect();
+    }
+
+    fn children(&mut self, key: &str) -> Vec<&mut Self> {
+        use serde_yaml::Value;
+        match self.get_mut(key) {
+            Some(Value::Mapping(map)) => vec![map],
+            Some(Value::Sequence(s)) => s.iter_mut().filter_map(Value::as_mapping_mut).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn map_to_list(&mut self, key: &str) -> bool {
+        use serde_yaml::{Mapping, Value};
+        let value = match self.get_mut(key) {
+            Some(value @ Value::Mapping(_)) => value,
+            _ => return false,
+        };
+        let list = match std::mem::take(value) {
+            Value::Mapping(map) => map
+                .into_iter()
+                .map(|e| Value::Mapping(std::iter::once(e).collect::<Mapping>()))
+                .collect(),
+            _ => unreachable!("checked to be a mapping"),
+        };
+        *value = Value::Sequence(list);
+        true
+    }
+
+    fn bool(&self, key: &str) -> Option<bool> {
+        self.get(key)?.as_bool()
+    }
+
+    fn strings(&self, key: &str) -> Vec<String> {
+        use serde_yaml::Value;
+        match self.get(key) {
+            Some(Value::String(s)) => vec![s.clone()],
+            Some(Value::Sequence(s)) => s
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_owned))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn copy(&mut self, from: &str, key: &str) {
+        if let Some(value) = self.get(from).cloned() {
+            self.insert(key.into(), value);
+        }
+    }
+
+    fn set_bool(&mut self, key: &str, value: bool) {
+        self.insert(key.into(), value.into());
+    }
+
+    fn remove(&mut self, key: &str) {
+        serde_yaml::Mapping::remove(self, key);
+    }
+}
@@ -0,0 +1,200 @@
+//! Legacy keys rewritten by both `compat::clap2_yaml` and `migrate::migrate`.
+
+use crate::MigrationNote;
+
+/// Notes of the keys rewritten so far.
+pub(crate) struct Migration {
+    pub(crate) notes: Vec<MigrationNote>,
+}
+
+impl Migration {
+    pub(crate) fn new() -> Self {
+        Self { notes: Vec::new() }
+    }
+
+    pub(crate) fn note(&mut self, path: &str, message: impl Into<String>) {
+        self.notes.push(MigrationNote {
+            path: path.to_owned(),
+            message: message.into(),
+        });
+    }
+
+    /// Note the legacy `key` at `path` rewritten as `rewrite`.
+    ///
+    /// [`Rewrite::Settings`] is noted by each setting, so nothing is noted here.
+    pub(crate) fn rewrite(&mut self, path: &str, key: &str, rewrite: Rewrite) {
+        let message = match rewrite {
+            Rewrite::Rename(to) => format!("`{}` is renamed to `{}`", key, to),
+            Rewrite::Split(to) => return self.replace(path, key, to),
+            Rewrite::Negate(to) => format!("`{}` is replaced with `{}`", key, to),
+            Rewrite::Remove(instead) => format!("`{}` is removed, {}", key, instead),
+            Rewrite::Settings => return,
+            Rewrite::Usage => format!("`{}` is replaced with a usage string key", key),
+        };
+        self.note(path, message);
+    }
+
+    /// Note the setting or key `name` replaced with `keys`.
+    pub(crate) fn replace(&mut self, path: &str, name: &str, keys: &[&str]) {
+        self.note(
+            path,
+            format!("`{}` is replaced with `{}`", name, keys.join("` and `")),
+        );
+    }
+
+    /// Note the arg setting `name` and return the keys set to `true` instead of it.
+    pub(crate) fn arg_setting(&mut self, path: &str, name: &str, shape: ArgShape) -> Vec<String> {
+        let key = crate::de::snake_case(name);
+        let to: &[&str] = match key.as_str() {
+            "multiple" => multiple(shape),
+            "empty_values" => {
+                self.note(path, format!("`{}` is removed, as it is the default", name));
+                return Vec::new();
+            }
+            "allow_leading_hyphen" => &["allow_hyphen_values"],
+            "case_insensitive" => &["ignore_case"],
+            "hidden" => &["hide"],
+            "hidden_long_help" => &["hide_long_help"],
+            "hidden_short_help" => &["hide_short_help"],
+            "require_delimiter" => &["require_value_delimiter"],
+            _ => {
+                if name != key {
+                    self.note(path, format!("`{}` is replaced with `{}`", name, key));
+                }
+                return vec![key];
+            }
+        };
+        self.replace(path, name, to);
+        to.iter().map(|k| k.to_string()).collect()
+    }
+}
+
+/// How a legacy key is rewritten.
+#[derive(Clone, Copy)]
+pub(crate) enum Rewrite {
+    /// Renamed to the key, keeping the value.
+    Rename(&'static str),
+    /// Replaced with the keys, all with the value.
+    Split(&'static [&'static str]),
+    /// Replaced with the key of the negated bool.
+    Negate(&'static str),
+    /// Removed, with what to do instead.
+    Remove(&'static str),
+    /// Replaced with the keys of the settings in the value, set to `true`.
+    Settings,
+    /// Replaced with the usage string as the key of the arg.
+    Usage,
+}
+
+/// Rewrite of the snake case legacy `key` of a command.
+pub(crate) fn command_key(key: &str) -> Option<Rewrite> {
+    Some(match key {
+        "help_message" | "version_message" | "help_short" | "version_short" => {
+            Rewrite::Remove("modify the generated arg instead")
+        }
+        "help" => Rewrite::Rename("override_help"),
+        "help_heading" => Rewrite::Rename("next_help_heading"),
+        "set_term_width" => Rewrite::Rename("term_width"),
+        "template" => Rewrite::Rename("help_template"),
+        "usage" => Rewrite::Rename("override_usage"),
+        _ => return None,
+    })
+}
+
+/// Rewrite of the snake case legacy `key` of an arg of `shape`.
+pub(crate) fn arg_key(key: &str, shape: ArgShape) -> Option<Rewrite> {
+    Some(match key {
+        "from_usage" => Rewrite::Usage,
+        "set" | "setting" | "settings" => Rewrite::Settings,
+        "multiple" => Rewrite::Split(multiple(shape)),
+        "empty_values" => Rewrite::Negate("forbid_empty_values"),
+        "validator_regex" => Rewrite::Remove("use `value_parser` instead"),
+        "case_insensitive" => Rewrite::Rename("ignore_case"),
+        "hidden" => Rewrite::Rename("hide"),
+        "hidden_long_help" => Rewrite::Rename("hide_long_help"),
+        "hidden_short_help" => Rewrite::Rename("hide_short_help"),
+        "name" | "with_name" => Rewrite::Rename("id"),
+        "require_delimiter" => Rewrite::Rename("require_value_delimiter"),
+        "required_if" => Rewrite::Rename("required_if_eq"),
+        "required_ifs" => Rewrite::Rename("required_if_eq_any"),
+        "required_unless" => Rewrite::Rename("required_unless_present"),
+        "required_unless_all" => Rewrite::Rename("required_unless_present_all"),
+        "required_unless_one" => Rewrite::Rename("required_unless_present_any"),
+        "use_delimiter" => Rewrite::Rename("use_value_delimiter"),
+        _ => return None,
+    })
+}
+
+/// Rewrite of the snake case legacy `key` of a group.
+#[cfg(feature = "migrate")]
+pub(crate) fn group_key(key: &str) -> Option<Rewrite> {
+    match key {
+        "name" => Some(Rewrite::Rename("id")),
+        _ => None,
+    }
+}
+
+/// `multiple` of clap 2 is split into `multiple_occurrences` and `multiple_values`.
+fn multiple(shape: ArgShape) -> &'static [&'static str] {
+    match shape {
+        ArgShape::Flag => &["multiple_occurrences"],
+        ArgShape::Option => &["multiple_occurrences", "multiple_values"],
+        ArgShape::Positional => &["multiple_values"],
+    }
+}
+
+/// How an arg of clap 2 takes values, which decides the keys `multiple` is split into.
+#[derive(Clone, Copy)]
+pub(crate) enum ArgShape {
+    Flag,
+    Option,
+    Positional,
+}
+
+impl ArgShape {
+    /// Shape of an arg with the snake case `keys`, and the value of `takes_value`,
+    /// the names of the settings and the usage string, if any.
+    pub(crate) fn of(
+        keys: &[String],
+        takes_value: Option<bool>,
+        settings: &[String],
+        usage: Option<&str>,
+    ) -> Self {
+        let has = |key: &str| keys.iter().any(|k| k == key);
+        let named =
+            has("short") || has("long") || usage.is_some_and(|u| u.trim_start().starts_with('-'));
+        if has("index") || !named {
+            return Self::Positional;
+        }
+        let takes_value = takes_value == Some(true)
+            || [
+                "value_name",
+                "value_names",
+                "number_of_values",
+                "min_values",
+                "max_values",
+                "possible_values",
+                "default_value",
+            ]
+            .iter()
+            .any(|k| has(k))
+            || settings
+                .iter()
+                .any(|s| crate::de::snake_case(s) == "takes_value")
+            || usage.is_some_and(|u| u.contains(['<', '[']));
+        if takes_value {
+            Self::Option
+        } else {
+            Self::Flag
+        }
+    }
+}
+
+/// Path of `key` in the map at `path`.
+pub(crate) fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
@@ -0,0 +1,296 @@
+use crate::{de, CommandWrap, Diagnostic, MetaValue, StringStore};
+use clap::{Arg, ArgGroup, Command};
+use serde::Deserializer;
+use std::sync::Arc;
+
+/// How to treat the keys which are not known.
+///
+/// Keys starting with `x-` or `x_` are not unknown keys, and kept as [`Metadata`](crate::Metadata).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownKeys {
+    /// Fail to load.
+    #[default]
+    Deny,
+    /// Skip the key, reporting a [`Diagnostic`].
+    Warn,
+    /// Skip the key.
+    Ignore,
+}
+
+/// How to treat keys, arg ids and subcommand names given more than once in the same map.
+///
+/// The later key wins (or appends, for list keys like `alias`), and so do the later arg and subcommand.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Duplicates {
+    /// Load them.
+    #[default]
+    Allow,
+    /// Load them, reporting a [`Diagnostic`].
+    Warn,
+    /// Fail to load.
+    Deny,
+}
+
+/// Limits of the size of a config, all unlimited by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Limits {
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) max_args: Option<usize>,
+    pub(crate) max_nodes: Option<usize>,
+    pub(crate) max_string_len: Option<usize>,
+}
+
+/**
+Options to load [`Command`](clap::Command).
+```
+const CLAP_TOML: &'static str = r#"
+name = "app_clap_serde"
+owner = "someone"
+"#;
+let (app, diagnostics) = clap_serde::LoadOptions::new()
+    .unknown_keys(clap_serde::UnknownKeys::Warn)
+    .load(&mut toml::Deserializer::new(CLAP_TOML))
+    .expect("parse failed");
+assert_eq!(app.get_name(), "app_clap_serde");
+assert_eq!(diagnostics[0].key, "owner");
+```
+*/
+#[derive(Clone, Default)]
+pub struct LoadOptions {
+    pub(crate) unknown_keys: UnknownKeys,
+    pub(crate) duplicates: Duplicates,
+    pub(crate) limits: Limits,
+    pub(crate) handlers: Vec<Arc<dyn KeyHandler>>,
+}
+
+impl std::fmt::Debug for LoadOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoadOptions")
+            .field("unknown_keys", &self.unknown_keys)
+            .field("duplicates", &self.duplicates)
+            .field("limits", &self.limits)
+            .field("handlers", &self.handlers.len())
+            .finish()
+    }
+}
+
+impl LoadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How to treat the keys which are not known. [`UnknownKeys::Deny`] by default.
+    pub fn unknown_keys(mut self, unknown_keys: UnknownKeys) -> Self {
+        self.unknown_keys = unknown_keys;
+        self
+    }
+
+    /// How to treat keys, arg ids and subcommand names given more than once. [`Duplicates::Allow`] by default.
+    pub fn duplicates(mut self, duplicates: Duplicates) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    /// Fail if subcommands are nested deeper than `depth`. `0` allows no subcommands.
+    ///
+    /// The lists and maps in the values of unknown keys, like `x-` keys, are limited to the same depth.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.limits.max_depth = Some(depth);
+        self
+    }
+
+    /// Fail if a command has more than `args` args.
+    pub fn max_args(mut self, args: usize) -> Self {
+        self.limits.max_args = Some(args);
+        self
+    }
+
+    /// Fail if the config has more than `nodes` subcommands, args and groups in total.
+    ///
+    /// The items of the lists and maps in the values of unknown keys count as well.
+    pub fn max_nodes(mut self, nodes: usize) -> Self {
+        self.limits.max_nodes = Some(nodes);
+        self
+    }
+
+    /// Fail if a key, a name or a string value is longer than `len` bytes.
+    pub fn max_string_len(mut self, len: usize) -> Self {
+        self.limits.max_string_len = Some(len);
+        self
+    }
+
+    /// Add a handler of the keys which are not known.
+    ///
+    /// Handlers are consulted in the order they are added, before the `x-` keys are kept as metadata
+    /// and the [`UnknownKeys`] option is applied.
+    pub fn key_handler(mut self, handler: impl KeyHandler + 'static) -> Self {
+        self.handlers.push(Arc::new(handler));
+        self
+    }
+
+    /// Deserialize [`CommandWrap`] from [`Deserializer`], collecting [`Diagnostic`]s.
+    ///
+    /// Deprecated keys are reported in the same way as [`load_with_diagnostics`](crate::load_with_diagnostics).
+    /// A value which is not in the text, like an integer given to `default_value`, fails without a [`StringStore`],
+    /// so use [`LoadOptions::load_in`] for it.
+    pub fn load<'de, D>(&self, de: D) -> Result<(CommandWrap<'de>, Vec<Diagnostic>), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Self::load_ctx(de, de::Context::with_options(self.clone()))
+    }
+
+    /// Deserialize [`CommandWrap`] as [`LoadOptions::load`], keeping the strings made while loading in `strings`.
+    pub fn load_in<'de, D>(
+        &self,
+        de: D,
+        strings: &'de StringStore,
+    ) -> Result<(CommandWrap<'de>, Vec<Diagnostic>), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Self::load_ctx(
+            de,
+            de::Context::with_options(self.clone()).with_strings(strings),
+        )
+    }
+
+    fn load_ctx<'de, D>(
+        de: D,
+        mut ctx: de::Context<'de>,
+    ) -> Result<(CommandWrap<'de>, Vec<Diagnostic>), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let app = de::load_root(de, &mut ctx)?;
+        Ok((app, ctx.take_diagnostics()))
+    }
+}
+
+/**
+Handler of the keys which are not known, to add keys to commands, args and groups.
+
+Each method returns `Ok(true)` if it handled the key, and `Ok(false)` to leave it to the next handler.
+An error fails loading.
+The commands of clap 4 are given to the `clap4_` methods, with the `clap4` feature.
+
+The value is read into a [`MetaValue`] before the handlers are consulted, as the deserializer of the config
+can be read only once, and within the limits of [`LoadOptions`].
+It can be deserialized to any type with [`MetaValue::deserialize_into`], but only from what `MetaValue` keeps:
+integers over `i64::MAX` are floats, and the values a format has beyond the serde data model,
+like the dates of toml, are read as the format gives them to serde.
+The strings of the value are owned by it, so a string set to the command is kept in `strings`,
+the [`StringStore`] of the loader, which is `None` for the loaders without one like [`LoadOptions::load`].
+```
+use clap::Arg;
+use clap_serde::{KeyHandler, LoadOptions, MetaValue, StringStore};
+
+struct RequiresFeature;
+
+impl KeyHandler for RequiresFeature {
+    fn arg_key<'a>(
+        &self,
+        key: &str,
+        value: &MetaValue,
+        arg: &mut Arg<'a>,
+        strings: Option<&'a StringStore>,
+    ) -> Result<bool, String> {
+        if key != "requires_feature" {
+            return Ok(false);
+        }
+        let feature: String = value.deserialize_into().map_err(|e| e.to_string())?;
+        let help = format!("Needs the {} feature", feature);
+        let help = strings.ok_or("requires_feature needs a StringStore")?.add(help);
+        *arg = std::mem::take(arg).help(help).hide(feature != "nightly");
+        Ok(true)
+    }
+}
+
+const CLAP_TOML: &'static str = r#"
+name = "app_clap_serde"
+[args]
+turbo = { long = "turbo", requires_feature = "unstable" }
+"#;
+let strings = StringStore::new();
+let (app, _) = LoadOptions::new()
+    .key_handler(RequiresFeature)
+    .load_in(&mut toml::Deserializer::new(CLAP_TOML), &strings)
+    .expect("parse failed");
+let turbo = app.get_arguments().find(|a| a.get_id() == "turbo").unwrap();
+assert!(turbo.is_hide_set());
+assert_eq!(turbo.get_help(), Some("Needs the unstable feature"));
+```
+*/
+pub trait KeyHandler: Send + Sync {
+    /// Handle `key` of a command.
+    fn command_key<'a>(
+        &self,
+        key: &str,
+        value: &MetaValue,
+        command: &mut Command<'a>,
+        strings: Option<&'a StringStore>,
+    ) -> Result<bool, String> {
+        let _ = (key, value, command, strings);
+        Ok(false)
+    }
+
+    /// Handle `key` of an arg.
+    fn arg_key<'a>(
+        &self,
+        key: &str,
+        value: &MetaValue,
+        arg: &mut Arg<'a>,
+        strings: Option<&'a StringStore>,
+    ) -> Result<bool, String> {
+        let _ = (key, value, arg, strings);
+        Ok(false)
+    }
+
+    /// Handle `key` of a group.
+    fn group_key<'a>(
+        &self,
+        key: &str,
+        value: &MetaValue,
+        group: &mut ArgGroup<'a>,
+        strings: Option<&'a StringStore>,
+    ) -> Result<bool, String> {
+        let _ = (key, value, group, strings);
+        Ok(false)
+    }
+
+    /// Handle `key` of a command of clap 4, loaded by [`clap4::load_with_options`](crate::clap4::load_with_options).
+    #[cfg(feature = "clap4")]
+    fn clap4_command_key(
+        &self,
+        key: &str,
+        value: &MetaValue,
+        command: &mut clap4::Command,
+    ) -> Result<bool, String> {
+        let _ = (key, value, command);
+        Ok(false)
+    }
+
+    /// Handle `key` of an arg of clap 4.
+    #[cfg(feature = "clap4")]
+    fn clap4_arg_key(
+        &self,
+        key: &str,
+        value: &MetaValue,
+        arg: &mut clap4::Arg,
+    ) -> Result<bool, String> {
+        let _ = (key, value, arg);
+        Ok(false)
+    }
+
+    /// Handle `key` of a group of clap 4.
+    #[cfg(feature = "clap4")]
+    fn clap4_group_key(
+        &self,
+        key: &str,
+        value: &MetaValue,
+        group: &mut clap4::ArgGroup,
+    ) -> Result<bool, String> {
+        let _ = (key, value, group);
+        Ok(false)
+    }
+}
@@ -0,0 +1,580 @@
+use crate::{
+    codegen::{recorded, Builder},
+    de::Context,
+    Diagnostic, LoadOptions, StringStore,
+};
+use clap::{ArgMatches, Command};
+use std::path::{Path, PathBuf};
+
+/// Format of a config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Yaml,
+    Json,
+    #[cfg(feature = "ron")]
+    Ron,
+    #[cfg(feature = "json5")]
+    Json5,
+    #[cfg(feature = "kdl")]
+    Kdl,
+}
+
+impl Format {
+    /// Format of a file by its extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "json" => Some(Self::Json),
+            #[cfg(feature = "ron")]
+            "ron" => Some(Self::Ron),
+            #[cfg(feature = "json5")]
+            "json5" => Some(Self::Json5),
+            #[cfg(feature = "kdl")]
+            "kdl" => Some(Self::Kdl),
+            _ => None,
+        }
+    }
+
+    /**
+    Guess the format from the first line which is not blank nor a comment.
+
+    A line starting with `{` is json, and a line starting with `[` or with `=` before any `:` is toml.
+    The others are yaml.
+    ```
+    use clap_serde::Format;
+    assert_eq!(Format::sniff("# cli\nname = \"app\""), Format::Toml);
+    assert_eq!(Format::sniff("name: app"), Format::Yaml);
+    assert_eq!(Format::sniff(" {\"name\": \"app\"}"), Format::Json);
+    ```
+    */
+    pub fn sniff(src: &str) -> Self {
+        let line = src
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with("//"))
+            .unwrap_or_default();
+        if line.starts_with('{') {
+            Self::Json
+        } else if line.starts_with('[') {
+            Self::Toml
+        } else {
+            match (line.find('='), line.find(':')) {
+                (Some(eq), Some(colon)) if eq < colon => Self::Toml,
+                (Some(_), None) => Self::Toml,
+                _ => Self::Yaml,
+            }
+        }
+    }
+
+    fn feature(self) -> &'static str {
+        match self {
+            Self::Toml => "toml",
+            Self::Yaml => "serde_yaml",
+            Self::Json => "serde_json",
+            #[cfg(feature = "ron")]
+            Self::Ron => "ron",
+            #[cfg(feature = "json5")]
+            Self::Json5 => "json5",
+            #[cfg(feature = "kdl")]
+            Self::Kdl => "kdl",
+        }
+    }
+}
+
+/// Line and column in a source, both starting from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/**
+Error of loading a config, like [`from_toml_str`], with the location in the source.
+
+The alternate format (`{:#}`) renders the line of the source with a caret under the column.
+```
+# #[cfg(feature = "toml")]
+# {
+let err = clap_serde::from_toml_str("name = \"app\"\nshrot = \"a\"\n").unwrap_err();
+assert_eq!(err.location.map(|l| l.line), Some(2));
+assert!(err.to_string().starts_with("2:1: unknown field `shrot`"));
+assert!(format!("{:#}", err).contains("2 | shrot = \"a\"\n  | ^"));
+# }
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    /// Message of the error, without the location.
+    pub message: String,
+    pub location: Option<Location>,
+    /// Path of the file, if loaded by [`from_path`].
+    pub file: Option<PathBuf>,
+    /// The line of the source at the location.
+    line: Option<String>,
+}
+
+impl LoadError {
+    pub(crate) fn new(message: String, location: Option<Location>, src: &str) -> Self {
+        let message = match location {
+            Some(Location { line, column }) => {
+                let suffix = format!(" at line {} column {}", line, column);
+                message
+                    .strip_suffix(&suffix)
+                    .map(str::to_owned)
+                    .unwrap_or(message)
+            }
+            None => message,
+        };
+        // The formats point at the map of an unknown key, so move to the key itself.
+        let location = location.map(|l| {
+            unknown_field(&message)
+                .and_then(|key| find_key(src, l, key))
+                .unwrap_or(l)
+        });
+        let line = location
+            .and_then(|l| src.lines().nth(l.line.checked_sub(1)?))
+            .map(str::to_owned);
+        Self {
+            message,
+            location,
+            file: None,
+            line,
+        }
+    }
+}
+
+/// Name of the key in a message of serde for an unknown field.
+fn unknown_field(message: &str) -> Option<&str> {
+    let rest = &message[message.find("unknown field `")? + "unknown field `".len()..];
+    Some(&rest[..rest.find('`')?])
+}
+
+/// Location of the first `key` written as a key (followed by `:` or `=`) from the line of `from`.
+///
+/// The whole line is searched, as json points at the end of the key.
+fn find_key(src: &str, from: Location, key: &str) -> Option<Location> {
+    for (i, line) in src.lines().enumerate().skip(from.line.checked_sub(1)?) {
+        let mut rest = line;
+        while let Some(pos) = rest.find(key) {
+            let before = line[..line.len() - rest.len() + pos].chars().next_back();
+            let after = rest[pos + key.len()..].trim_start_matches(['"', '\'', ' ', '\t']);
+            let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+            if !before.is_some_and(is_word) && (after.starts_with(':') || after.starts_with('=')) {
+                let offset = line.len() - rest.len() + pos;
+                // Point at the opening quote if quoted.
+                let offset = match line[..offset].ends_with(['"', '\'']) {
+                    true => offset - 1,
+                    false => offset,
+                };
+                return Some(Location {
+                    line: i + 1,
+                    column: line[..offset].chars().count() + 1,
+                });
+            }
+            rest = &rest[pos + key.len()..];
+        }
+    }
+    None
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+        }
+        match (self.location, &self.file) {
+            (Some(location), _) => write!(f, "{}: ", location)?,
+            (None, Some(_)) => f.write_str(" ")?,
+            (None, None) => {}
+        }
+        f.write_str(&self.message)?;
+        if let (true, Some(location), Some(line)) = (f.alternate(), self.location, &self.line) {
+            let number = location.line.to_string();
+            let pad = " ".repeat(number.len());
+            // Count chars, not bytes, so that the caret is under the column.
+            let indent = line
+                .chars()
+                .take(location.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            write!(
+                f,
+                "\n{} |\n{} | {}\n{} | {}^",
+                pad, number, line, pad, indent
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Load [`Command`] from a toml string, failing with the location of the error.
+#[cfg(feature = "toml")]
+pub fn from_toml_str(src: &str) -> Result<Command<'_>, LoadError> {
+    toml_str(src, &mut Context::new())
+}
+
+#[cfg(feature = "toml")]
+fn toml_str<'a>(src: &'a str, ctx: &mut Context<'a>) -> Result<Command<'a>, LoadError> {
+    load(&mut toml::Deserializer::new(src), ctx).map_err(|e| {
+        let location = e.line_col().map(|(line, col)| Location {
+            line: line + 1,
+            column: col + 1,
+        });
+        LoadError::new(e.to_string(), location, src)
+    })
+}
+
+/// Load [`Command`] from a yaml string, failing with the location of the error.
+#[cfg(feature = "serde_yaml")]
+pub fn from_yaml_str(src: &str) -> Result<Command<'_>, LoadError> {
+    yaml_str(src, &mut Context::new())
+}
+
+#[cfg(feature = "serde_yaml")]
+fn yaml_str<'a>(src: &'a str, ctx: &mut Context<'a>) -> Result<Command<'a>, LoadError> {
+    load(serde_yaml::Deserializer::from_str(src), ctx).map_err(|e| {
+        let location = e.location().map(|l| Location {
+            line: l.line(),
+            column: l.column(),
+        });
+        LoadError::new(e.to_string(), location, src)
+    })
+}
+
+/// Load [`Command`] from a json string, failing with the location of the error.
+#[cfg(feature = "serde_json")]
+pub fn from_json_str(src: &str) -> Result<Command<'_>, LoadError> {
+    json_str(src, &mut Context::new())
+}
+
+#[cfg(feature = "serde_json")]
+fn json_str<'a>(src: &'a str, ctx: &mut Context<'a>) -> Result<Command<'a>, LoadError> {
+    let mut de = serde_json::Deserializer::from_str(src);
+    load(&mut de, ctx)
+        .and_then(|app| de.end().map(|_| app))
+        .map_err(|e| {
+            let location = (e.line() > 0).then_some(Location {
+                line: e.line(),
+                column: e.column(),
+            });
+            LoadError::new(e.to_string(), location, src)
+        })
+}
+
+/// Load [`Command`] from a ron string, failing with the location of the error.
+#[cfg(feature = "ron")]
+pub fn from_ron_str(src: &str) -> Result<Command<'_>, LoadError> {
+    ron_str(src, &mut Context::new())
+}
+
+#[cfg(feature = "ron")]
+fn ron_str<'a>(src: &'a str, ctx: &mut Context<'a>) -> Result<Command<'a>, LoadError> {
+    let error = |e: ron::error::SpannedError| {
+        let location = Location {
+            line: e.position.line,
+            column: e.position.col,
+        };
+        LoadError::new(e.code.to_string(), Some(location), src)
+    };
+    let mut de = ron::Deserializer::from_str(src).map_err(error)?;
+    load(&mut de, ctx)
+        .and_then(|app| de.end().map(|_| app))
+        .map_err(|e| error(de.span_error(e)))
+}
+
+/**
+Load [`Command`] from a string in `format`, failing if the feature of the format is not enabled.
+```
+# #[cfg(feature = "toml")]
+# {
+use clap_serde::Format;
+let src = "name = \"app\"\nabout = \"an app\"\n";
+let app = clap_serde::from_str_with_format(src, Format::sniff(src)).expect("parse failed");
+assert_eq!(app.get_about(), Some("an app"));
+# }
+```
+*/
+pub fn from_str_with_format(src: &str, format: Format) -> Result<Command<'_>, LoadError> {
+    str_with_format(src, format, &mut Context::new())
+}
+
+/// Load the command of `de` in `ctx`.
+#[cfg(any(
+    feature = "toml",
+    feature = "serde_yaml",
+    feature = "serde_json",
+    feature = "ron"
+))]
+fn load<'a, D: serde::Deserializer<'a>>(
+    de: D,
+    ctx: &mut Context<'a>,
+) -> Result<Command<'a>, D::Error> {
+    crate::de::load_root(de, ctx).map(Command::from)
+}
+
+/// Load a string in `format` in `ctx`.
+fn str_with_format<'a>(
+    src: &'a str,
+    format: Format,
+    ctx: &mut Context<'a>,
+) -> Result<Command<'a>, LoadError> {
+    match format {
+        #[cfg(feature = "toml")]
+        Format::Toml => toml_str(src, ctx),
+        #[cfg(feature = "serde_yaml")]
+        Format::Yaml => yaml_str(src, ctx),
+        #[cfg(feature = "serde_json")]
+        Format::Json => json_str(src, ctx),
+        #[cfg(feature = "ron")]
+        Format::Ron => ron_str(src, ctx),
+        // The document is dropped here, so its strings are kept in the store.
+        #[cfg(feature = "json5")]
+        Format::Json5 => crate::Document::from_json5(src)?.load(store(src, format, ctx)?, ctx),
+        #[cfg(feature = "kdl")]
+        Format::Kdl => crate::Document::from_kdl(src)?.load(store(src, format, ctx)?, ctx),
+        #[allow(unreachable_patterns)]
+        _ => {
+            let _ = ctx;
+            Err(LoadError::new(
+                format!("the `{}` feature is not enabled", format.feature()),
+                None,
+                src,
+            ))
+        }
+    }
+}
+
+/// Store of `ctx` for the strings of a document in `format`, which the loaders without one can't load.
+#[cfg(any(feature = "json5", feature = "kdl"))]
+fn store<'a>(
+    src: &str,
+    format: Format,
+    ctx: &Context<'a>,
+) -> Result<&'a crate::StringStore, LoadError> {
+    ctx.strings().ok_or_else(|| {
+        LoadError::new(
+            format!(
+                "the strings of a {} document are not borrowed from the text, so they need a `StringStore`: {}",
+                format.feature(),
+                crate::strings::NO_STORE
+            ),
+            None,
+            src,
+        )
+    })
+}
+
+/**
+Text of a config read by [`from_path`], which the [`Command`] loaded by [`Source::command`] borrows.
+
+The strings made while loading, like an integer given to `default_value`, are kept in the source as well.
+
+```no_run
+let source = clap_serde::from_path("cli.toml")?;
+let matches = source.command()?.get_matches();
+# Ok::<(), clap_serde::LoadError>(())
+```
+*/
+pub struct Source {
+    text: String,
+    format: Format,
+    path: Option<PathBuf>,
+    strings: StringStore,
+}
+
+impl Source {
+    /// Source of `text` in `format`.
+    pub fn new(text: impl Into<String>, format: Format) -> Self {
+        Self {
+            text: text.into(),
+            format,
+            path: None,
+            strings: StringStore::new(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Path of the file, if read by [`from_path`].
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Load [`Command`] borrowing the text and the strings kept in this source, with the path of the file in the error.
+    pub fn command(&self) -> Result<Command<'_>, LoadError> {
+        self.load(&mut Context::new().with_strings(&self.strings))
+    }
+
+    /**
+    Load [`Command`] as [`Source::command`] with `options`, collecting [`Diagnostic`]s as [`LoadOptions::load`].
+    ```
+    use clap_serde::{Format, LoadOptions, Source, UnknownKeys};
+
+    # #[cfg(feature = "toml")]
+    # {
+    let source = Source::new("name = \"app\"\nowner = \"someone\"\n", Format::Toml);
+    let options = LoadOptions::new().unknown_keys(UnknownKeys::Warn);
+    let (app, diagnostics) = source.command_with_options(&options).expect("load failed");
+    assert_eq!(app.get_name(), "app");
+    assert_eq!(diagnostics[0].key, "owner");
+    # }
+    ```
+    */
+    pub fn command_with_options(
+        &self,
+        options: &LoadOptions,
+    ) -> Result<(Command<'_>, Vec<Diagnostic>), LoadError> {
+        let mut ctx = Context::with_options(options.clone()).with_strings(&self.strings);
+        let app = self.load(&mut ctx)?;
+        Ok((app, ctx.take_diagnostics()))
+    }
+
+    /**
+    Load [`Command`] recording the builder methods called, and check it as [`load_checked`](crate::load_checked).
+
+    The [`Builder`] is shown as the Rust code building the same command without loading it.
+    */
+    pub fn builder(&self) -> Result<Builder, LoadError> {
+        let mut ctx = Context::recording().with_strings(&self.strings);
+        let app = self.load(&mut ctx)?;
+        crate::check::check(&app).map_err(|message| LoadError {
+            file: self.path.clone(),
+            ..LoadError::new(message, None, &self.text)
+        })?;
+        Ok(recorded(&mut ctx, &app))
+    }
+
+    /// Load the text in `ctx`, which keeps the strings made in this source, with the path of the file in the error.
+    fn load<'a>(&'a self, ctx: &mut Context<'a>) -> Result<Command<'a>, LoadError> {
+        str_with_format(&self.text, self.format, ctx).map_err(|mut e| {
+            e.file = self.path.clone();
+            e
+        })
+    }
+}
+
+/// A clone has none of the strings made while loading, which are made again when needed.
+impl Clone for Source {
+    fn clone(&self) -> Self {
+        Self {
+            text: self.text.clone(),
+            format: self.format,
+            path: self.path.clone(),
+            strings: StringStore::new(),
+        }
+    }
+}
+
+impl PartialEq for Source {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text && self.format == other.format && self.path == other.path
+    }
+}
+
+impl Eq for Source {}
+
+impl std::fmt::Debug for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Source")
+            .field("text", &self.text)
+            .field("format", &self.format)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/**
+Read a config file, with the format by its extension, or guessed by [`Format::sniff`] if unknown.
+
+The [`Command`] is loaded by [`Source::command`], borrowing the returned [`Source`].
+*/
+pub fn from_path(path: impl AsRef<Path>) -> Result<Source, LoadError> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|e| LoadError {
+        file: Some(path.to_owned()),
+        ..LoadError::new(e.to_string(), None, "")
+    })?;
+    let format = Format::from_path(path).unwrap_or_else(|| Format::sniff(&text));
+    Ok(Source {
+        path: Some(path.to_owned()),
+        ..Source::new(text, format)
+    })
+}
+
+/**
+[`Command`] loaded from a [`Source`] which it owns, so that neither the text nor the strings made while loading
+need to outlive it.
+
+As the command borrows the source, it is not kept but loaded again each time it is used,
+parsing the text anew, so keep the command given by [`LoadedCommand::command`] for as long as it is used.
+The source is checked by [`LoadedCommand::new`], and loading it again fails only with the same error.
+```no_run
+let loaded = clap_serde::LoadedCommand::from_path("cli.toml")?;
+let about = loaded.with_command(|app| app.get_about().map(str::to_owned))?;
+let matches = loaded.get_matches()?;
+# Ok::<(), clap_serde::LoadError>(())
+```
+*/
+#[derive(Debug, Clone)]
+pub struct LoadedCommand {
+    source: Source,
+}
+
+impl LoadedCommand {
+    /// Load the command from `source`, failing if it is invalid.
+    pub fn new(source: Source) -> Result<Self, LoadError> {
+        source.command()?;
+        Ok(Self { source })
+    }
+
+    /// Read and load a config file, as [`from_path`].
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        Self::new(from_path(path)?)
+    }
+
+    /// Load the command again, borrowing this, which parses the text each time.
+    pub fn command(&self) -> Result<Command<'_>, LoadError> {
+        self.source.command()
+    }
+
+    /// Call `f` with the command loaded by [`LoadedCommand::command`].
+    pub fn with_command<R>(&self, f: impl FnOnce(&Command<'_>) -> R) -> Result<R, LoadError> {
+        self.command().map(|app| f(&app))
+    }
+
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    /// Load the command and parse the arguments of the process, as [`Command::get_matches`].
+    pub fn get_matches(&self) -> Result<ArgMatches, LoadError> {
+        self.command().map(Command::get_matches)
+    }
+
+    /// Load the command and parse `itr`, as [`Command::get_matches_from`].
+    ///
+    /// Use [`Command::try_get_matches_from`] of [`LoadedCommand::command`] to handle the errors of the arguments.
+    pub fn get_matches_from<I, T>(&self, itr: I) -> Result<ArgMatches, LoadError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        self.command().map(|app| app.get_matches_from(itr))
+    }
+}
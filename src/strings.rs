@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+/// Number of chunks, enough for any number of strings which fits in memory.
+const CHUNKS: usize = usize::BITS as usize;
+
+/**
+Strings made while loading, which the loaded [`Command`](clap::Command) borrows.
+
+As clap 3 only borrows strings, the values which are not in the text of a config,
+like an integer given to `default_value` or the `long` filled in by `auto_long`, are kept here
+until the store is dropped.
+Each string is kept once, however many times it is added.
+
+[`LoadOptions::load_in`](crate::LoadOptions::load_in) loads into a store given by the caller,
+and [`Source`](crate::Source) has its own.
+The other loaders like [`load`](crate::load) have no store, so they fail with a value which needs one.
+[`StringStore::global`] can be given to `load_in` to keep the strings as long as the process instead.
+```
+const CLAP_TOML: &'static str = r#"
+name = "app"
+[args]
+jobs = { long = "jobs", default_value = 8 }
+"#;
+let strings = clap_serde::StringStore::new();
+let (app, _) = clap_serde::LoadOptions::new()
+    .load_in(&mut toml::Deserializer::new(CLAP_TOML), &strings)
+    .expect("parse failed");
+let jobs = app.get_arguments().find(|a| a.get_id() == "jobs").unwrap();
+assert_eq!(jobs.get_default_values(), ["8"]);
+assert_eq!(strings.len(), 1);
+```
+*/
+pub struct StringStore {
+    /// Chunk `i` holds `2^i` strings, so that the strings never move once added.
+    chunks: [OnceLock<Box<[OnceLock<String>]>>; CHUNKS],
+    /// Index of each string added.
+    index: Mutex<HashMap<String, usize>>,
+}
+
+impl StringStore {
+    pub fn new() -> Self {
+        Self {
+            chunks: std::array::from_fn(|_| OnceLock::new()),
+            index: Mutex::default(),
+        }
+    }
+
+    /// Store living as long as the process, which never frees the strings kept.
+    ///
+    /// Only the loaders given it use it, so loading many configs with it grows it without bound.
+    pub fn global() -> &'static Self {
+        static GLOBAL: OnceLock<StringStore> = OnceLock::new();
+        GLOBAL.get_or_init(Self::new)
+    }
+
+    /// Number of the strings kept.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Keep `s`, or return the same string kept before.
+    pub fn add(&self, s: String) -> &str {
+        let mut index = self.lock();
+        let i = match index.get(&s) {
+            Some(&i) => i,
+            None => {
+                let i = index.len();
+                let _ = self.slot(i).set(s.clone());
+                index.insert(s, i);
+                i
+            }
+        };
+        self.slot(i).get().expect("set when added")
+    }
+
+    fn slot(&self, i: usize) -> &OnceLock<String> {
+        let chunk = (i + 1).ilog2() as usize;
+        let slots =
+            self.chunks[chunk].get_or_init(|| (0..1 << chunk).map(|_| OnceLock::new()).collect());
+        &slots[i + 1 - (1 << chunk)]
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, usize>> {
+        self.index.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Keep `s` in `strings`, failing if the loader has no store.
+pub(crate) fn keep(strings: Option<&StringStore>, s: String) -> Result<&str, String> {
+    match strings {
+        Some(strings) => Ok(strings.add(s)),
+        None => Err(format!(
+            "`{}` is not in the config, so it needs a `StringStore`: {}",
+            s, NO_STORE
+        )),
+    }
+}
+
+/// How to load with a store.
+pub(crate) const NO_STORE: &str =
+    "load with `LoadOptions::load_in` or from a `Source` to keep the strings made while loading";
+
+impl Default for StringStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for StringStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StringStore")
+            .field("len", &self.len())
+            .finish()
+    }
+}
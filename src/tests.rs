@@ -307,3 +307,2688 @@ apple = { short =  }
     let mut de = toml::Deserializer::new(CLAP_TOML);
     assert!(wrap.deserialize(&mut de).is_err());
 }
+
+#[test]
+fn scalar_as_str_toml() {
+    const CLAP_TOML: &str = r#"
+name = "app_clap_serde"
+version = 1.0
+[args]
+jobs = { default_value = 8, possible_values = [1, 2, 4, 8] }
+ratio = { default_value = 0.5 }
+verbose = { default_value = true }
+"#;
+    // The stringified values are not in the config, so they need a store.
+    let err = toml::from_str::<CommandWrap>(CLAP_TOML).unwrap_err();
+    assert!(err.to_string().contains("needs a `StringStore`"), "{}", err);
+    let strings = crate::StringStore::new();
+    let (app, _) = crate::LoadOptions::new()
+        .load_in(&mut toml::Deserializer::new(CLAP_TOML), &strings)
+        .expect("parse failed");
+    assert_eq!(app.get_version(), Some("1.0"));
+    let args = app.get_arguments().collect::<Vec<_>>();
+    let jobs = args.iter().find(|x| x.get_id() == "jobs").unwrap();
+    assert_eq!(jobs.get_default_values(), ["8"]);
+    let possible_values = jobs
+        .get_possible_values()
+        .unwrap()
+        .iter()
+        .map(|p| p.get_name())
+        .collect::<Vec<_>>();
+    assert_eq!(possible_values, ["1", "2", "4", "8"]);
+    assert!(args
+        .iter()
+        .any(|x| x.get_id() == "ratio" && x.get_default_values() == ["0.5"]));
+    assert!(args
+        .iter()
+        .any(|x| x.get_id() == "verbose" && x.get_default_values() == ["true"]));
+}
+
+#[test]
+fn scalar_as_str_yaml() {
+    const CLAP_YAML: &str = r#"
+name: app_clap_serde
+version: "1.10"
+long_version: 1.10
+args:
+    - jobs:
+        default_value: 8
+"#;
+    let strings = crate::StringStore::new();
+    let (app, _) = crate::LoadOptions::new()
+        .load_in(serde_yaml::Deserializer::from_str(CLAP_YAML), &strings)
+        .expect("fail to make yaml");
+    assert_eq!(app.get_version(), Some("1.10"));
+    // A float is parsed, so it keeps the fractional part but not the trailing zeros.
+    assert_eq!(app.get_long_version(), Some("1.1"));
+    assert!(app
+        .get_arguments()
+        .any(|x| x.get_id() == "jobs" && x.get_default_values() == ["8"]));
+}
+
+#[test]
+fn scalar_as_str_json() {
+    use crate::{LoadOptions, StringStore};
+    const CLAP_JSON: &str = r#"{
+        "name": "app_clap_serde",
+        "version": 1.0,
+        "args": [
+            { "jobs": { "default_value": 8, "possible_values": [1, 2, 4, 8] } },
+            { "verbose": { "default_value": true } },
+            { "tab": { "value_name": "T\tB" } }
+        ]
+    }"#;
+    let global = StringStore::global();
+    let (app, _) = LoadOptions::new()
+        .load_in(&mut serde_json::Deserializer::from_str(CLAP_JSON), global)
+        .expect("parse failed");
+    assert_eq!(app.get_version(), Some("1.0"));
+    let args = app.get_arguments().collect::<Vec<_>>();
+    let jobs = args.iter().find(|x| x.get_id() == "jobs").unwrap();
+    assert_eq!(jobs.get_default_values(), ["8"]);
+    let verbose = args.iter().find(|x| x.get_id() == "verbose").unwrap();
+    assert_eq!(verbose.get_default_values(), ["true"]);
+    // An escaped string is not borrowed from the input.
+    let tab = args.iter().find(|x| x.get_id() == "tab").unwrap();
+    assert_eq!(tab.get_value_names(), Some(&["T\tB"][..]));
+
+    // The strings made are kept once, however many times the config is loaded.
+    let strings = StringStore::new();
+    for _ in 0..3 {
+        LoadOptions::new()
+            .load_in(&mut serde_json::Deserializer::from_str(CLAP_JSON), &strings)
+            .expect("parse failed");
+    }
+    assert_eq!(strings.len(), ["1.0", "8", "1", "2", "4", "T\tB"].len());
+}
+
+#[test]
+fn one_or_many_toml() {
+    const CLAP_TOML: &str = r#"
+name = "app_clap_serde"
+aliases = "app"
+settings = "arg_required_else_help"
+global_settings = "disable_help_subcommand"
+[args]
+apple = { short = "a", short_aliases = "p", requires = ["banana", "cherry"] }
+banana = { long = "banana", alias = ["musa", "musa_spp"], value_name = ["A", "B"] }
+cherry = { long = "cherry" }
+[groups]
+fruit = { args = "apple", conflicts_with = ["banana", "cherry"] }
+"#;
+    let app: Command = toml::from_str::<CommandWrap>(CLAP_TOML)
+        .expect("parse failed")
+        .into();
+    assert_eq!(app.get_all_aliases().collect::<Vec<_>>(), ["app"]);
+    assert!(app.is_arg_required_else_help_set());
+    assert!(app.is_disable_help_subcommand_set());
+    let args = app.get_arguments().collect::<Vec<_>>();
+    let apple = args.iter().find(|x| x.get_id() == "apple").unwrap();
+    assert_eq!(apple.get_all_short_aliases(), Some(vec!['p']));
+    let banana = args.iter().find(|x| x.get_id() == "banana").unwrap();
+    assert_eq!(banana.get_all_aliases(), Some(vec!["musa", "musa_spp"]));
+    assert_eq!(banana.get_value_names(), Some(&["A", "B"][..]));
+}
+
+#[test]
+fn action_toml() {
+    const CLAP_TOML: &str = r#"
+name = "app_clap_serde"
+[args]
+verbose = { short = "v", action = "count" }
+quiet = { short = "q", arg_action = "set_true" }
+"#;
+    let app: Command = toml::from_str::<CommandWrap>(CLAP_TOML)
+        .expect("parse failed")
+        .into();
+    let args = app.get_arguments().collect::<Vec<_>>();
+    let vp_b: ValueParser = ValueParser::bool();
+    assert!(args.iter().any(|x| x.get_id() == "quiet"
+        && !x.is_takes_value_set()
+        && x.get_value_parser().type_id() == vp_b.type_id()));
+    let m = app.get_matches_from(["app", "-vvvq"]);
+    assert_eq!(m.get_one::<u8>("verbose").copied(), Some(3));
+    assert_eq!(m.get_one::<bool>("quiet").copied(), Some(true));
+}
+
+#[test]
+fn action_conflict_toml() {
+    const CLAP_TOML: &str = r#"
+name = "app_clap_serde"
+[args]
+verbose = { short = "v", action = "count", takes_value = true }
+"#;
+    let err = toml::from_str::<CommandWrap>(CLAP_TOML).unwrap_err();
+    assert!(err.to_string().contains("takes_value"));
+}
+
+#[test]
+fn color_toml() {
+    const CLAP_TOML: &str = r#"
+name = "app_clap_serde"
+color = "always"
+"#;
+    let app = toml::from_str::<CommandWrap>(CLAP_TOML);
+    #[cfg(feature = "color")]
+    assert_eq!(
+        app.expect("parse failed").get_color(),
+        clap::ColorChoice::Always
+    );
+    #[cfg(not(feature = "color"))]
+    assert!(app
+        .unwrap_err()
+        .to_string()
+        .contains("color feature disabled"));
+}
+
+#[test]
+fn env_toml() {
+    const CLAP_TOML: &str = r#"
+name = "app_clap_serde"
+[args]
+apple = { long = "apple", env = "APPLE", hide_env = true, hide_env_values = true }
+"#;
+    let app = toml::from_str::<CommandWrap>(CLAP_TOML);
+    #[cfg(feature = "env")]
+    {
+        let app = app.expect("parse failed");
+        let apple = app.get_arguments().find(|a| a.get_id() == "apple").unwrap();
+        assert_eq!(apple.get_env(), Some(std::ffi::OsStr::new("APPLE")));
+        assert!(apple.is_hide_env_set());
+        assert!(apple.is_hide_env_values_set());
+    }
+    #[cfg(not(feature = "env"))]
+    assert!(app
+        .unwrap_err()
+        .to_string()
+        .contains("env feature disabled"));
+}
+
+#[test]
+fn gated_keys() {
+    for key in crate::gated_keys() {
+        let enabled = match key.feature {
+            "color" => cfg!(feature = "color"),
+            "env" => cfg!(feature = "env"),
+            f => panic!("unknown feature {}", f),
+        };
+        assert_eq!(key.enabled, enabled, "{}", key.key);
+    }
+}
+
+#[test]
+fn auto_env_toml() {
+    const CLAP_TOML: &str = r#"
+name = "app_clap_serde"
+env_prefix = "MYTOOL_"
+auto_env = true
+[args]
+foo_bar = { long = "foo-bar" }
+apple = { long = "apple", env = "APPLE" }
+quiet = { long = "quiet", auto_env = false }
+[subcommands.build]
+args = [{ jobs = { long = "jobs" } }]
+[subcommands.run]
+env_prefix = "RUN_"
+args = [{ dry-run = { long = "dry-run" } }]
+"#;
+    let strings = crate::StringStore::new();
+    let app = crate::LoadOptions::new()
+        .load_in(&mut toml::Deserializer::new(CLAP_TOML), &strings)
+        .map(|(app, _)| app);
+    #[cfg(feature = "env")]
+    {
+        use std::ffi::OsStr;
+        let app = app.expect("parse failed");
+        let env = |app: &Command, id: &str| {
+            app.get_arguments()
+                .find(|a| a.get_id() == id)
+                .unwrap()
+                .get_env()
+                .map(|e| e.to_owned())
+        };
+        assert_eq!(
+            env(&app, "foo_bar").as_deref(),
+            Some(OsStr::new("MYTOOL_FOO_BAR"))
+        );
+        assert_eq!(env(&app, "apple").as_deref(), Some(OsStr::new("APPLE")));
+        assert_eq!(env(&app, "quiet"), None);
+        let build = app.find_subcommand("build").unwrap();
+        assert_eq!(
+            env(build, "jobs").as_deref(),
+            Some(OsStr::new("MYTOOL_BUILD_JOBS"))
+        );
+        let run = app.find_subcommand("run").unwrap();
+        assert_eq!(
+            env(run, "dry-run").as_deref(),
+            Some(OsStr::new("RUN_DRY_RUN"))
+        );
+    }
+    #[cfg(not(feature = "env"))]
+    assert!(app
+        .unwrap_err()
+        .to_string()
+        .contains("env feature disabled"));
+}
+
+#[test]
+fn auto_long_toml() {
+    const CLAP_TOML: &str = r#"
+name = "app_clap_serde"
+auto_long = "kebab"
+auto_value_name = true
+[args]
+foo_bar = { takes_value = true }
+dry_run = { action = "set_true" }
+short_only = { short = "s", auto_long = false }
+explicit = { long = "other", value_name = "X" }
+input = { index = 1 }
+verbose = { short = "v" }
+[subcommands.build]
+auto_value_name = false
+args = [{ max_jobs = { takes_value = true } }, { target = { required = true } }]
+"#;
+    let strings = crate::StringStore::new();
+    let app: Command = crate::LoadOptions::new()
+        .load_in(&mut toml::Deserializer::new(CLAP_TOML), &strings)
+        .expect("parse failed")
+        .0
+        .into();
+    fn arg<'a>(app: &Command<'a>, id: &str) -> (Option<&'a str>, Option<Vec<&'a str>>) {
+        let a = app.get_arguments().find(|a| a.get_id() == id).unwrap();
+        (a.get_long(), a.get_value_names().map(|v| v.to_vec()))
+    }
+    assert_eq!(
+        arg(&app, "foo_bar"),
+        (Some("foo-bar"), Some(vec!["FOO_BAR"]))
+    );
+    assert_eq!(arg(&app, "dry_run"), (Some("dry-run"), None));
+    assert_eq!(arg(&app, "short_only"), (None, None));
+    assert_eq!(arg(&app, "explicit"), (Some("other"), Some(vec!["X"])));
+    assert_eq!(arg(&app, "input"), (None, Some(vec!["INPUT"])));
+    assert_eq!(arg(&app, "verbose"), (Some("verbose"), None));
+    let build = app.find_subcommand("build").unwrap();
+    assert_eq!(arg(build, "max_jobs"), (Some("max-jobs"), None));
+    // Neither taking a value nor a flag, which is positional.
+    assert_eq!(arg(build, "target"), (None, None));
+    let m =
+        app.clone()
+            .get_matches_from(["app", "--foo-bar", "1", "--dry-run", "in", "build", "x"]);
+    assert_eq!(
+        m.get_one::<String>("foo_bar").map(|s| s.as_str()),
+        Some("1")
+    );
+    assert_eq!(m.get_one::<String>("input").map(|s| s.as_str()), Some("in"));
+    let build = m.subcommand_matches("build").unwrap();
+    assert_eq!(
+        build.get_one::<String>("target").map(|s| s.as_str()),
+        Some("x")
+    );
+
+    // `foo-bar`, `FOO_BAR`, `dry-run`, `INPUT` and `max-jobs` are made once.
+    let strings = crate::StringStore::new();
+    for _ in 0..2 {
+        crate::LoadOptions::new()
+            .load_in(&mut toml::Deserializer::new(CLAP_TOML), &strings)
+            .expect("parse failed");
+    }
+    assert_eq!(strings.len(), 5);
+}
+
+#[test]
+fn auto_long_renamed_subcommand_toml() {
+    const CLAP_TOML: &str = r#"
+name = "app_clap_serde"
+[subcommands.build]
+name = "b"
+about = "builds"
+[subcommands.test]
+name = "t"
+auto_long = true
+args = [{ max_jobs = { takes_value = true } }]
+"#;
+    let strings = crate::StringStore::new();
+    let app: Command = crate::LoadOptions::new()
+        .load_in(&mut toml::Deserializer::new(CLAP_TOML), &strings)
+        .expect("parse failed")
+        .0
+        .into();
+    let names: Vec<_> = app.get_subcommands().map(|s| s.get_name()).collect();
+    assert_eq!(names, ["b", "t"]);
+    assert_eq!(
+        app.find_subcommand("b").unwrap().get_about(),
+        Some("builds")
+    );
+    let test = app.find_subcommand("t").unwrap();
+    let max_jobs = test.get_arguments().find(|a| a.get_id() == "max_jobs");
+    assert_eq!(max_jobs.unwrap().get_long(), Some("max-jobs"));
+}
+
+#[test]
+fn usage_toml() {
+    const CLAP_TOML: &str = r#"
+name = "app_clap_serde"
+args = [
+    "-c, --config <FILE> 'Sets a config file'",
+    "-v... 'Verbosity'",
+    "--out=[DIR]",
+    "input: <INPUT>... 'Input files'",
+    { "-d, --debug 'Debug mode'" = { hide = true } },
+    { plain = { short = "p" } },
+]
+"#;
+    let app: Command = toml::from_str::<CommandWrap>(CLAP_TOML)
+        .expect("parse failed")
+        .into();
+    let arg = |id: &str| app.get_arguments().find(|a| a.get_id() == id).unwrap();
+    let config = arg("config");
+    assert_eq!(config.get_short(), Some('c'));
+    assert_eq!(config.get_long(), Some("config"));
+    assert_eq!(config.get_value_names(), Some(&["FILE"][..]));
+    assert_eq!(config.get_help(), Some("Sets a config file"));
+    assert!(config.is_takes_value_set() && config.is_required_set());
+    let v = arg("v");
+    assert!(v.is_multiple_occurrences_set() && !v.is_takes_value_set());
+    let out = arg("out");
+    assert!(out.is_takes_value_set() && !out.is_required_set());
+    let input = arg("input");
+    assert!(input.is_positional() && input.is_multiple_values_set() && input.is_required_set());
+    assert!(arg("debug").is_hide_set());
+    assert_eq!(arg("plain").get_short(), Some('p'));
+
+    let m = app
+        .clone()
+        .get_matches_from(["app", "-c", "a.toml", "-vv", "x", "y"]);
+    assert_eq!(
+        m.get_one::<String>("config").map(|s| s.as_str()),
+        Some("a.toml")
+    );
+    assert_eq!(m.occurrences_of("v"), 2);
+    assert_eq!(m.get_many::<String>("input").unwrap().count(), 2);
+
+    for usage in ["--config <FILE", "-ab", "'help"] {
+        let toml = format!("name = \"app\"\nargs = [{:?}]", usage);
+        assert!(toml::from_str::<CommandWrap>(&toml).is_err(), "{}", usage);
+    }
+
+    // Usage strings with escapes are not borrowed from the config.
+    let toml = r#"
+name = "app"
+args = ["-c, --config <FILE> 'a \"c\" file'"]
+"#;
+    assert!(toml::from_str::<CommandWrap>(toml).is_err());
+    let strings = crate::StringStore::new();
+    let options = crate::LoadOptions::new();
+    let (app, _) = options
+        .load_in(&mut toml::Deserializer::new(toml), &strings)
+        .unwrap();
+    let config = app
+        .get_arguments()
+        .find(|a| a.get_id() == "config")
+        .unwrap();
+    assert_eq!(config.get_help(), Some("a \"c\" file"));
+    let json = r#"{"name": "app", "args": ["-o, --out <DIR> 'output\tdir'"]}"#;
+    let (app, _) = options
+        .load_in(&mut serde_json::Deserializer::from_str(json), &strings)
+        .unwrap();
+    let out = app.get_arguments().find(|a| a.get_id() == "out").unwrap();
+    assert_eq!(out.get_help(), Some("output\tdir"));
+}
+
+#[test]
+fn usage_yaml() {
+    const YAML_STR: &str = r#"
+name: app_clap_serde
+args:
+    - "-c, --config <FILE> 'Sets a config file'"
+    - "--debug 'Debug mode'":
+        hide: true
+"#;
+    let app: Command = serde_yaml::from_str::<CommandWrap>(YAML_STR)
+        .expect("parse failed")
+        .into();
+    let config = app
+        .get_arguments()
+        .find(|a| a.get_id() == "config")
+        .unwrap();
+    assert_eq!(config.get_short(), Some('c'));
+    assert!(config.is_takes_value_set());
+    let debug = app.get_arguments().find(|a| a.get_id() == "debug").unwrap();
+    assert!(debug.is_hide_set() && !debug.is_takes_value_set());
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn compat_clap2_yaml() {
+    const CLI_YML: &str = r#"
+name: myapp
+version: "1.0"
+settings:
+    - SubcommandRequiredElseHelp
+    - ColoredHelp
+global_settings:
+    - GlobalVersion
+    - DisableHelpSubcommand
+args:
+    - verbose:
+        short: v
+        multiple: true
+    - config:
+        from_usage: -c, --config <FILE> 'Sets a config file'
+    - output:
+        short: o
+        takes_value: true
+        multiple: true
+        use_delimiter: true
+    - input:
+        index: 1
+        multiple: true
+        empty_values: false
+subcommands:
+    - test:
+        about: controls testing features
+        settings: ArgRequiredElseHelp
+        args:
+            - debug:
+                short: d
+                hidden: true
+                required_unless: verbose
+            - list:
+                long: list
+"#;
+    let docs = yaml_rust::YamlLoader::load_from_str(CLI_YML).unwrap();
+    let migrated = crate::compat::clap2_yaml(&docs[0]);
+    let app = migrated.to_app().expect("parse failed");
+    fn arg<'a>(app: &Command<'a>, id: &str) -> clap::Arg<'a> {
+        app.get_arguments()
+            .find(|a| a.get_id() == id)
+            .cloned()
+            .unwrap()
+    }
+    assert!(app.is_subcommand_required_set() && app.is_arg_required_else_help_set());
+    assert!(app.is_propagate_version_set() && app.is_disable_help_subcommand_set());
+    let verbose = arg(&app, "verbose");
+    assert!(verbose.is_multiple_occurrences_set() && !verbose.is_multiple_values_set());
+    let config = arg(&app, "config");
+    assert_eq!(config.get_long(), Some("config"));
+    assert_eq!(config.get_help(), Some("Sets a config file"));
+    let output = arg(&app, "output");
+    assert!(output.is_multiple_occurrences_set() && output.is_multiple_values_set());
+    assert!(output.is_use_value_delimiter_set());
+    let input = arg(&app, "input");
+    assert!(input.is_multiple_values_set() && input.is_forbid_empty_values_set());
+
+    let test = app.find_subcommand("test").unwrap();
+    assert!(test.is_arg_required_else_help_set());
+    assert!(test.is_propagate_version_set() && test.is_disable_help_subcommand_set());
+    assert!(arg(test, "debug").is_hide_set());
+
+    let notes = migrated
+        .notes()
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>();
+    for expected in [
+        "settings: `SubcommandRequiredElseHelp` is replaced with `subcommand_required` and `arg_required_else_help`",
+        "settings: `ColoredHelp` is removed, as it is the default or no longer needed",
+        "args.verbose.multiple: `multiple` is replaced with `multiple_occurrences`",
+        "args.config.from_usage: `from_usage` is replaced with a usage string key",
+        "args.output.multiple: `multiple` is replaced with `multiple_occurrences` and `multiple_values`",
+        "args.input.empty_values: `empty_values` is replaced with `forbid_empty_values`",
+        "subcommands.test.args.debug.hidden: `hidden` is renamed to `hide`",
+        "subcommands.test.args.debug.required_unless: `required_unless` is renamed to `required_unless_present`",
+    ] {
+        assert!(notes.iter().any(|n| n == expected), "{}", expected);
+    }
+    assert!(!notes.iter().any(|n| n.contains("list")));
+}
+
+#[cfg(feature = "migrate")]
+#[test]
+fn migrate_json() {
+    use crate::migrate::{migrate, Format};
+    const OLD_JSON: &str = r#"{
+        "name": "app",
+        "help_heading": "OPTIONS",
+        "args": {
+            "apple": { "short": "a", "use_delimiter": true }
+        },
+        "subcommands": {
+            "sub": { "args": [ { "banana": { "name": "musa", "require_delimiter": true } } ] }
+        },
+        "groups": { "fruit": { "name": "fruits", "args": "apple" } }
+    }"#;
+    let migrated = migrate(OLD_JSON, Format::Json).unwrap();
+    let app = crate::load(&mut serde_json::Deserializer::from_str(migrated.text())).unwrap();
+    assert_eq!(app.get_next_help_heading(), Some("OPTIONS"));
+    let apple = app.get_arguments().find(|a| a.get_id() == "apple").unwrap();
+    assert!(apple.is_use_value_delimiter_set());
+    let sub = app.find_subcommand("sub").unwrap();
+    let banana = sub.get_arguments().find(|a| a.get_id() == "musa").unwrap();
+    assert!(banana.is_require_value_delimiter_set());
+    assert!(format!("{:?}", app.get_groups().next().unwrap()).contains("\"fruits\""));
+
+    let notes = migrated
+        .notes()
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notes,
+        [
+            "help_heading: `help_heading` is renamed to `next_help_heading`",
+            "args: `args` is changed from a map to a list",
+            "args.apple.use_delimiter: `use_delimiter` is renamed to `use_value_delimiter`",
+            "subcommands: `subcommands` is changed from a map to a list",
+            "subcommands.sub.args.banana.name: `name` is renamed to `id`",
+            "subcommands.sub.args.banana.require_delimiter: `require_delimiter` is renamed to `require_value_delimiter`",
+            "groups.fruit.name: `name` is renamed to `id`",
+        ]
+    );
+
+    let current = migrate(migrated.text(), Format::Json).unwrap();
+    assert!(!current.is_changed());
+    assert_eq!(current.text(), migrated.text());
+}
+
+#[cfg(feature = "migrate")]
+#[test]
+fn migrate_clap2_keys() {
+    use crate::migrate::{migrate, Format};
+    const OLD_JSON: &str = r#"{
+        "name": "app",
+        "help_message": "Print help",
+        "args": [
+            { "verbose": { "short": "v", "multiple": true } },
+            { "output": {
+                "short": "o",
+                "takes_value": true,
+                "multiple": true,
+                "validator_regex": "^[a-z]+$"
+            } },
+            { "input": {
+                "index": 1,
+                "empty_values": false,
+                "settings": ["Hidden", "TakesValue"]
+            } }
+        ],
+        "subcommands": [
+            { "test": { "args": [
+                { "debug": { "short": "d", "hidden": true, "required_unless": "verbose" } }
+            ] } }
+        ]
+    }"#;
+    const OLD_TOML: &str = r#"name = "app"
+[args.verbose]
+short = "v"
+multiple = true
+[args.input]
+set = "Hidden"
+[args.config]
+from_usage = "-c, --config <FILE> 'Sets a config file'"
+"#;
+    fn arg<'a>(app: &Command<'a>, id: &str) -> clap::Arg<'a> {
+        app.get_arguments()
+            .find(|a| a.get_id() == id)
+            .cloned()
+            .unwrap()
+    }
+
+    let migrated = migrate(OLD_JSON, Format::Json).unwrap();
+    let app = crate::load(&mut serde_json::Deserializer::from_str(migrated.text())).unwrap();
+    let verbose = arg(&app, "verbose");
+    assert!(verbose.is_multiple_occurrences_set() && !verbose.is_multiple_values_set());
+    let output = arg(&app, "output");
+    assert!(output.is_multiple_occurrences_set() && output.is_multiple_values_set());
+    let input = arg(&app, "input");
+    assert!(input.is_forbid_empty_values_set() && input.is_hide_set());
+    assert!(input.is_takes_value_set());
+    let debug = arg(app.find_subcommand("test").unwrap(), "debug");
+    assert!(debug.is_hide_set());
+
+    let notes = migrated
+        .notes()
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>();
+    for expected in [
+        "help_message: `help_message` is removed, modify the generated arg instead",
+        "args.verbose.multiple: `multiple` is replaced with `multiple_occurrences`",
+        "args.output.multiple: `multiple` is replaced with `multiple_occurrences` and `multiple_values`",
+        "args.output.validator_regex: `validator_regex` is removed, use `value_parser` instead",
+        "args.input.empty_values: `empty_values` is replaced with `forbid_empty_values`",
+        "args.input.settings: `Hidden` is replaced with `hide`",
+        "subcommands.test.args.debug.hidden: `hidden` is renamed to `hide`",
+        "subcommands.test.args.debug.required_unless: `required_unless` is renamed to `required_unless_present`",
+    ] {
+        assert!(notes.iter().any(|n| n == expected), "{}", expected);
+    }
+    assert!(!migrate(migrated.text(), Format::Json).unwrap().is_changed());
+
+    let migrated = migrate(OLD_TOML, Format::Toml).unwrap();
+    let app = crate::load(&mut toml::Deserializer::new(migrated.text())).unwrap();
+    assert!(arg(&app, "verbose").is_multiple_occurrences_set());
+    assert!(arg(&app, "input").is_hide_set());
+    let config = arg(&app, "config");
+    assert_eq!(config.get_long(), Some("config"));
+    assert_eq!(config.get_help(), Some("Sets a config file"));
+    assert_eq!(
+        migrated.notes().last().unwrap().to_string(),
+        "args.config.from_usage: `from_usage` is replaced with a usage string key"
+    );
+}
+
+#[cfg(feature = "migrate")]
+#[test]
+fn migrate_toml_keeps_comments() {
+    use crate::migrate::{migrate, Format};
+    const OLD_TOML: &str = r#"name = "app"
+# heading of the args
+help_heading = "OPTIONS" # shown in help
+[args]
+# the apple
+apple = { short = "a", use_delimiter = true }
+[args.banana]
+name = "musa"
+short = "b"
+"#;
+    const NEW_TOML: &str = r#"name = "app"
+# heading of the args
+next_help_heading = "OPTIONS" # shown in help
+[args]
+# the apple
+apple = { short = "a", use_value_delimiter = true }
+[args.banana]
+id = "musa"
+short = "b"
+"#;
+    let migrated = migrate(OLD_TOML, Format::Toml).unwrap();
+    assert_eq!(migrated.text(), NEW_TOML);
+    assert_eq!(migrated.notes().len(), 3);
+    let app = crate::load(&mut toml::Deserializer::new(migrated.text())).unwrap();
+    assert!(app.get_arguments().any(|a| a.get_id() == "musa"));
+}
+
+#[test]
+fn deprecated_diagnostics_toml() {
+    use crate::{Diagnostic, Severity};
+    const DEP_TOML: &str = r#"
+name = "app"
+help_heading = "OPTIONS"
+[args]
+apple = { name = "musa", use_delimiter = true }
+[subcommands.sub]
+help_message = "Prints help"
+[groups]
+fruit = { name = "fruits", args = "musa" }
+"#;
+    let (app, diagnostics) =
+        crate::load_with_diagnostics(&mut toml::Deserializer::new(DEP_TOML)).unwrap();
+    assert_eq!(app.get_next_help_heading(), Some("OPTIONS"));
+    let musa = app.get_arguments().find(|a| a.get_id() == "musa").unwrap();
+    assert!(musa.is_use_value_delimiter_set());
+    let diagnostic = |path: &str, key: &str, replacement, severity| Diagnostic {
+        path: path.to_owned(),
+        key: key.to_owned(),
+        replacement,
+        severity,
+        kind: crate::DiagnosticKind::Deprecated,
+    };
+    assert_eq!(
+        diagnostics,
+        [
+            diagnostic(
+                "help_heading",
+                "help_heading",
+                Some("next_help_heading"),
+                Severity::Warning
+            ),
+            diagnostic("args.apple.name", "name", Some("id"), Severity::Warning),
+            diagnostic(
+                "args.apple.use_delimiter",
+                "use_delimiter",
+                Some("use_value_delimiter"),
+                Severity::Warning
+            ),
+            diagnostic(
+                "subcommands.sub.help_message",
+                "help_message",
+                None,
+                Severity::Error
+            ),
+            diagnostic("groups.fruit.name", "name", Some("id"), Severity::Warning),
+        ]
+    );
+
+    let loaded = crate::load(&mut toml::Deserializer::new(
+        "name = \"app\"\nhelp_message = \"Prints help\"",
+    ));
+    assert!(loaded.unwrap_err().to_string().contains("help_message"));
+    let loaded = crate::load(&mut toml::Deserializer::new(
+        "name = \"app\"\nhelp_heading = \"OPTIONS\"",
+    ));
+    if cfg!(feature = "allow-deprecated") {
+        assert_eq!(loaded.unwrap().get_next_help_heading(), Some("OPTIONS"));
+    } else {
+        assert!(loaded
+            .unwrap_err()
+            .to_string()
+            .contains("next_help_heading"));
+    }
+    let loaded = crate::load(&mut toml::Deserializer::new(
+        "name = \"app\"\nsetting = \"multicall\"",
+    ));
+    if cfg!(feature = "allow-deprecated") {
+        assert!(loaded.unwrap().is_multicall_set());
+    } else {
+        assert!(loaded.is_err());
+    }
+}
+
+#[test]
+fn metadata_yaml() {
+    use crate::MetaValue;
+    const META_YAML: &str = r#"
+name: app
+x-docs: https://example.com
+args:
+    - verbose:
+        short: v
+        x_telemetry: false
+subcommands:
+    - build:
+        x-owner: build-team
+        args:
+            - jobs:
+                short: j
+                x-owner: build-team
+                x-limits: { min: 1, max: 64 }
+"#;
+    let wrap: CommandWrap = serde_yaml::from_str(META_YAML).unwrap();
+    let root = wrap.command_metadata("").unwrap();
+    assert_eq!(
+        root.get("docs").and_then(MetaValue::as_str),
+        Some("https://example.com")
+    );
+    let verbose = wrap.metadata("", "verbose").unwrap();
+    assert_eq!(verbose.get("telemetry"), Some(&MetaValue::Bool(false)));
+    let build = wrap.command_metadata("build").unwrap();
+    assert_eq!(
+        build.get("owner").and_then(MetaValue::as_str),
+        Some("build-team")
+    );
+    let jobs = wrap.metadata("build", "jobs").unwrap();
+    assert_eq!(
+        jobs.get("limits"),
+        Some(&MetaValue::Map(vec![
+            ("min".to_owned(), MetaValue::Int(1)),
+            ("max".to_owned(), MetaValue::Int(64)),
+        ]))
+    );
+    assert!(wrap.metadata("build", "verbose").is_none());
+    assert!(wrap.command_metadata("test").is_none());
+}
+
+#[test]
+fn unknown_keys_toml() {
+    use crate::{DiagnosticKind, LoadOptions, UnknownKeys};
+    const UNKNOWN_TOML: &str = r#"
+name = "app"
+owner = "someone"
+[args]
+apple = { short = "a", colour = "red" }
+"#;
+    let err = crate::load(&mut toml::Deserializer::new(UNKNOWN_TOML)).unwrap_err();
+    assert!(err.to_string().contains("unknown field `owner`"));
+
+    let (wrap, diagnostics) = LoadOptions::new()
+        .unknown_keys(UnknownKeys::Warn)
+        .load(&mut toml::Deserializer::new(UNKNOWN_TOML))
+        .unwrap();
+    assert!(wrap.get_arguments().any(|a| a.get_short() == Some('a')));
+    let paths = diagnostics
+        .iter()
+        .map(|d| (d.path.as_str(), d.kind))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            ("owner", DiagnosticKind::UnknownKey),
+            ("args.apple.colour", DiagnosticKind::UnknownKey)
+        ]
+    );
+
+    let (_, diagnostics) = LoadOptions::new()
+        .unknown_keys(UnknownKeys::Ignore)
+        .load(&mut toml::Deserializer::new(UNKNOWN_TOML))
+        .unwrap();
+    assert!(diagnostics.is_empty());
+}
+
+#[test]
+fn key_handler_toml() {
+    use crate::{KeyHandler, LoadOptions, MetaValue, StringStore};
+    use clap::{Arg, ArgGroup, Command};
+
+    struct Handler;
+    impl KeyHandler for Handler {
+        fn command_key<'a>(
+            &self,
+            key: &str,
+            value: &MetaValue,
+            command: &mut Command<'a>,
+            strings: Option<&'a StringStore>,
+        ) -> Result<bool, String> {
+            if key != "stage" {
+                return Ok(false);
+            }
+            let stage: String = value.deserialize_into().map_err(|e| e.to_string())?;
+            if stage == "beta" {
+                let about = format!("In {} stage", stage);
+                let about = strings.ok_or("stage needs a StringStore")?.add(about);
+                let hidden = std::mem::replace(command, Command::new("")).hide(true);
+                *command = hidden.about(about);
+                Ok(true)
+            } else {
+                Err(format!("unknown stage {}", stage))
+            }
+        }
+
+        fn arg_key<'a>(
+            &self,
+            key: &str,
+            value: &MetaValue,
+            arg: &mut Arg<'a>,
+            _: Option<&'a StringStore>,
+        ) -> Result<bool, String> {
+            if key != "required_in" {
+                return Ok(false);
+            }
+            let envs: Vec<String> = value.deserialize_into().map_err(|e| e.to_string())?;
+            *arg = std::mem::take(arg).required(envs.iter().any(|e| e == "ci"));
+            Ok(true)
+        }
+
+        fn group_key<'a>(
+            &self,
+            key: &str,
+            value: &MetaValue,
+            group: &mut ArgGroup<'a>,
+            _: Option<&'a StringStore>,
+        ) -> Result<bool, String> {
+            if key != "required_in" {
+                return Ok(false);
+            }
+            let envs: Vec<String> = value.deserialize_into().map_err(|e| e.to_string())?;
+            *group = std::mem::take(group).required(envs.iter().any(|e| e == "ci"));
+            Ok(true)
+        }
+    }
+
+    const HANDLER_TOML: &str = r#"
+name = "app"
+[args]
+token = { long = "token", takes_value = true, required_in = ["ci", "prod"] }
+[subcommands.preview]
+stage = "beta"
+[subcommands.preview.args]
+fast = { long = "fast" }
+slow = { long = "slow" }
+[subcommands.preview.groups]
+speed = { args = ["fast", "slow"], required_in = ["ci"] }
+"#;
+    let strings = StringStore::new();
+    let (wrap, diagnostics) = LoadOptions::new()
+        .key_handler(Handler)
+        .load_in(&mut toml::Deserializer::new(HANDLER_TOML), &strings)
+        .unwrap();
+    assert!(diagnostics.is_empty());
+    assert!(wrap
+        .get_arguments()
+        .any(|a| a.get_id() == "token" && a.is_required_set()));
+    let preview = wrap.find_subcommand("preview").unwrap().clone();
+    assert!(preview.is_hide_set());
+    assert_eq!(preview.get_about(), Some("In beta stage"));
+    assert!(preview.clone().try_get_matches_from(["preview"]).is_err());
+    assert!(preview.try_get_matches_from(["preview", "--fast"]).is_ok());
+
+    let err = LoadOptions::new()
+        .key_handler(Handler)
+        .load(&mut toml::Deserializer::new(
+            "name = \"app\"\nstage = \"alpha\"",
+        ))
+        .unwrap_err();
+    assert!(err.to_string().contains("unknown stage alpha"));
+    // The loaders without a store give none to the handlers.
+    let err = LoadOptions::new()
+        .key_handler(Handler)
+        .load(&mut toml::Deserializer::new(HANDLER_TOML))
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("stage needs a StringStore"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn duplicates_yaml() {
+    use crate::{DiagnosticKind, Duplicates, LoadOptions};
+    const DUP_YAML: &str = r#"
+name: app
+about: first
+about: second
+args:
+    - verbose:
+        short: v
+        alias: loud
+        alias: noisy
+    - verbose:
+        long: verbose
+subcommands:
+    - build:
+        about: build
+    - test:
+        name: build
+"#;
+    let wrap: CommandWrap = serde_yaml::from_str(DUP_YAML).unwrap();
+    assert_eq!(wrap.get_about(), Some("second"));
+
+    let (_, diagnostics) = LoadOptions::new()
+        .duplicates(Duplicates::Warn)
+        .load(serde_yaml::Deserializer::from_str(DUP_YAML))
+        .unwrap();
+    let paths = diagnostics
+        .iter()
+        .map(|d| (d.path.as_str(), d.kind))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            ("about", DiagnosticKind::Duplicate),
+            ("args.verbose.alias", DiagnosticKind::Duplicate),
+            ("args.verbose", DiagnosticKind::Duplicate),
+            ("subcommands.build", DiagnosticKind::Duplicate),
+        ]
+    );
+
+    let err = LoadOptions::new()
+        .duplicates(Duplicates::Deny)
+        .load(serde_yaml::Deserializer::from_str(DUP_YAML))
+        .unwrap_err();
+    assert!(err.to_string().contains("duplicate key: `about`"));
+}
+
+#[test]
+fn conflicts_yaml() {
+    use crate::find_conflicts;
+    const CONFLICT_YAML: &str = r#"
+name: app
+args:
+    - verbose:
+        short: v
+        long: verbose
+        global: true
+    - config:
+        short: c
+        visible_alias: conf
+subcommands:
+    - build:
+        short_flag: c
+        aliases: [make]
+        args:
+            - verbose:
+                short: v
+                long: verbose
+        subcommands:
+            - release:
+                args:
+                    # not a conflict, as build overrides the global verbose.
+                    - version:
+                        short: v
+    - make:
+        long_flag: conf
+        args:
+            - level:
+                short_alias: v
+"#;
+    let wrap: CommandWrap = serde_yaml::from_str(CONFLICT_YAML).unwrap();
+    let conflicts = find_conflicts(&wrap)
+        .into_iter()
+        .map(|c| (c.name, c.first, c.second))
+        .collect::<Vec<_>>();
+    let expected = [
+        ("-c", "args.config.short", "subcommands.build.short_flag"),
+        (
+            "--conf",
+            "args.config.visible_aliases",
+            "subcommands.make.long_flag",
+        ),
+        ("make", "subcommands.build.aliases", "subcommands.make.name"),
+        (
+            "-v",
+            "args.verbose.short",
+            "subcommands.make.args.level.short_aliases",
+        ),
+    ];
+    assert_eq!(
+        conflicts,
+        expected
+            .iter()
+            .map(|(n, f, s)| (n.to_string(), f.to_string(), s.to_string()))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn load_checked_toml() {
+    use crate::load_checked;
+    fn check_err(toml: &str) -> String {
+        load_checked(&mut toml::Deserializer::new(toml))
+            .unwrap_err()
+            .to_string()
+    }
+
+    let ok = r#"
+name = "app"
+[args]
+input = { index = 1 }
+output = { index = 2 }
+"#;
+    assert!(load_checked(&mut toml::Deserializer::new(ok)).is_ok());
+
+    let err = check_err(
+        r#"
+name = "app"
+[subcommands.build.args]
+flag = { long = "flag", last = true, takes_value = true }
+"#,
+    );
+    assert!(
+        err.starts_with("subcommands.build.args.flag.last: "),
+        "{}",
+        err
+    );
+
+    let err = check_err(
+        r#"
+name = "app"
+[args]
+input = { index = 1 }
+output = { index = 1 }
+"#,
+    );
+    assert!(err.starts_with("args.input.index: "), "{}", err);
+
+    let err = check_err(
+        r#"
+name = "app"
+[args]
+verbose = { short = "v", requires = "missing" }
+"#,
+    );
+    assert!(err.starts_with("args.verbose.requires: "), "{}", err);
+
+    // The key is found out of the ids, which may contain it.
+    let err = check_err(
+        r#"
+name = "app"
+[args]
+indexer = { long = "indexer", last = true, takes_value = true }
+"#,
+    );
+    assert!(err.starts_with("args.indexer.last: "), "{}", err);
+}
+
+#[test]
+fn load_checked_flags_toml() {
+    use crate::load_checked;
+    fn check_err(toml: &str) -> String {
+        load_checked(&mut toml::Deserializer::new(toml))
+            .unwrap_err()
+            .to_string()
+    }
+
+    // The flags of the generated `help` and `version` can be taken.
+    let ok = r#"
+name = "app"
+version = "1.0"
+[args]
+host = { short = "h", long = "host" }
+verbose = { short = "V", long = "version" }
+config = { long = "config", global = true }
+[subcommands.sub.args]
+flag = { short = "f" }
+config = { short = "c", long = "config" }
+"#;
+    assert!(load_checked(&mut toml::Deserializer::new(ok)).is_ok());
+
+    let err = check_err(
+        r#"
+name = "app"
+[args]
+verbose = { short = "v" }
+very = { short_alias = "v" }
+"#,
+    );
+    assert_eq!(
+        err,
+        "args.very.short_aliases: `-v` is taken by both args.verbose.short and args.very.short_aliases"
+    );
+    let err = check_err(
+        r#"
+name = "app"
+[subcommands.build.args]
+quiet = { long = "quiet", alias = "silent" }
+silent = { long = "silent" }
+"#,
+    );
+    assert!(
+        err.starts_with("subcommands.build.args.silent.long: `--silent` is taken"),
+        "{}",
+        err
+    );
+    let err = check_err(
+        r#"
+name = "app"
+[args]
+config = { long = "config", global = true }
+[subcommands.build.args]
+conf = { long = "config" }
+"#,
+    );
+    assert!(
+        err.starts_with("subcommands.build.args.conf.long: "),
+        "{}",
+        err
+    );
+    // An arg of the id of a generated arg replaces it, and is checked as the other args.
+    let err = check_err(
+        r#"
+name = "app"
+[args]
+help = { short = "x", long = "help" }
+extra = { short = "x" }
+"#,
+    );
+    assert!(err.starts_with("args.extra.short: `-x`"), "{}", err);
+    // The generated `--version` is kept with a version, so it clashes with an alias.
+    let err = check_err(
+        r#"
+name = "app"
+version = "1.0"
+[args]
+verbose = { long = "verbose", alias = "version" }
+"#,
+    );
+    assert!(
+        err.starts_with("args.verbose.aliases: `--version`"),
+        "{}",
+        err
+    );
+    // The later arg replaces the former with override-arg.
+    #[cfg(not(feature = "override-arg"))]
+    {
+        let err = check_err(
+            r#"
+name = "app"
+args = [{ input = {} }, { input = {} }]
+"#,
+        );
+        assert!(err.starts_with("args.input.id: "), "{}", err);
+    }
+}
+
+/// Random configs, built from the keys and values which reach the builders of clap.
+mod arbitrary {
+    use serde_json::{Map, Value};
+
+    const KEYS: &[&str] = &[
+        "name",
+        "about",
+        "version",
+        "args",
+        "args_map",
+        "subcommands",
+        "subcommands_map",
+        "groups",
+        "arg",
+        "group",
+        "id",
+        "short",
+        "long",
+        "alias",
+        "aliases",
+        "short_alias",
+        "short_aliases",
+        "visible_short_alias",
+        "visible_short_aliases",
+        "short_flag",
+        "short_flag_alias",
+        "visible_short_flag_aliases",
+        "long_flag",
+        "long_flag_alias",
+        "value_delimiter",
+        "value_parser",
+        "type",
+        "min",
+        "max",
+        "max_inclusive",
+        "action",
+        "arg_action",
+        "takes_value",
+        "multiple_values",
+        "multiple_occurrences",
+        "index",
+        "last",
+        "global",
+        "required",
+        "requires",
+        "requires_if",
+        "conflicts_with",
+        "default_value",
+        "default_value_if",
+        "required_if_eq",
+        "possible_value",
+        "possible_values",
+        "value_hint",
+        "value_name",
+        "value_names",
+        "number_of_values",
+        "setting",
+        "settings",
+        "global_setting",
+        "global_settings",
+        "color",
+        "env",
+        "env_prefix",
+        "auto_env",
+        "auto_long",
+        "auto_value_name",
+        "help_heading",
+        "next_help_heading",
+        "from_usage",
+        "multiple",
+        "hidden",
+        "display_order",
+        "term_width",
+        "x-owner",
+        "owner",
+        "-v, --verbose 'Sets verbosity'",
+        "<FILE>",
+        "--",
+        "-",
+        "",
+    ];
+
+    const STRINGS: &[&str] = &[
+        "-",
+        "--",
+        "",
+        "a",
+        "v",
+        "app",
+        "verbose",
+        "i64",
+        "u8",
+        "set",
+        "count",
+        "append",
+        "file_path",
+        "ArgRequiredElseHelp",
+        "always",
+        "-c, --config <FILE>",
+        "<IN> <OUT>",
+        "'unclosed",
+        "é",
+        "\u{0}",
+    ];
+
+    /// Xorshift, to be reproducible without dependencies.
+    pub(super) struct Rng(pub(super) u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        pub(super) fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        pub(super) fn value(&mut self, depth: usize) -> Value {
+            match self.below(if depth == 0 { 6 } else { 8 }) {
+                0 => Value::Null,
+                1 => Value::Bool(self.below(2) == 0),
+                2 => [0, 1, -1, 255, 256, i64::MIN, i64::MAX][self.below(7)].into(),
+                3 => [0.5, -1.0, 1e300][self.below(3)].into(),
+                4 | 5 => STRINGS[self.below(STRINGS.len())].into(),
+                6 => Value::Array((0..self.below(4)).map(|_| self.value(depth - 1)).collect()),
+                _ => Value::Object(self.map(depth - 1)),
+            }
+        }
+
+        pub(super) fn map(&mut self, depth: usize) -> Map<String, Value> {
+            (0..self.below(6))
+                .map(|_| {
+                    let key = KEYS[self.below(KEYS.len())];
+                    (key.to_owned(), self.value_of(key, depth))
+                })
+                .collect()
+        }
+
+        /// Mostly the maps of the named maps for the keys like `args`, to reach the keys inside.
+        fn value_of(&mut self, key: &str, depth: usize) -> Value {
+            let named = matches!(
+                key,
+                "args" | "args_map" | "subcommands" | "subcommands_map" | "groups"
+            );
+            if !named || depth == 0 || self.below(4) == 0 {
+                return self.value(depth);
+            }
+            let list = self.below(2) == 0;
+            let mut named = || {
+                let name = STRINGS[self.below(STRINGS.len())].to_owned();
+                (name, Value::Object(self.map(depth - 1)))
+            };
+            if !list {
+                Value::Object((0..3).map(|_| named()).collect())
+            } else {
+                Value::Array(
+                    (0..3)
+                        .map(|_| Value::Object([named()].into_iter().collect()))
+                        .collect(),
+                )
+            }
+        }
+    }
+}
+
+#[test]
+fn arbitrary_configs_never_panic() {
+    use crate::{Duplicates, LoadOptions, UnknownKeys};
+    let options = LoadOptions::new()
+        .unknown_keys(UnknownKeys::Warn)
+        .duplicates(Duplicates::Warn);
+    let mut rng = arbitrary::Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..3000 {
+        let mut map = rng.map(4);
+        if rng.below(2) == 0 {
+            map.insert("name".to_owned(), "app".into());
+        }
+        let value = serde_json::Value::Object(map);
+
+        let json = value.to_string();
+        if let Ok(app) = crate::load(&mut serde_json::Deserializer::from_str(&json)) {
+            crate::find_conflicts(&app);
+        }
+        let _ = options.load(&mut serde_json::Deserializer::from_str(&json));
+
+        let yaml = serde_yaml::to_string(&value).unwrap();
+        let _ = crate::load(serde_yaml::Deserializer::from_str(&yaml));
+        let _ = options.load(serde_yaml::Deserializer::from_str(&yaml));
+        #[cfg(feature = "yaml")]
+        #[allow(deprecated)]
+        if let Ok(docs) = yaml_rust::YamlLoader::load_from_str(&yaml) {
+            for doc in &docs {
+                let _ = crate::yaml_to_app(doc);
+                let _ = crate::compat::clap2_yaml(doc).to_app();
+            }
+        }
+        // Neither is kdl, but both have the braces and strings of it.
+        #[cfg(feature = "kdl")]
+        for src in [&json, &yaml] {
+            if let Ok(doc) = crate::Document::from_kdl(src) {
+                let _ = doc.command();
+            }
+        }
+    }
+
+    // Numbers of kdl at the edges of i64, and signs where they are not allowed.
+    #[cfg(feature = "kdl")]
+    for (number, valid) in [
+        ("-0x8000000000000000", true),
+        ("0x7fffffffffffffff", true),
+        ("-9223372036854775808", true),
+        ("-0x-8000000000000000", false),
+        ("-0x8000000000000001", false),
+        ("0x8000000000000000", false),
+        ("0x-5", false),
+        ("+0o+7", false),
+        ("0b", false),
+        ("-0xffffffffffffffff", false),
+    ] {
+        let src = format!("name \"app\"\nterm_width {}\n", number);
+        let doc = crate::Document::from_kdl(&src);
+        assert_eq!(doc.is_ok(), valid, "{}", number);
+        let _ = crate::from_str_with_format(&src, crate::Format::Kdl);
+        if let Ok(doc) = doc {
+            let _ = doc.command();
+        }
+    }
+}
+
+#[test]
+fn limits_json() {
+    use crate::LoadOptions;
+    fn load_err(options: LoadOptions, json: &str) -> String {
+        options
+            .load(&mut serde_json::Deserializer::from_str(json))
+            .unwrap_err()
+            .to_string()
+    }
+    const NESTED_JSON: &str = r#"{
+        "name": "app",
+        "subcommands": [{"a": {"subcommands": [{"b": {"subcommands": [{"c": {}}]}}]}}]
+    }"#;
+    assert!(LoadOptions::new()
+        .max_depth(3)
+        .load(&mut serde_json::Deserializer::from_str(NESTED_JSON))
+        .is_ok());
+    let err = load_err(LoadOptions::new().max_depth(2), NESTED_JSON);
+    assert!(
+        err.starts_with("subcommands.a.subcommands.b: subcommand `c` is nested deeper"),
+        "{}",
+        err
+    );
+    let err = load_err(LoadOptions::new().max_nodes(2), NESTED_JSON);
+    assert!(
+        err.contains("subcommand `c` is over the limit of 2"),
+        "{}",
+        err
+    );
+
+    let args = (0..10)
+        .map(|i| format!(r#""--arg{i}""#))
+        .collect::<Vec<_>>()
+        .join(",");
+    let many_args = format!(r#"{{"name": "app", "args": [{}]}}"#, args);
+    assert!(LoadOptions::new()
+        .max_args(10)
+        .load(&mut serde_json::Deserializer::from_str(&many_args))
+        .is_ok());
+    let err = load_err(LoadOptions::new().max_args(9), &many_args);
+    assert!(
+        err.contains("arg `arg9` is over the limit of 9 args"),
+        "{}",
+        err
+    );
+
+    let long_about = format!(r#"{{"name": "app", "about": "{}"}}"#, "x".repeat(100));
+    let err = load_err(LoadOptions::new().max_string_len(64), &long_about);
+    assert!(err.contains("string of 100 bytes"), "{}", err);
+    let long_alias =
+        r#"{"name": "app", "args": [{"verbose": {"aliases": ["v", "very-long-alias"]}}]}"#;
+    let err = load_err(LoadOptions::new().max_string_len(8), long_alias);
+    assert!(
+        err.starts_with("args.verbose: string of 15 bytes"),
+        "{}",
+        err
+    );
+    // Strings over the limit fail before they are kept in the store.
+    let strings = crate::StringStore::new();
+    let made = [
+        r#"{"name": "app", "about": "an\tescaped about"}"#,
+        r#"{"name": "app", "version": 123456789}"#,
+    ];
+    for (max, kept) in [(8, 0), (16, 2)] {
+        for src in made {
+            let loaded = LoadOptions::new()
+                .max_string_len(max)
+                .load_in(&mut serde_json::Deserializer::from_str(src), &strings);
+            assert_eq!(loaded.is_ok(), kept > 0, "{}", src);
+        }
+        assert_eq!(strings.len(), kept);
+    }
+
+    // The values of `x-` keys and the keys of handlers are limited as well.
+    struct Owner;
+    impl crate::KeyHandler for Owner {
+        fn command_key(
+            &self,
+            key: &str,
+            _: &crate::MetaValue,
+            _: &mut clap::Command,
+            _: Option<&crate::StringStore>,
+        ) -> Result<bool, String> {
+            Ok(key == "owner")
+        }
+    }
+    let long_meta = r#"{"name": "app", "x-tags": ["ok", {"note": "a very long note"}]}"#;
+    let err = load_err(LoadOptions::new().max_string_len(8), long_meta);
+    assert!(
+        err.starts_with("string of 16 bytes is longer than the limit 8"),
+        "{}",
+        err
+    );
+    let long_key = r#"{"name": "app", "x-tags": {"a very long key": 1}}"#;
+    let err = load_err(LoadOptions::new().max_string_len(8), long_key);
+    assert!(
+        err.starts_with("string of 15 bytes is longer than the limit 8"),
+        "{}",
+        err
+    );
+    let long_owner = r#"{"name": "app", "owner": "a very long owner"}"#;
+    let err = load_err(
+        LoadOptions::new().max_string_len(8).key_handler(Owner),
+        long_owner,
+    );
+    assert!(
+        err.starts_with("string of 17 bytes is longer than the limit 8"),
+        "{}",
+        err
+    );
+    assert!(LoadOptions::new()
+        .max_string_len(17)
+        .key_handler(Owner)
+        .load(&mut serde_json::Deserializer::from_str(long_owner))
+        .is_ok());
+
+    let deep_meta = r#"{"name": "app", "args": [{"a": {"x-deep": [[[1]]]}}]}"#;
+    assert!(LoadOptions::new()
+        .max_depth(3)
+        .load(&mut serde_json::Deserializer::from_str(deep_meta))
+        .is_ok());
+    let err = load_err(LoadOptions::new().max_depth(2), deep_meta);
+    assert!(
+        err.starts_with("args.a: value of `x-deep` is nested deeper than the limit 2"),
+        "{}",
+        err
+    );
+    let many_meta = r#"{"name": "app", "x-list": [1, 2, 3], "x-map": {"a": 1, "b": 2}}"#;
+    assert!(LoadOptions::new()
+        .max_nodes(5)
+        .load(&mut serde_json::Deserializer::from_str(many_meta))
+        .is_ok());
+    let err = load_err(LoadOptions::new().max_nodes(4), many_meta);
+    assert!(
+        err.starts_with("item of `x-map` is over the limit of 4 nodes"),
+        "{}",
+        err
+    );
+}
+
+#[cfg(all(feature = "toml", feature = "serde_json", feature = "serde_yaml"))]
+#[test]
+fn located_errors() {
+    use crate::{from_json_str, from_toml_str, from_yaml_str, Location};
+    const CLAP_TOML: &str = "name = \"app\"\n\n[args.verbose]\nshort = \"v\"\nshrot = \"x\"\n";
+    const CLAP_YAML: &str = "name: app\nargs:\n  - verbose:\n      short: v\n      shrot: x\n";
+    const CLAP_JSON: &str = "{\n  \"name\": \"app\",\n  \"args\": [\n    {\"verbose\": {\"short\": \"v\", \"shrot\": \"x\"}}\n  ]\n}";
+
+    let err = from_toml_str(CLAP_TOML).unwrap_err();
+    assert_eq!(err.location, Some(Location { line: 5, column: 1 }));
+    assert!(err.message.starts_with("unknown field `shrot`"), "{}", err);
+    assert!(format!("{:#}", err).ends_with("\n  |\n5 | shrot = \"x\"\n  | ^"));
+
+    let err = from_yaml_str(CLAP_YAML).unwrap_err();
+    assert_eq!(err.location, Some(Location { line: 5, column: 7 }));
+    assert!(format!("{:#}", err).ends_with("\n5 |       shrot: x\n  |       ^"));
+
+    // Json points after the key, which is moved to the opening quote.
+    let err = from_json_str(CLAP_JSON).unwrap_err();
+    assert_eq!(
+        err.location,
+        Some(Location {
+            line: 4,
+            column: 32
+        })
+    );
+
+    let err = from_json_str("{\"name\": \"app\"} x").unwrap_err();
+    assert_eq!(err.to_string(), "1:17: trailing characters");
+
+    assert!(from_toml_str("name = \"app\"\n[args]\nverbose = { short = \"v\" }\n").is_ok());
+}
+
+#[cfg(all(feature = "toml", feature = "serde_json", feature = "serde_yaml"))]
+#[test]
+fn from_path_sniffs_format() {
+    use crate::{from_path, Format};
+    let dir = std::env::temp_dir().join(format!("clap-serde-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cases = [
+        (
+            "cli.toml",
+            "name = \"app\"\nabout = \"toml\"\n",
+            Format::Toml,
+        ),
+        ("cli.yml", "name: app\nabout: yaml\n", Format::Yaml),
+        (
+            "cli.conf",
+            "# yaml\nname: app\nabout: 'a: b'\n",
+            Format::Yaml,
+        ),
+        (
+            "cli",
+            "name = \"app\"\n[args.verbose]\nshort = \"v\"\n",
+            Format::Toml,
+        ),
+        (
+            "cli.cfg",
+            "{\"name\": \"app\", \"about\": \"json\"}",
+            Format::Json,
+        ),
+    ];
+    for (name, text, format) in cases {
+        let path = dir.join(name);
+        std::fs::write(&path, text).unwrap();
+        let source = from_path(&path).unwrap();
+        assert_eq!(source.format(), format, "{}", name);
+        assert!(source.command().is_ok(), "{}", name);
+    }
+
+    let path = dir.join("bad.toml");
+    std::fs::write(&path, "name = \"app\"\nshrot = \"a\"\n").unwrap();
+    let err = from_path(&path).unwrap().command().unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with(&format!("{}:2:1: unknown field `shrot`", path.display())));
+    let err = from_path(dir.join("missing.toml")).unwrap_err();
+    assert!(err.location.is_none());
+    assert!(err.file.is_some());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(all(feature = "ron", feature = "json5", feature = "kdl"))]
+#[test]
+fn from_path_document_formats() {
+    use crate::{from_path, from_str_with_format, Format, LoadOptions, Source, UnknownKeys};
+    let dir = std::env::temp_dir().join(format!("clap-serde-doc-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cases = [
+        (
+            "cli.ron",
+            "{\"name\": \"app\", \"about\": \"ron\"}",
+            Format::Ron,
+        ),
+        ("cli.json5", "{name: 'app', about: 'json5',}", Format::Json5),
+        ("cli.kdl", "name \"app\"\nabout \"kdl\"\n", Format::Kdl),
+    ];
+    for (name, text, format) in cases {
+        let path = dir.join(name);
+        std::fs::write(&path, text).unwrap();
+        let source = from_path(&path).unwrap();
+        assert_eq!(source.format(), format, "{}", name);
+        let app = source.command().unwrap();
+        assert_eq!(app.get_about(), name.strip_prefix("cli."), "{}", name);
+        // The strings of json5 and kdl are not borrowed from the text.
+        match from_str_with_format(text, format) {
+            Ok(app) => assert_eq!(app.get_about(), Some("ron")),
+            Err(e) => assert!(e.message.contains("need a `StringStore`"), "{}", e),
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // The strings made from the document are kept in the source.
+    let source = Source::new(
+        "name app\nowner someone\narg \"jobs\" default_value=8\n",
+        Format::Kdl,
+    );
+    let options = LoadOptions::new().unknown_keys(UnknownKeys::Warn);
+    let (app, diagnostics) = source.command_with_options(&options).unwrap();
+    assert_eq!(app.get_name(), "app");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].key, "owner");
+    let err = Source::new("{name: 'app',\n shrot: 'a'}", Format::Json5)
+        .command()
+        .unwrap_err();
+    assert!(err.message.contains("shrot"), "{}", err);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn loaded_command_owns_source() {
+    use crate::{Format, LoadedCommand, Source};
+    let text =
+        String::from("name = \"app\"\n[args.verbose]\nshort = \"v\"\nhelp = \"more output\"\n");
+    let loaded = LoadedCommand::new(Source::new(text, Format::Toml)).unwrap();
+    // Moving it keeps the strings the command borrows.
+    let loaded = vec![loaded].pop().unwrap();
+    let help = loaded.with_command(|app| {
+        let verbose = app.get_arguments().find(|a| a.get_id() == "verbose");
+        verbose.unwrap().get_help().map(str::to_owned)
+    });
+    assert_eq!(help.unwrap().as_deref(), Some("more output"));
+    let m = loaded.get_matches_from(["app", "-v"]).unwrap();
+    assert!(m.is_present("verbose"));
+    let app = loaded.command().unwrap();
+    assert!(app.try_get_matches_from(["app", "-x"]).is_err());
+    assert_eq!(loaded.source().format(), Format::Toml);
+
+    let err = LoadedCommand::new(Source::new("name = 1\nshrot = 2\n", Format::Toml)).unwrap_err();
+    assert_eq!(err.location.map(|l| l.line), Some(2));
+}
+
+#[cfg(all(feature = "clap4", feature = "toml"))]
+#[test]
+fn load_clap4() {
+    const CLAP_TOML: &str = r#"
+name = "app"
+[args]
+verbose = { short = "v", action = "count" }
+level = { long = "level", possible_values = ["low", "high"], num_args = 1 }
+input = { required = true }
+files = { long = "files", num_args = "1..=2" }
+[subcommands.sub]
+about = "sub command"
+args = { flag = { long = "flag", action = "set_true" } }
+"#;
+    let app = crate::clap4::load(&mut toml::Deserializer::new(CLAP_TOML)).unwrap();
+    let m = app
+        .clone()
+        .try_get_matches_from([
+            "app", "-vv", "--level", "high", "--files", "x", "y", "a", "sub", "--flag",
+        ])
+        .unwrap();
+    assert_eq!(m.get_count("verbose"), 2);
+    assert_eq!(
+        m.get_one::<String>("level").map(String::as_str),
+        Some("high")
+    );
+    assert!(m.subcommand_matches("sub").unwrap().get_flag("flag"));
+    assert!(app
+        .clone()
+        .try_get_matches_from(["app", "--level", "mid", "a"])
+        .is_err());
+    assert!(app.try_get_matches_from(["app", "a", "b"]).is_err());
+
+    let err = crate::clap4::load(&mut toml::Deserializer::new(
+        "name = \"app\"\n[args.a]\ntakes_value = true\n",
+    ))
+    .unwrap_err();
+    assert!(err.to_string().contains("removed in clap 4"));
+    assert!(crate::clap4::load(&mut toml::Deserializer::new(
+        "name = \"app\"\n[args.a]\nnum_args = \"3..1\"\n"
+    ))
+    .is_err());
+}
+
+#[cfg(all(feature = "clap4", feature = "toml"))]
+#[test]
+fn clap4_groups() {
+    const CLAP_TOML: &str = r#"
+name = "app"
+[args]
+json = { long = "json", action = "set_true" }
+yaml = { long = "yaml", action = "set_true" }
+out = { long = "out", num_args = 1 }
+[groups.format]
+args = ["json", "yaml"]
+required = true
+requires = "out"
+[groups.output]
+arg = "out"
+conflicts_with = "yaml"
+"#;
+    let app = crate::clap4::load(&mut toml::Deserializer::new(CLAP_TOML)).unwrap();
+    let mut format = app
+        .get_groups()
+        .find(|g| g.get_id() == "format")
+        .cloned()
+        .unwrap();
+    assert_eq!(
+        format.get_args().map(|a| a.as_str()).collect::<Vec<_>>(),
+        ["json", "yaml"]
+    );
+    assert!(format.is_required_set() && !format.is_multiple());
+    let m = app
+        .clone()
+        .try_get_matches_from(["app", "--json", "--out", "x"])
+        .unwrap();
+    assert!(m.get_flag("json"));
+    for args in [
+        &["app"][..],
+        &["app", "--json"],
+        &["app", "--json", "--yaml", "--out", "x"],
+        &["app", "--yaml", "--out", "x"],
+    ] {
+        assert!(
+            app.clone().try_get_matches_from(args).is_err(),
+            "{:?}",
+            args
+        );
+    }
+
+    let (app, diagnostics) = crate::clap4::load_with_options(
+        &mut toml::Deserializer::new(
+            "name = \"app\"\n[args.a]\n[groups.g]\nname = \"grp\"\nargs = [\"a\"]\n",
+        ),
+        &crate::LoadOptions::new(),
+    )
+    .unwrap();
+    assert!(app.get_groups().any(|g| g.get_id() == "grp"));
+    assert_eq!(diagnostics[0].path, "groups.g.name");
+    assert_eq!(diagnostics[0].replacement, Some("id"));
+}
+
+#[cfg(all(feature = "clap4", feature = "toml"))]
+#[test]
+fn clap4_removed_keys() {
+    fn load_err(toml: &str) -> String {
+        crate::clap4::load(&mut toml::Deserializer::new(toml))
+            .unwrap_err()
+            .to_string()
+    }
+    for (key, instead) in [
+        ("takes_value", "use `action` or `num_args` instead"),
+        ("multiple_values", "use `num_args` instead"),
+        ("min_values", "use `num_args` instead"),
+        ("max_values", "use `num_args` instead"),
+        ("number_of_values", "use `num_args` instead"),
+        (
+            "multiple_occurrences",
+            "use `action` of `append` or `count` instead",
+        ),
+        ("max_occurrences", "removed in clap 4"),
+        (
+            "forbid_empty_values",
+            "use `value_parser` of `non_empty_string` instead",
+        ),
+        (
+            "allow_invalid_utf8",
+            "use `value_parser` of `os_string` instead",
+        ),
+        ("use_value_delimiter", "use `value_delimiter` instead"),
+        ("require_value_delimiter", "use `value_delimiter` instead"),
+    ] {
+        let err = load_err(&format!("name = \"app\"\n[args.a]\n{} = true\n", key));
+        assert!(
+            err.contains(&format!("not supported key `{}`", key)) && err.contains(instead),
+            "{}",
+            err
+        );
+    }
+    for (key, value, instead) in [
+        (
+            "setting",
+            "\"hidden\"",
+            "use the key of each setting instead",
+        ),
+        (
+            "settings",
+            "[\"hidden\"]",
+            "use the key of each setting instead",
+        ),
+        (
+            "global_setting",
+            "\"hidden\"",
+            "use the key of each setting instead",
+        ),
+        (
+            "global_settings",
+            "[\"hidden\"]",
+            "use the key of each setting instead",
+        ),
+        (
+            "allow_invalid_utf8_for_external_subcommands",
+            "true",
+            "use `external_subcommand_value_parser` of clap instead",
+        ),
+        (
+            "dont_collapse_args_in_usage",
+            "true",
+            "removed in clap 4, as it is the default",
+        ),
+        ("trailing_var_arg", "true", "moved to args in clap 4"),
+    ] {
+        let err = load_err(&format!("name = \"app\"\n{} = {}\n", key, value));
+        assert!(
+            err.contains(&format!("not supported key `{}`", key)) && err.contains(instead),
+            "{}",
+            err
+        );
+    }
+    // The keys are removed in subcommands as well, and kept where clap 4 still has them.
+    let err = load_err("name = \"app\"\n[subcommands.sub.args.a]\nmultiple_values = true\n");
+    assert!(
+        err.contains("not supported key `multiple_values`"),
+        "{}",
+        err
+    );
+    let app = crate::clap4::load(&mut toml::Deserializer::new(
+        "name = \"app\"\n[args.rest]\nnum_args = \"1..\"\ntrailing_var_arg = true\n",
+    ))
+    .unwrap();
+    let rest = app.get_arguments().find(|a| a.get_id() == "rest").unwrap();
+    assert!(rest.is_trailing_var_arg_set());
+}
+
+#[cfg(all(feature = "clap4", feature = "toml"))]
+#[test]
+fn clap4_subcommand_arrays() {
+    const CLAP_TOML: &str = r#"
+name = "app"
+args = [{ verbose = { short = "v", action = "count", global = true } }]
+subcommands = [
+    { build = { about = "build it", args = [{ release = { long = "release", action = "set_true" } }] } },
+    { test = { subcommands_map = { unit = { args_map = { filter = { index = 1 } } } } } },
+]
+"#;
+    let app = crate::clap4::load(&mut toml::Deserializer::new(CLAP_TOML)).unwrap();
+    assert_eq!(
+        app.get_subcommands()
+            .map(|c| c.get_name())
+            .collect::<Vec<_>>(),
+        ["build", "test"]
+    );
+    let build = app.find_subcommand("build").unwrap();
+    assert_eq!(
+        build.get_about().map(|a| a.to_string()).as_deref(),
+        Some("build it")
+    );
+    let m = app
+        .clone()
+        .try_get_matches_from(["app", "-v", "build", "--release"])
+        .unwrap();
+    assert_eq!(m.get_count("verbose"), 1);
+    assert!(m.subcommand_matches("build").unwrap().get_flag("release"));
+    let m = app
+        .try_get_matches_from(["app", "test", "unit", "parser"])
+        .unwrap();
+    let unit = m
+        .subcommand_matches("test")
+        .and_then(|m| m.subcommand_matches("unit"))
+        .unwrap();
+    assert_eq!(
+        unit.get_one::<String>("filter").map(String::as_str),
+        Some("parser")
+    );
+
+    let err = crate::clap4::load(&mut toml::Deserializer::new(
+        "name = \"app\"\nsubcommands = [{}]\n",
+    ))
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("missing command in subcommand"),
+        "{}",
+        err
+    );
+}
+
+#[cfg(all(feature = "clap4", feature = "toml"))]
+#[test]
+fn clap4_metadata_and_key_handlers() {
+    use crate::{KeyHandler, LoadOptions, MetaValue};
+
+    struct Stage;
+    impl KeyHandler for Stage {
+        fn clap4_command_key(
+            &self,
+            key: &str,
+            value: &MetaValue,
+            command: &mut clap4::Command,
+        ) -> Result<bool, String> {
+            if key != "stage" {
+                return Ok(false);
+            }
+            let beta = value.as_str() == Some("beta");
+            *command = std::mem::take(command).hide(beta);
+            Ok(true)
+        }
+
+        fn clap4_arg_key(
+            &self,
+            key: &str,
+            value: &MetaValue,
+            arg: &mut clap4::Arg,
+        ) -> Result<bool, String> {
+            if key != "note" {
+                return Ok(false);
+            }
+            let note = value.as_str().ok_or("note is not a string")?;
+            *arg = std::mem::take(arg).help(format!("{} (note)", note));
+            Ok(true)
+        }
+    }
+
+    const CLAP_TOML: &str = r#"
+name = "app"
+x-docs = "https://example.com"
+[args]
+verbose = { short = "v", note = "more output", x-since = "1.2" }
+[subcommands.preview]
+stage = "beta"
+args = { fast = { long = "fast", x-owner = "perf" } }
+"#;
+    let (wrap, diagnostics) = crate::clap4::load_with_options(
+        &mut toml::Deserializer::new(CLAP_TOML),
+        &LoadOptions::new().key_handler(Stage),
+    )
+    .unwrap();
+    assert!(diagnostics.is_empty());
+    let verbose = wrap.get_arguments().find(|a| a.get_id() == "verbose");
+    let help = verbose.unwrap().get_help().map(|h| h.to_string());
+    assert_eq!(help.as_deref(), Some("more output (note)"));
+    assert!(wrap.find_subcommand("preview").unwrap().is_hide_set());
+
+    let str_of = |m: Option<&crate::Metadata>, name: &str| {
+        m.and_then(|m| m.get(name))
+            .and_then(|v| v.as_str())
+            .map(str::to_owned)
+    };
+    assert_eq!(
+        str_of(wrap.command_metadata(""), "docs").as_deref(),
+        Some("https://example.com")
+    );
+    assert_eq!(
+        str_of(wrap.metadata("", "verbose"), "since").as_deref(),
+        Some("1.2")
+    );
+    assert_eq!(
+        str_of(wrap.metadata("preview", "fast"), "owner").as_deref(),
+        Some("perf")
+    );
+    // `load` keeps the metadata as well.
+    let wrap: crate::clap4::CommandWrap =
+        toml::from_str("name = \"app\"\nargs = { a = { x-owner = \"me\" } }\n").unwrap();
+    assert_eq!(
+        str_of(wrap.metadata("", "a"), "owner").as_deref(),
+        Some("me")
+    );
+
+    // Without the handler, its keys are unknown.
+    let err = crate::clap4::load(&mut toml::Deserializer::new(CLAP_TOML)).unwrap_err();
+    assert!(err.to_string().contains("unknown field `note`"), "{}", err);
+}
+
+#[cfg(all(feature = "clap4", feature = "toml"))]
+#[test]
+fn clap4_diagnostics_and_limits() {
+    use crate::{DiagnosticKind, Duplicates, LoadOptions, Severity, UnknownKeys};
+    fn load(
+        options: &LoadOptions,
+        toml: &str,
+    ) -> Result<(crate::clap4::CommandWrap, Vec<crate::Diagnostic>), toml::de::Error> {
+        crate::clap4::load_with_options(&mut toml::Deserializer::new(toml), options)
+    }
+    const DIAG_TOML: &str = r#"
+name = "app"
+help_heading = "OPTIONS"
+owner = "cli-team"
+x-docs = "https://example.com"
+[args.a]
+name = "apple"
+"#;
+    let options = LoadOptions::new().unknown_keys(UnknownKeys::Warn);
+    let (app, diagnostics) = load(&options, DIAG_TOML).unwrap();
+    assert_eq!(
+        app.get_next_help_heading()
+            .map(|h| h.to_string())
+            .as_deref(),
+        Some("OPTIONS")
+    );
+    assert!(app.get_arguments().any(|a| a.get_id() == "apple"));
+    let summary = diagnostics
+        .iter()
+        .map(|d| (d.path.as_str(), d.kind, d.severity))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        [
+            (
+                "help_heading",
+                DiagnosticKind::Deprecated,
+                Severity::Warning
+            ),
+            ("owner", DiagnosticKind::UnknownKey, Severity::Warning),
+            ("args.a.name", DiagnosticKind::Deprecated, Severity::Warning),
+        ]
+    );
+    let err = load(&LoadOptions::new(), DIAG_TOML).unwrap_err();
+    assert!(err.to_string().contains("unknown field `owner`"), "{}", err);
+
+    const DUP_TOML: &str = r#"
+name = "app"
+args = [{ a = { short = "a" } }, { a = { short = "b" } }]
+"#;
+    let (_, diagnostics) =
+        load(&LoadOptions::new().duplicates(Duplicates::Warn), DUP_TOML).unwrap();
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::Duplicate);
+    assert!(load(&LoadOptions::new().duplicates(Duplicates::Deny), DUP_TOML).is_err());
+
+    const NESTED_TOML: &str = r#"
+name = "app"
+[subcommands.a.subcommands.b.subcommands.c]
+args = { x = {}, y = {} }
+"#;
+    assert!(load(&LoadOptions::new().max_depth(3), NESTED_TOML).is_ok());
+    let err = load(&LoadOptions::new().max_depth(2), NESTED_TOML).unwrap_err();
+    assert!(
+        err.to_string().contains("subcommand `c` is nested deeper"),
+        "{}",
+        err
+    );
+    let err = load(&LoadOptions::new().max_nodes(4), NESTED_TOML).unwrap_err();
+    assert!(
+        err.to_string().contains("arg `y` is over the limit of 4"),
+        "{}",
+        err
+    );
+    let err = load(&LoadOptions::new().max_args(1), NESTED_TOML).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("arg `y` is over the limit of 1 args"),
+        "{}",
+        err
+    );
+    let err = load(
+        &LoadOptions::new().max_string_len(8),
+        "name = \"app\"\nabout = \"a long about\"\n",
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("string of 12 bytes"), "{}", err);
+}
+
+#[cfg(feature = "ron")]
+#[test]
+fn args_ron() {
+    const CLAP_RON: &str = r#"{
+    "name": "app_clap_serde",
+    "args": [
+        { "apple": { "short": 'a', "takes_value": true, "value_parser": { "type": "i64", "min": 0 } } },
+        { "banana": { "short": 'b', "long": "banana", "aliases": ["musa_spp"] } },
+    ],
+    "subcommands": [{ "sub1": { "about": "subcommand_1" } }],
+}"#;
+    let app = crate::from_ron_str(CLAP_RON).expect("parse failed");
+    let m = app
+        .clone()
+        .try_get_matches_from(["app", "-a", "1", "--musa_spp"])
+        .unwrap();
+    assert_eq!(m.get_one::<i64>("apple"), Some(&1));
+    assert!(m.is_present("banana"));
+    assert!(app.find_subcommand("sub1").is_some());
+
+    let err =
+        crate::from_ron_str("{\n    \"name\": \"app\",\n    \"shrot\": \"a\",\n}").unwrap_err();
+    assert_eq!(err.location.map(|l| l.line), Some(3));
+    assert!(err.message.contains("shrot"));
+}
+
+#[cfg(feature = "json5")]
+#[test]
+fn args_json5() {
+    const CLAP_JSON5: &str = r#"{
+    // comments and unquoted keys
+    name: 'app_clap_serde',
+    args: [
+        { apple: { short: 'a', takes_value: true, value_parser: { type: 'i64', min: 0 } } },
+        { banana: { short: 'b', long: 'banana', }, },
+    ],
+    subcommands: [{ sub1: { about: 'subcommand_1' } }],
+}"#;
+    let doc = crate::Document::from_json5(CLAP_JSON5).expect("parse failed");
+    let app = doc.command().expect("load failed");
+    let m = app
+        .clone()
+        .try_get_matches_from(["app", "-a", "1", "--banana"])
+        .unwrap();
+    assert_eq!(m.get_one::<i64>("apple"), Some(&1));
+    assert!(m.is_present("banana"));
+    assert!(app.find_subcommand("sub1").is_some());
+
+    let err = crate::Document::from_json5("{ name: 'app', }}").unwrap_err();
+    assert_eq!(err.location.map(|l| l.line), Some(1));
+}
+
+#[cfg(feature = "kdl")]
+#[test]
+fn args_kdl() {
+    const CLAP_KDL: &str = r##"
+// kdl v1 and v2 values
+name "app_clap_serde"
+about r#"raw "about""#
+arg "apple" short="a" takes_value=true {
+    value_parser type="i64" min=0 /* inclusive */ max=10
+}
+arg "banana" short="b" long="banana" required=#false {
+    aliases "musa_spp" "plantain"
+}
+arg "cherry" long="cherry" action=count
+/-arg "durian" short="d"
+group "fruits" {
+    args { - "apple"; - "banana"; }
+    multiple true
+}
+subcommand "sub1" {
+    about "subcommand_1"
+    arg "flag" long="flag" \
+        help="escaped newline"
+}
+"##;
+    let doc = crate::Document::from_kdl(CLAP_KDL).expect("parse failed");
+    let app = doc.command().expect("load failed");
+    assert_eq!(app.get_about(), Some("raw \"about\""));
+    let m = app
+        .clone()
+        .try_get_matches_from(["app", "-a", "3", "--plantain", "--cherry", "--cherry"])
+        .unwrap();
+    assert_eq!(m.get_one::<i64>("apple"), Some(&3));
+    assert!(m.is_present("banana"));
+    assert_eq!(m.get_one::<u8>("cherry"), Some(&2));
+    assert!(m.is_present("fruits"));
+    assert!(app
+        .clone()
+        .try_get_matches_from(["app", "-a", "11"])
+        .is_err());
+    assert!(app.clone().try_get_matches_from(["app", "-d"]).is_err());
+    let sub = app.find_subcommand("sub1").unwrap();
+    assert_eq!(sub.get_about(), Some("subcommand_1"));
+    let flag = sub.get_arguments().find(|a| a.get_id() == "flag").unwrap();
+    assert_eq!(flag.get_help(), Some("escaped newline"));
+
+    // The map shape of `args` works as well.
+    let doc = crate::Document::from_kdl("name app\nargs {\n    apple short=a\n}\n").unwrap();
+    let app = doc.command().unwrap();
+    assert!(app.try_get_matches_from(["app", "-a"]).is_ok());
+
+    let err = crate::Document::from_kdl("name \"app\"\narg \"a\" {\n    shrot \"a\"\n}\n")
+        .unwrap()
+        .command()
+        .unwrap_err();
+    assert_eq!(
+        err.location.map(|l| (l.line, l.column)),
+        Some((3, 5)),
+        "{}",
+        err
+    );
+    let err = crate::Document::from_kdl("name \"app\n").unwrap_err();
+    assert_eq!(err.location.map(|l| (l.line, l.column)), Some((1, 6)));
+    assert!(crate::Document::from_kdl("name \"app\" {").is_err());
+    assert!(crate::Document::from_kdl("arg short=\"a\"").is_err());
+}
+
+#[cfg(feature = "kdl")]
+#[test]
+fn kdl_grammar() {
+    use crate::document::Value::{self, *};
+
+    /// Message of an error, with its line and column.
+    type Located = (String, (usize, usize));
+    /// The value of each node, or the error.
+    fn parse(src: &str) -> Result<Vec<(String, Value)>, Located> {
+        match crate::kdl::parse(src) {
+            Ok(Map(entries)) => Ok(entries.into_iter().map(|e| (e.key, e.value)).collect()),
+            Ok(v) => panic!("not a map: {:?}", v),
+            Err(e) => Err((e.message, e.location.map(|l| (l.line, l.column)).unwrap())),
+        }
+    }
+    /// The value of the only node, without the locations.
+    fn one(src: &str) -> Value {
+        fn plain(v: Value) -> Value {
+            match v {
+                Seq(items) => Seq(items.into_iter().map(plain).collect()),
+                Map(entries) => map(entries.into_iter().map(|e| (e.key, plain(e.value)))),
+                v => v,
+            }
+        }
+        let mut nodes = parse(src).unwrap_or_else(|e| panic!("{}: {:?}", src, e));
+        assert_eq!(nodes.len(), 1, "{}", src);
+        plain(nodes.remove(0).1)
+    }
+    fn str(s: &str) -> Value {
+        Str(s.to_owned())
+    }
+    fn map<K: Into<String>>(entries: impl IntoIterator<Item = (K, Value)>) -> Value {
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| crate::document::Entry {
+                key: key.into(),
+                location: None,
+                value,
+            });
+        Map(entries.collect())
+    }
+
+    // raw strings of v1 and v2, which keep the escapes and quotes
+    assert_eq!(one(r#"n r"a\nb""#), str(r"a\nb"));
+    assert_eq!(one(r##"n r#"say "hi""#"##), str(r#"say "hi""#));
+    assert_eq!(one(r##"n #"say "hi""#"##), str(r#"say "hi""#));
+    assert_eq!(one(r###"n ##"a"#b"##"###), str(r##"a"#b"##));
+    assert_eq!(one(r##"#"raw name"# 1"##), Int(1));
+    assert_eq!(one("n r\"two\nlines\""), str("two\nlines"));
+    assert_eq!(parse("a r\"x\ny\"\nb 1\nc \"").unwrap_err().1, (4, 3));
+    assert_eq!(
+        parse("n r#\"a\"").unwrap_err(),
+        ("unclosed string".to_owned(), (1, 3))
+    );
+    // identifiers starting with `r` are not raw strings
+    assert_eq!(one("n r"), str("r"));
+    assert_eq!(one("n required"), str("required"));
+
+    // escapes, with `\/` of v1 and `\s` and the escaped spaces of v2
+    assert_eq!(
+        one(r#"n "\n\r\t\b\f\"\\\/\s""#),
+        str("\n\r\t\u{08}\u{0C}\"\\/ ")
+    );
+    assert_eq!(one(r#"n "\u{1F600}\u{e9}""#), str("\u{1F600}\u{e9}"));
+    assert_eq!(one("n \"a\\   \n\t  b\""), str("ab"));
+    assert_eq!(
+        parse(r#"n "\q""#).unwrap_err().0,
+        "invalid escape in string"
+    );
+    for bad in [
+        r#"n "\u{}""#,
+        r#"n "\u{110000}""#,
+        r#"n "\u{D800}""#,
+        r#"n "\u{1234567}""#,
+    ] {
+        assert_eq!(
+            parse(bad).unwrap_err().0,
+            "invalid unicode escape",
+            "{}",
+            bad
+        );
+    }
+    assert_eq!(
+        parse("n \"\"\"\n  text\n  \"\"\"").unwrap_err().0,
+        "multi-line strings are not supported"
+    );
+
+    // slashdash of nodes, arguments, properties and children
+    assert_eq!(
+        parse("/-a 1\nb 2\n/- c {\n d\n}").unwrap(),
+        [("b".to_owned(), Int(2))]
+    );
+    assert_eq!(one("n /-1 2"), Int(2));
+    assert_eq!(one("n /-k=1 k2=2"), map([("k2", Int(2))]));
+    assert_eq!(one("n /-{ a 1; }"), Bool(true));
+    assert_eq!(one("n /-{ a 1; } { b 2; }"), map([("b", Int(2))]));
+    assert_eq!(one("n { b 2; } /-{ a 1; }"), map([("b", Int(2))]));
+    assert_eq!(one("n {\n    /-a 1\n    b 2\n}"), map([("b", Int(2))]));
+    assert!(parse("n { b 2; } 3").is_err());
+    assert!(parse("n { b 2; } /-3").is_err());
+
+    // type annotations, which are skipped, with the spaces of v2
+    assert_eq!(one("(cmd)n (u8)8"), Int(8));
+    assert_eq!(one("n k=(i64)1"), map([("k", Int(1))]));
+    assert_eq!(one(r#"n ("quoted type")"v""#), str("v"));
+    assert_eq!(one(r##"n (#"raw"#)"v""##), str("v"));
+    assert_eq!(one("( cmd ) n ( u8 ) 8"), Int(8));
+    assert_eq!(one("n k = (u8) 1"), map([("k", Int(1))]));
+    assert_eq!(
+        parse("n (1)2").unwrap_err(),
+        ("expected a type name".to_owned(), (1, 4))
+    );
+    assert!(parse("n (u8 8").is_err());
+
+    // keywords of v1 are bare and of v2 start with `#`, and quoted ones are strings
+    assert_eq!(
+        one("n true false null"),
+        Seq(vec![Bool(true), Bool(false), Null])
+    );
+    assert_eq!(
+        one("n #true #false #null"),
+        Seq(vec![Bool(true), Bool(false), Null])
+    );
+    assert_eq!(
+        one(r#"n "true" "null""#),
+        Seq(vec![str("true"), str("null")])
+    );
+    match one("n #inf #-inf #nan") {
+        Seq(v) => assert!(matches!(
+            v[..],
+            [Float(a), Float(b), Float(c)] if a == f64::INFINITY && b == f64::NEG_INFINITY && c.is_nan()
+        )),
+        v => panic!("{:?}", v),
+    }
+    assert_eq!(parse("n #yes").unwrap_err().0, "unknown keyword `#yes`");
+    assert_eq!(one("n"), Bool(true));
+
+    // numbers
+    assert_eq!(
+        one("n 0x1f -0o17 0b101 1_000 +2 1.5e3 -.5"),
+        Seq(vec![
+            Int(31),
+            Int(-15),
+            Int(5),
+            Int(1000),
+            Int(2),
+            Float(1500.0),
+            Float(-0.5)
+        ])
+    );
+    assert_eq!(parse("n 0xg").unwrap_err().0, "invalid number `0xg`");
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+#[allow(deprecated)]
+fn yaml_rust_deserializer() {
+    use crate::YamlWrap;
+    use serde::Deserialize;
+    use yaml_rust::{Yaml, YamlLoader};
+
+    const CLI_YML: &str = r#"
+name: app
+args:
+    - a:
+        long: a
+        takes_value: true
+        required_if_eq: [b, x]
+        default_value_if: [b, x, "1"]
+    - b:
+        long: b
+        takes_value: true
+    - c: &opt
+        help: shared
+        takes_value: true
+    - d: *opt
+"#;
+    let docs = YamlLoader::load_from_str(CLI_YML).unwrap();
+    let app = crate::yaml_to_app(&docs[0]).expect("parse failed");
+    let m = app
+        .clone()
+        .try_get_matches_from(["app", "--b", "x", "--a", "2"])
+        .unwrap();
+    assert_eq!(m.value_of("a"), Some("2"));
+    assert!(app
+        .clone()
+        .try_get_matches_from(["app", "--b", "x"])
+        .is_err());
+    let d = app.get_arguments().find(|a| a.get_id() == "d").unwrap();
+    assert_eq!(d.get_help(), Some("shared"));
+
+    let load_err = |yml: &str| {
+        let docs = YamlLoader::load_from_str(yml).unwrap();
+        crate::yaml_to_app(&docs[0]).unwrap_err().to_string()
+    };
+    assert!(
+        load_err("name: app\nargs:\n  - a:\n      required_if_eq: [b, x, y]\n")
+            .contains("invalid length")
+    );
+    assert!(load_err("name: app\nargs:\n  - a:\n      short: ab\n").contains("char"));
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum E {
+        Unit,
+        Newtype(f64),
+        Tuple(u8, String),
+        Struct { x: i128 },
+    }
+    let es =
+        YamlLoader::load_from_str("- Unit\n- Newtype: .inf\n- Tuple: [1, 2]\n- Struct: { x: 3 }\n")
+            .unwrap();
+    let es = Vec::<E>::deserialize(YamlWrap::new(&es[0])).unwrap();
+    assert_eq!(
+        es,
+        [
+            E::Unit,
+            E::Newtype(f64::INFINITY),
+            E::Tuple(1, "2".to_owned()),
+            E::Struct { x: 3 }
+        ]
+    );
+    let err = E::deserialize(YamlWrap::new(&Yaml::Alias(0))).unwrap_err();
+    assert!(err.to_string().contains("alias of an unknown anchor"));
+}
+
+// The args are recorded as `mut_arg` with `override-arg`.
+#[cfg(all(feature = "toml", not(feature = "override-arg")))]
+#[test]
+fn builder_records_calls() {
+    const CLAP_TOML: &str = r#"
+name = "app"
+[args]
+"-c, --config [FILE] 'config file'" = { alias = "conf" }
+level = { value_parser = { type = "i8", max = 0, max_inclusive = false }, alias = ["lv", "lvl"] }
+[subcommands.test]
+auto_long = true
+args = [{ fast = { action = "set_true" } }]
+"#;
+    let (app, builder) =
+        crate::codegen::load_builder(&mut toml::Deserializer::new(CLAP_TOML)).expect("load failed");
+    assert_eq!(app.get_name(), "app");
+    assert_eq!(
+        builder.to_string(),
+        r#"clap::Command::new("app")
+    .arg(
+        clap::Arg::new("config")
+            .short('c')
+            .long("config")
+            .help("config file")
+            .value_names(&["FILE"])
+            .required(false)
+            .takes_value(true)
+            .alias("conf")
+    )
+    .arg(
+        clap::Arg::new("level")
+            .value_parser(clap::value_parser!(i8).range(-128..=-1))
+            .aliases(&["lv", "lvl"])
+    )
+    .subcommand(
+        clap::Command::new("test")
+            .arg(
+                clap::Arg::new("fast")
+                    .action(clap::ArgAction::SetTrue)
+                    .takes_value(false)
+                    .value_parser(clap::builder::ValueParser::bool())
+            )
+    )
+    .mut_subcommand("test", |c| {
+        c
+            .mut_arg("fast", |a| {
+                a
+                    .long("fast")
+            })
+    })"#
+    );
+
+    let source = crate::Source::new(
+        "name = \"app\"\n[args]\na = { index = 1 }\nb = { index = 1 }\n",
+        crate::Format::Toml,
+    );
+    #[cfg(debug_assertions)]
+    assert!(source
+        .builder()
+        .unwrap_err()
+        .message
+        .contains("args.a.index"));
+}
+
+#[test]
+fn to_builder_rust_reads_getters() {
+    use clap::{Arg, PossibleValue};
+
+    let app = Command::new("app")
+        .visible_alias("a")
+        .alias("hidden")
+        .allow_negative_numbers(true)
+        .arg(
+            Arg::new("mode")
+                .long("mode")
+                .takes_value(true)
+                .possible_values([PossibleValue::new("fast").help("go fast"), "slow".into()]),
+        )
+        .arg(
+            Arg::new("path")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .required(true),
+        )
+        .subcommand(Command::new("test").short_flag('t'));
+    assert_eq!(
+        crate::codegen::to_builder_rust(&app),
+        r#"clap::Command::new("app")
+    .alias("hidden")
+    .visible_alias("a")
+    .allow_negative_numbers(true)
+    .arg(
+        clap::Arg::new("mode")
+            .long("mode")
+            .possible_values([clap::PossibleValue::new("fast").help("go fast"), clap::PossibleValue::new("slow")])
+            .takes_value(true)
+    )
+    .arg(
+        clap::Arg::new("path")
+            .required(true)
+            .value_parser(clap::value_parser!(std::path::PathBuf))
+    )
+    .subcommand(
+        clap::Command::new("test")
+            .short_flag('t')
+    )"#
+    );
+}
+
+#[test]
+fn to_derive_rust_infers_types() {
+    const CLAP_TOML: &str = r#"
+name = "my-app"
+about = "an app"
+[args]
+verbose = { short = "v", multiple_occurrences = true }
+quiet = { short = "q" }
+jobs = { long = "jobs", value_parser = "u32", takes_value = true, default_value = "1" }
+type = { long = "type", takes_value = true }
+files = { multiple_values = true, required = true }
+[subcommands.run_tests.args]
+fast = { long = "fast", action = "set_true" }
+[subcommands.clean]
+about = "clean up"
+"#;
+    let app = crate::load(&mut toml::Deserializer::new(CLAP_TOML)).expect("load failed");
+    assert_eq!(
+        crate::codegen::to_derive_rust(&app),
+        r#"#[derive(Debug, clap::Parser)]
+#[clap(name = "my-app", about = "an app")]
+pub struct MyApp {
+    #[clap(short = 'v', action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    #[clap(short = 'q', action = clap::ArgAction::SetTrue)]
+    pub quiet: bool,
+    #[clap(default_value = "1", long = "jobs", value_parser = clap::value_parser!(u32))]
+    pub jobs: u32,
+    #[clap(long = "type", value_parser)]
+    pub r#type: Option<String>,
+    #[clap(multiple_values = true, required = true, value_parser)]
+    pub files: Vec<String>,
+    #[clap(subcommand)]
+    pub command: Option<MyAppSubcommand>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum MyAppSubcommand {
+    #[clap(name = "run_tests")]
+    RunTests(MyAppRunTests),
+    #[clap(about = "clean up")]
+    Clean,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct MyAppRunTests {
+    #[clap(
+        action = clap::ArgAction::SetTrue,
+        long = "fast",
+        value_parser = clap::value_parser!(bool),
+    )]
+    pub fast: bool,
+}
+"#
+    );
+}
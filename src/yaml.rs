@@ -1,7 +1,7 @@
 use serde::{
     de::{
         value::{MapDeserializer, SeqDeserializer},
-        Error as _, IntoDeserializer, Unexpected,
+        EnumAccess, Error as _, IntoDeserializer, Unexpected, VariantAccess, Visitor,
     },
     Deserializer,
 };
@@ -45,7 +45,9 @@ pub fn yaml_to_app(yaml: &Yaml) -> Result<clap::Command<'_>, Error> {
 
 /// Wrapper to use [`Yaml`] as [`Deserializer`].
 ///
-/// Currently this implement functions in [`Deserializer`] that is only needed in deserializing into `Command`.
+/// The aliases are resolved by [`YamlLoader`](yaml_rust::YamlLoader) while loading,
+/// and a [`Yaml::Alias`] left in a hand-made [`Yaml`] fails as the anchor is unknown.
+/// A map can also be given as a list of maps, as written for `load_yaml!` of clap 2.
 /// Recommend to use [`yaml_to_app`] instead.
 pub struct YamlWrap<'a> {
     yaml: &'a yaml_rust::Yaml,
@@ -64,7 +66,9 @@ pub enum Error {
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Debug::fmt(self, f)
+        match self {
+            Error::Custom(message) => f.write_str(message),
+        }
     }
 }
 impl std::error::Error for Error {}
@@ -104,7 +108,7 @@ fn as_invalid(y: &Yaml, expected: &str) -> Error {
             Yaml::Boolean(b) => Unexpected::Bool(*b),
             Yaml::Array(_) => Unexpected::Seq,
             Yaml::Hash(_) => Unexpected::Map,
-            Yaml::Alias(_) => todo!(),
+            Yaml::Alias(_) => Unexpected::Other("alias of an unknown anchor"),
             Yaml::Null => Unexpected::Unit,
             Yaml::BadValue => Unexpected::Other("BadValue"),
         },
@@ -128,16 +132,14 @@ impl<'de> Deserializer<'de> for YamlWrap<'de> {
         V: serde::de::Visitor<'de>,
     {
         match self.yaml {
-            yaml_rust::Yaml::Real(s) => {
-                visitor.visit_f64(s.parse::<f64>().map_err(|e| Error::Custom(e.to_string()))?)
-            }
+            yaml_rust::Yaml::Real(_) => self.deserialize_f64(visitor),
             yaml_rust::Yaml::Integer(i) => visitor.visit_i64(*i),
-            yaml_rust::Yaml::String(s) => visitor.visit_str(s),
+            yaml_rust::Yaml::String(s) => visitor.visit_borrowed_str(s),
             yaml_rust::Yaml::Boolean(b) => visitor.visit_bool(*b),
             yaml_rust::Yaml::Array(_) => self.deserialize_seq(visitor), //visitor.visit_seq(a),
             yaml_rust::Yaml::Hash(_) => self.deserialize_map(visitor),
-            yaml_rust::Yaml::Alias(_) => todo!(),
-            yaml_rust::Yaml::Null => visitor.visit_none(),
+            yaml_rust::Yaml::Alias(_) => Err(as_invalid(self.yaml, "any")),
+            yaml_rust::Yaml::Null => visitor.visit_unit(),
             yaml_rust::Yaml::BadValue => Err(as_invalid(self.yaml, "any")),
         }
     }
@@ -157,81 +159,79 @@ impl<'de> Deserializer<'de> for YamlWrap<'de> {
     de_num!(deserialize_i16, visit_i16);
     de_num!(deserialize_i32, visit_i32);
     de_num!(deserialize_i64, visit_i64);
-    de_num!(deserialize_u8, visit_i8);
+    de_num!(deserialize_u8, visit_u8);
     de_num!(deserialize_u16, visit_u16);
     de_num!(deserialize_u32, visit_u32);
     de_num!(deserialize_u64, visit_u64);
+    de_num!(deserialize_i128, visit_i128);
+    de_num!(deserialize_u128, visit_u128);
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_f32(
-            self.yaml
-                .as_f64()
-                .ok_or_else(|| as_invalid(self.yaml, "f32"))? as f32,
-        )
+        visitor.visit_f32(as_f64(self.yaml).ok_or_else(|| as_invalid(self.yaml, "f32"))? as f32)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_f64(
-            self.yaml
-                .as_f64()
-                .ok_or_else(|| as_invalid(self.yaml, "f64"))?,
-        )
+        visitor.visit_f64(as_f64(self.yaml).ok_or_else(|| as_invalid(self.yaml, "f64"))?)
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_char(
-            self.yaml
-                .as_str()
-                .ok_or_else(|| as_invalid(self.yaml, "char"))?
-                .chars()
-                .next()
-                .ok_or_else(|| as_invalid(self.yaml, "char"))?,
-        )
+        let mut chars = self.yaml.as_str().unwrap_or_default().chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(as_invalid(self.yaml, "char")),
+        }
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        let s = self.yaml.as_str();
-        visitor.visit_borrowed_str(s.ok_or_else(|| as_invalid(self.yaml, "str"))?)
+        match self.yaml {
+            Yaml::String(s) | Yaml::Real(s) => visitor.visit_borrowed_str(s),
+            Yaml::Integer(i) => visitor.visit_i64(*i),
+            Yaml::Boolean(b) => visitor.visit_bool(*b),
+            _ => Err(as_invalid(self.yaml, "str")),
+        }
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_string(
-            self.yaml
-                .as_str()
-                .ok_or_else(|| as_invalid(self.yaml, "string"))?
-                .to_string(),
-        )
+        match self.yaml {
+            Yaml::String(s) | Yaml::Real(s) => visitor.visit_str(s),
+            Yaml::Integer(i) => visitor.visit_string(i.to_string()),
+            Yaml::Boolean(b) => visitor.visit_string(b.to_string()),
+            _ => Err(as_invalid(self.yaml, "string")),
+        }
     }
 
-    ///not supported
-    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    /// Bytes of a string.
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        unimplemented!()
+        match self.yaml {
+            Yaml::String(s) => visitor.visit_borrowed_bytes(s.as_bytes()),
+            _ => Err(as_invalid(self.yaml, "bytes")),
+        }
     }
 
-    ///not supported
-    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    /// Bytes of a string.
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_bytes(visitor)
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -256,62 +256,59 @@ impl<'de> Deserializer<'de> for YamlWrap<'de> {
         }
     }
 
-    ///unimplemented
     fn deserialize_unit_struct<V>(
         self,
         _name: &'static str,
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        todo!()
+        self.deserialize_unit(visitor)
     }
 
-    ///unimplemented
     fn deserialize_newtype_struct<V>(
         self,
         _name: &'static str,
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        todo!()
+        visitor.visit_newtype_struct(self)
     }
 
-    ///unimplemented
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
         if let Some(n) = self.yaml.as_vec() {
-            let seq = SeqDeserializer::new(n.iter().map(|y| YamlWrap { yaml: y }));
-            visitor.visit_seq(seq)
+            let mut seq = SeqDeserializer::new(n.iter().map(|y| YamlWrap { yaml: y }));
+            let value = visitor.visit_seq(&mut seq)?;
+            seq.end()?;
+            Ok(value)
         } else {
             Err(as_invalid(self.yaml, "seq"))
         }
     }
 
-    ///unimplemented
-    fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        todo!()
+        self.deserialize_seq(visitor)
     }
 
-    ///unimplemented
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
         _len: usize,
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        todo!()
+        self.deserialize_seq(visitor)
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -320,39 +317,42 @@ impl<'de> Deserializer<'de> for YamlWrap<'de> {
     {
         match self.yaml {
             Yaml::Hash(h) => {
-                let m = MapDeserializer::new(
+                let mut m = MapDeserializer::new(
                     h.iter()
                         .map(|(k, v)| (YamlWrap { yaml: k }, YamlWrap { yaml: v })),
                 );
-                visitor.visit_map(m)
+                let value = visitor.visit_map(&mut m)?;
+                m.end()?;
+                Ok(value)
             }
             Yaml::Array(a) => {
                 let x = a
                     .iter()
                     .map(|y| y.as_hash().ok_or_else(|| as_invalid(self.yaml, "map")))
                     .collect::<Result<Vec<_>, _>>()?;
-                let m = MapDeserializer::new(
+                let mut m = MapDeserializer::new(
                     x.into_iter()
                         .flat_map(|x| x.iter())
                         .map(|(k, v)| (YamlWrap { yaml: k }, YamlWrap { yaml: v })),
                 );
-                visitor.visit_map(m)
+                let value = visitor.visit_map(&mut m)?;
+                m.end()?;
+                Ok(value)
             }
             _ => Err(as_invalid(self.yaml, "map")),
         }
     }
 
-    ///unimplemented
     fn deserialize_struct<V>(
         self,
         _name: &'static str,
         _fields: &'static [&'static str],
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        todo!()
+        self.deserialize_map(visitor)
     }
 
     fn deserialize_enum<V>(
@@ -364,10 +364,19 @@ impl<'de> Deserializer<'de> for YamlWrap<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        if let Some(s) = self.yaml.as_str() {
-            visitor.visit_enum(s.into_deserializer())
-        } else {
-            Err(as_invalid(self.yaml, "enum"))
+        match self.yaml {
+            Yaml::String(_) => visitor.visit_enum(EnumWrap {
+                variant: self.yaml,
+                value: None,
+            }),
+            Yaml::Hash(h) if h.len() == 1 => {
+                let (variant, value) = h.front().expect("checked the length");
+                visitor.visit_enum(EnumWrap {
+                    variant,
+                    value: Some(value),
+                })
+            }
+            _ => Err(as_invalid(self.yaml, "enum")),
         }
     }
 
@@ -385,3 +394,84 @@ impl<'de> Deserializer<'de> for YamlWrap<'de> {
         self.deserialize_any(visitor)
     }
 }
+
+/// Float of a real or an integer, including `.inf` and `.nan`.
+fn as_f64(y: &Yaml) -> Option<f64> {
+    match y {
+        Yaml::Integer(i) => Some(*i as f64),
+        _ => y.as_f64(),
+    }
+}
+
+/// Variant of an enum, written as the name or a map from the name to the value.
+struct EnumWrap<'a> {
+    variant: &'a Yaml,
+    value: Option<&'a Yaml>,
+}
+
+impl<'de> EnumAccess<'de> for EnumWrap<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(YamlWrap { yaml: self.variant })?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for EnumWrap<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        match self.value {
+            None | Some(Yaml::Null) => Ok(()),
+            Some(y) => Err(as_invalid(y, "unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        match self.value {
+            Some(yaml) => seed.deserialize(YamlWrap { yaml }),
+            None => Err(Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Some(yaml) => YamlWrap { yaml }.deserialize_seq(visitor),
+            None => Err(Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Some(yaml) => YamlWrap { yaml }.deserialize_map(visitor),
+            None => Err(Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}